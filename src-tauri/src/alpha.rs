@@ -0,0 +1,102 @@
+use crate::image_io::neighbors;
+use image::RgbaImage;
+
+// Snap alpha below `low` to 0 and above `high` to 255, then drop opaque specks
+// with fewer than `min_neighbors` visible 8-neighbors (0 disables that pass)
+pub fn clean_alpha(img: &mut RgbaImage, low: u8, high: u8, min_neighbors: u8) {
+    for pixel in img.pixels_mut() {
+        if pixel[3] < low {
+            pixel[3] = 0;
+        } else if pixel[3] > high {
+            pixel[3] = 255;
+        }
+    }
+
+    if min_neighbors == 0 {
+        return;
+    }
+
+    let (width, height) = img.dimensions();
+    let isolated: Vec<(u32, u32)> = img
+        .enumerate_pixels()
+        .filter(|(x, y, pixel)| {
+            pixel[3] > 0
+                && neighbors(*x, *y, width, height)
+                    .filter(|&(nx, ny)| img.get_pixel(nx, ny)[3] > 0)
+                    .count()
+                    < min_neighbors as usize
+        })
+        .map(|(x, y, _)| (x, y))
+        .collect();
+
+    for (x, y) in isolated {
+        img.get_pixel_mut(x, y)[3] = 0;
+    }
+}
+
+pub fn premultiply(img: &mut RgbaImage) {
+    for pixel in img.pixels_mut() {
+        let a = pixel[3] as u32;
+        for c in 0..3 {
+            pixel[c] = ((pixel[c] as u32 * a + 127) / 255) as u8;
+        }
+    }
+}
+
+pub fn unpremultiply(img: &mut RgbaImage) {
+    for pixel in img.pixels_mut() {
+        let a = pixel[3] as u32;
+        if a == 0 {
+            continue;
+        }
+        for c in 0..3 {
+            pixel[c] = ((pixel[c] as u32 * 255 + a / 2) / a).min(255) as u8;
+        }
+    }
+}
+
+// Spread edge colors into fully transparent neighbors (alpha stays 0) for
+// `radius` passes, so bilinear sampling at sprite edges doesn't pull in black
+pub fn bleed_colors(img: &mut RgbaImage, radius: u32) {
+    let (width, height) = img.dimensions();
+    let mut has_color: Vec<bool> = img.pixels().map(|p| p[3] > 0).collect();
+
+    for _ in 0..radius {
+        let mut filled = Vec::new();
+
+        for y in 0..height {
+            for x in 0..width {
+                if has_color[(y * width + x) as usize] {
+                    continue;
+                }
+
+                let mut sum = [0u32; 3];
+                let mut count = 0u32;
+                for (nx, ny) in neighbors(x, y, width, height) {
+                    if has_color[(ny * width + nx) as usize] {
+                        let p = img.get_pixel(nx, ny);
+                        for c in 0..3 {
+                            sum[c] += p[c] as u32;
+                        }
+                        count += 1;
+                    }
+                }
+
+                if count > 0 {
+                    filled.push((x, y, sum.map(|v| ((v + count / 2) / count) as u8)));
+                }
+            }
+        }
+
+        if filled.is_empty() {
+            break;
+        }
+        for (x, y, rgb) in filled {
+            let pixel = img.get_pixel_mut(x, y);
+            pixel[0] = rgb[0];
+            pixel[1] = rgb[1];
+            pixel[2] = rgb[2];
+            has_color[(y * width + x) as usize] = true;
+        }
+    }
+}
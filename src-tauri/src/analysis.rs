@@ -0,0 +1,661 @@
+use image::imageops::{self, FilterType};
+use image::{GrayImage, Luma, Rgba, RgbaImage};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::atlas_packer::SourceSprite;
+use crate::color_profile::srgb_to_linear;
+use crate::error::AppError;
+use crate::maxrects::Region;
+use crate::quantize::{self, QuantizeSettings};
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    // Gradient hash: fast, robust to small color shifts
+    #[default]
+    Dhash,
+    // DCT hash: more tolerant of blur and rescaling
+    Phash,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct DuplicateOptions {
+    pub algorithm: HashAlgorithm,
+    // Maximum Hamming distance (of 64 bits) for two frames to count as duplicates
+    pub threshold: u32,
+}
+
+impl Default for DuplicateOptions {
+    fn default() -> Self {
+        DuplicateOptions {
+            algorithm: HashAlgorithm::Dhash,
+            threshold: 4,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct DuplicateGroup {
+    pub names: Vec<String>,
+    // Every member is pixel-identical to the first
+    pub exact: bool,
+    // Largest Hamming distance from the first member
+    pub max_distance: u32,
+}
+
+// Luma with transparency composited over black, so a sprite and its
+// background-only twin don't hash the same
+fn flatten(img: &RgbaImage) -> GrayImage {
+    GrayImage::from_fn(img.width(), img.height(), |x, y| {
+        let p = img.get_pixel(x, y);
+        let luma = (p[0] as u32 * 299 + p[1] as u32 * 587 + p[2] as u32 * 114) / 1000;
+        Luma([(luma * p[3] as u32 / 255) as u8])
+    })
+}
+
+fn dhash(gray: &GrayImage) -> u64 {
+    let small = imageops::resize(gray, 9, 8, FilterType::Triangle);
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let bit = small.get_pixel(x, y)[0] < small.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | bit as u64;
+        }
+    }
+    hash
+}
+
+fn phash(gray: &GrayImage) -> u64 {
+    const N: usize = 32;
+    let small = imageops::resize(gray, N as u32, N as u32, FilterType::Triangle);
+    let pixel = |x: usize, y: usize| small.get_pixel(x as u32, y as u32)[0] as f32;
+
+    // Lowest 8x8 DCT-II frequencies
+    let basis =
+        |k: usize, n: usize| (std::f32::consts::PI / N as f32 * (n as f32 + 0.5) * k as f32).cos();
+    let mut coeffs = [0f32; 64];
+    for v in 0..8 {
+        for u in 0..8 {
+            let mut sum = 0f32;
+            for y in 0..N {
+                for x in 0..N {
+                    sum += pixel(x, y) * basis(u, x) * basis(v, y);
+                }
+            }
+            coeffs[v * 8 + u] = sum;
+        }
+    }
+
+    // The DC term only encodes overall brightness, so it's left out of the median
+    let mut sorted = coeffs[1..].to_vec();
+    sorted.sort_by(f32::total_cmp);
+    let median = sorted[sorted.len() / 2];
+    coeffs
+        .iter()
+        .fold(0u64, |hash, &c| (hash << 1) | (c > median) as u64)
+}
+
+pub fn perceptual_hash(img: &RgbaImage, algorithm: HashAlgorithm) -> u64 {
+    let gray = flatten(img);
+    match algorithm {
+        HashAlgorithm::Dhash => dhash(&gray),
+        HashAlgorithm::Phash => phash(&gray),
+    }
+}
+
+fn find(parent: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parent[root] != root {
+        root = parent[root];
+    }
+    parent[i] = root;
+    root
+}
+
+// Groups of near-identical sprites, in input order. Frames of different sizes
+// are never grouped, since they can't replace each other in an atlas.
+pub fn find_duplicates(
+    sprites: &[SourceSprite],
+    options: &DuplicateOptions,
+) -> Vec<DuplicateGroup> {
+    let images: Vec<RgbaImage> = sprites.iter().map(|s| s.image.to_rgba8()).collect();
+    let hashes: Vec<u64> = images
+        .iter()
+        .map(|img| perceptual_hash(img, options.algorithm))
+        .collect();
+
+    let mut parent: Vec<usize> = (0..sprites.len()).collect();
+    for i in 0..images.len() {
+        for j in i + 1..images.len() {
+            if images[i].dimensions() == images[j].dimensions()
+                && (hashes[i] ^ hashes[j]).count_ones() <= options.threshold
+            {
+                let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+                parent[b.max(a)] = a.min(b);
+            }
+        }
+    }
+
+    let mut members: Vec<Vec<usize>> = vec![Vec::new(); sprites.len()];
+    for i in 0..sprites.len() {
+        let root = find(&mut parent, i);
+        members[root].push(i);
+    }
+
+    members
+        .into_iter()
+        .filter(|group| group.len() > 1)
+        .map(|group| {
+            let first = group[0];
+            DuplicateGroup {
+                exact: group.iter().all(|&i| images[i] == images[first]),
+                max_distance: group
+                    .iter()
+                    .map(|&i| (hashes[i] ^ hashes[first]).count_ones())
+                    .max()
+                    .unwrap_or(0),
+                names: group.iter().map(|&i| sprites[i].name.clone()).collect(),
+            }
+        })
+        .collect()
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct GroupingOptions {
+    // Page size a group should fit in; larger clusters are split
+    pub max_atlas_size: u32,
+    // Untagged clusters smaller than this are folded into "misc"
+    pub min_group_size: usize,
+}
+
+impl Default for GroupingOptions {
+    fn default() -> Self {
+        GroupingOptions {
+            max_atlas_size: 2048,
+            min_group_size: 4,
+        }
+    }
+}
+
+pub struct SpriteInfo {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub group: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct GroupSuggestion {
+    pub name: String,
+    pub sprites: Vec<String>,
+    // Summed sprite area in pixels
+    pub total_area: u64,
+    pub reason: String,
+}
+
+fn size_class(info: &SpriteInfo) -> &'static str {
+    match info.width.max(info.height) {
+        0..=64 => "small",
+        65..=256 => "medium",
+        _ => "large",
+    }
+}
+
+fn shape_class(info: &SpriteInfo) -> &'static str {
+    let aspect = info.width as f32 / info.height.max(1) as f32;
+    if aspect > 2.0 {
+        "wide"
+    } else if aspect < 0.5 {
+        "tall"
+    } else {
+        "square"
+    }
+}
+
+struct Cluster {
+    name: String,
+    reason: String,
+    tagged: bool,
+    // Sprite indices in first-seen order
+    members: Vec<usize>,
+}
+
+// Proposed atlas split: tagged sprites keep their group, the rest cluster by
+// size and aspect. Groups over one page's area become numbered parts.
+pub fn suggest_groups(sprites: &[SpriteInfo], options: &GroupingOptions) -> Vec<GroupSuggestion> {
+    let mut clusters: Vec<Cluster> = Vec::new();
+    for (i, sprite) in sprites.iter().enumerate() {
+        let (name, reason) = match &sprite.group {
+            Some(group) => (group.clone(), "tagged".to_string()),
+            None => {
+                let (size, shape) = (size_class(sprite), shape_class(sprite));
+                (
+                    format!("{}-{}", size, shape),
+                    format!("{} {} sprites", size, shape),
+                )
+            }
+        };
+        match clusters.iter_mut().find(|c| c.name == name) {
+            Some(cluster) => cluster.members.push(i),
+            None => clusters.push(Cluster {
+                name,
+                reason,
+                tagged: sprite.group.is_some(),
+                members: vec![i],
+            }),
+        }
+    }
+
+    let mut misc = Vec::new();
+    clusters.retain(|cluster| {
+        let keep = cluster.tagged || cluster.members.len() >= options.min_group_size;
+        if !keep {
+            misc.extend_from_slice(&cluster.members);
+        }
+        keep
+    });
+    if !misc.is_empty() {
+        misc.sort_unstable();
+        clusters.push(Cluster {
+            name: "misc".to_string(),
+            reason: "small untagged clusters".to_string(),
+            tagged: false,
+            members: misc,
+        });
+    }
+
+    let area = |i: usize| sprites[i].width as u64 * sprites[i].height as u64;
+    let page_area = options.max_atlas_size as u64 * options.max_atlas_size as u64;
+    let mut suggestions = Vec::new();
+    for Cluster {
+        name,
+        reason,
+        members,
+        ..
+    } in clusters
+    {
+        let mut parts: Vec<Vec<usize>> = vec![Vec::new()];
+        let mut part_area = 0u64;
+        for i in members {
+            let current = parts.last_mut().unwrap();
+            if !current.is_empty() && part_area + area(i) > page_area {
+                parts.push(Vec::new());
+                part_area = 0;
+            }
+            parts.last_mut().unwrap().push(i);
+            part_area += area(i);
+        }
+
+        let numbered = parts.len() > 1;
+        for (n, part) in parts.into_iter().enumerate() {
+            suggestions.push(GroupSuggestion {
+                name: if numbered {
+                    format!("{}-{}", name, n + 1)
+                } else {
+                    name.clone()
+                },
+                total_area: part.iter().map(|&i| area(i)).sum(),
+                sprites: part.iter().map(|&i| sprites[i].name.clone()).collect(),
+                reason: reason.clone(),
+            });
+        }
+    }
+
+    suggestions
+}
+
+#[derive(Serialize)]
+pub struct SpriteOverdraw {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub opaque_pixels: u64,
+    pub opaque_ratio: f32,
+    // Fully transparent pixels that still get rasterized with the quad
+    pub transparent_area: u64,
+}
+
+pub struct OverdrawReport {
+    // Most transparent area first
+    pub sprites: Vec<SpriteOverdraw>,
+    pub total_transparent_area: u64,
+    pub heatmap: RgbaImage,
+}
+
+// Per-frame transparent area of a packed atlas. The heatmap keeps visible
+// pixels as dim grey and paints each frame's empty pixels red, brighter for
+// frames that are mostly empty.
+pub fn overdraw_report(atlas: &RgbaImage, frames: &[(String, Region)]) -> OverdrawReport {
+    let mut heatmap = RgbaImage::new(atlas.width(), atlas.height());
+    let mut sprites = Vec::with_capacity(frames.len());
+
+    for (name, region) in frames {
+        let right = region.right().min(atlas.width());
+        let bottom = region.bottom().min(atlas.height());
+        let area =
+            (right.saturating_sub(region.x) as u64) * (bottom.saturating_sub(region.y) as u64);
+
+        let mut opaque_pixels = 0u64;
+        for y in region.y..bottom {
+            for x in region.x..right {
+                if atlas.get_pixel(x, y)[3] > 0 {
+                    opaque_pixels += 1;
+                }
+            }
+        }
+        let opaque_ratio = if area > 0 {
+            opaque_pixels as f32 / area as f32
+        } else {
+            1.0
+        };
+
+        let heat = (255.0 * (1.0 - opaque_ratio)).round() as u8;
+        for y in region.y..bottom {
+            for x in region.x..right {
+                let p = atlas.get_pixel(x, y);
+                *heatmap.get_pixel_mut(x, y) = if p[3] > 0 {
+                    let l = (p[0] as u32 * 299 + p[1] as u32 * 587 + p[2] as u32 * 114) / 3000;
+                    Rgba([l as u8, l as u8, l as u8, 255])
+                } else {
+                    Rgba([heat.max(64), 0, 0, 160])
+                };
+            }
+        }
+
+        sprites.push(SpriteOverdraw {
+            name: name.clone(),
+            width: region.width,
+            height: region.height,
+            opaque_pixels,
+            opaque_ratio,
+            transparent_area: area - opaque_pixels,
+        });
+    }
+
+    sprites.sort_by_key(|s| std::cmp::Reverse(s.transparent_area));
+    OverdrawReport {
+        total_transparent_area: sprites.iter().map(|s| s.transparent_area).sum(),
+        sprites,
+        heatmap,
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct ColorAnalysisOptions {
+    pub dominant_count: usize,
+    // Buckets closer than this (RGB distance) are merged into one dominant color
+    pub merge_distance: f32,
+}
+
+impl Default for ColorAnalysisOptions {
+    fn default() -> Self {
+        ColorAnalysisOptions {
+            dominant_count: 8,
+            merge_distance: 24.0,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ChannelHistogram {
+    // 256 bins each; r, g and b count visible pixels only
+    pub r: Vec<u64>,
+    pub g: Vec<u64>,
+    pub b: Vec<u64>,
+    pub a: Vec<u64>,
+}
+
+#[derive(Serialize, Clone, Copy)]
+pub struct DominantColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub count: u64,
+    // Share of the pixels that were clustered
+    pub ratio: f32,
+}
+
+#[derive(Serialize)]
+pub struct AlphaDistribution {
+    pub transparent: u64,
+    pub partial: u64,
+    pub opaque: u64,
+}
+
+#[derive(Serialize)]
+pub struct ColorAnalysis {
+    pub histogram: ChannelHistogram,
+    // Distinct RGBA values among visible pixels
+    pub unique_colors: usize,
+    // Most common first
+    pub dominant_colors: Vec<DominantColor>,
+    pub alpha: AlphaDistribution,
+}
+
+// Group colors into 5-bit-per-channel buckets, then fold each bucket into an
+// earlier, more common one within `merge_distance`. Returns the `max` largest
+// clusters with their mean color.
+pub fn cluster_colors(
+    colors: impl Iterator<Item = [u8; 3]>,
+    max: usize,
+    merge_distance: f32,
+) -> Vec<DominantColor> {
+    let mut buckets: HashMap<u16, (u64, [u64; 3])> = HashMap::new();
+    let mut total = 0u64;
+    for c in colors {
+        let key = ((c[0] as u16 >> 3) << 10) | ((c[1] as u16 >> 3) << 5) | (c[2] as u16 >> 3);
+        let bucket = buckets.entry(key).or_insert((0, [0; 3]));
+        bucket.0 += 1;
+        for (sum, v) in bucket.1.iter_mut().zip(c) {
+            *sum += v as u64;
+        }
+        total += 1;
+    }
+
+    let mut buckets: Vec<(u64, [u64; 3])> = buckets.into_values().collect();
+    buckets.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+
+    let mean = |(count, sum): &(u64, [u64; 3])| sum.map(|s| s as f32 / *count as f32);
+    let mut clusters: Vec<(u64, [u64; 3])> = Vec::new();
+    for bucket in buckets {
+        let color = mean(&bucket);
+        let near = clusters.iter_mut().find(|cluster| {
+            let c = mean(cluster);
+            let d: f32 = (0..3).map(|i| (c[i] - color[i]).powi(2)).sum();
+            d.sqrt() < merge_distance
+        });
+        match near {
+            Some(cluster) => {
+                cluster.0 += bucket.0;
+                for (sum, v) in cluster.1.iter_mut().zip(bucket.1) {
+                    *sum += v;
+                }
+            }
+            None => clusters.push(bucket),
+        }
+    }
+
+    clusters.sort_by_key(|cluster| std::cmp::Reverse(cluster.0));
+    clusters
+        .iter()
+        .take(max)
+        .map(|cluster| {
+            let [r, g, b] = mean(cluster).map(|v| v.round() as u8);
+            DominantColor {
+                r,
+                g,
+                b,
+                count: cluster.0,
+                ratio: cluster.0 as f32 / total.max(1) as f32,
+            }
+        })
+        .collect()
+}
+
+// Color statistics of a sprite, for picking quantization settings and
+// chroma-key colors. Fully transparent pixels only count toward alpha.
+pub fn analyze_colors(img: &RgbaImage, options: &ColorAnalysisOptions) -> ColorAnalysis {
+    let mut histogram = ChannelHistogram {
+        r: vec![0; 256],
+        g: vec![0; 256],
+        b: vec![0; 256],
+        a: vec![0; 256],
+    };
+    let mut alpha = AlphaDistribution {
+        transparent: 0,
+        partial: 0,
+        opaque: 0,
+    };
+    let mut unique = HashSet::new();
+
+    for p in img.pixels() {
+        histogram.a[p[3] as usize] += 1;
+        match p[3] {
+            0 => {
+                alpha.transparent += 1;
+                continue;
+            }
+            255 => alpha.opaque += 1,
+            _ => alpha.partial += 1,
+        }
+        histogram.r[p[0] as usize] += 1;
+        histogram.g[p[1] as usize] += 1;
+        histogram.b[p[2] as usize] += 1;
+        unique.insert(u32::from_be_bytes(p.0));
+    }
+
+    let visible = img
+        .pixels()
+        .filter(|p| p[3] > 0)
+        .map(|p| [p[0], p[1], p[2]]);
+    ColorAnalysis {
+        histogram,
+        unique_colors: unique.len(),
+        dominant_colors: cluster_colors(visible, options.dominant_count, options.merge_distance),
+        alpha,
+    }
+}
+
+// Mean color difference people start to notice
+pub const DEFAULT_WARN_DELTA_E: f32 = 2.3;
+
+#[derive(Serialize)]
+pub struct SpriteQuantization {
+    pub name: String,
+    // Distinct visible colors before and after mapping to the palette
+    pub unique_colors: usize,
+    pub remaining_colors: usize,
+    // CIE76 ΔE between visible pixels and their palette colors, undithered
+    pub mean_delta_e: f32,
+    pub max_delta_e: f32,
+    // Mean ΔE above the warning threshold
+    pub warning: bool,
+}
+
+#[derive(Serialize)]
+pub struct QuantizationReport {
+    pub palette_size: usize,
+    // Worst first
+    pub sprites: Vec<SpriteQuantization>,
+}
+
+// CIELAB (D65) of an sRGB color
+fn to_lab(c: [u8; 3]) -> [f32; 3] {
+    let [r, g, b] = c.map(|v| srgb_to_linear(v as f32 / 255.0));
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+    let f = |t: f32| {
+        if t > 216.0 / 24389.0 {
+            t.cbrt()
+        } else {
+            (24389.0 / 27.0 * t + 16.0) / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+fn delta_e(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a.iter()
+        .zip(&b)
+        .map(|(x, y)| (x - y) * (x - y))
+        .sum::<f32>()
+        .sqrt()
+}
+
+fn quantization_loss(
+    name: &str,
+    img: &RgbaImage,
+    palette: &[(Rgba<u8>, [f32; 3])],
+    warn_delta_e: f32,
+) -> SpriteQuantization {
+    // Palette entry and ΔE per distinct color; sprites repeat colors a lot
+    let mut mapped: HashMap<Rgba<u8>, (usize, f32)> = HashMap::new();
+    let (mut sum, mut max, mut count) = (0.0f64, 0.0f32, 0u64);
+    for p in img.pixels().filter(|p| p[3] > 0) {
+        let (_, error) = *mapped.entry(*p).or_insert_with(|| {
+            let distance = |c: &Rgba<u8>| {
+                (0..4)
+                    .map(|i| (c[i] as i32 - p[i] as i32).pow(2))
+                    .sum::<i32>()
+            };
+            let index = (0..palette.len())
+                .min_by_key(|&i| distance(&palette[i].0))
+                .unwrap_or(0);
+            (index, delta_e(to_lab([p[0], p[1], p[2]]), palette[index].1))
+        });
+        sum += error as f64;
+        max = max.max(error);
+        count += 1;
+    }
+
+    let mean = if count > 0 {
+        (sum / count as f64) as f32
+    } else {
+        0.0
+    };
+    let remaining: HashSet<usize> = mapped.values().map(|&(index, _)| index).collect();
+    SpriteQuantization {
+        name: name.to_string(),
+        unique_colors: mapped.len(),
+        remaining_colors: remaining.len(),
+        mean_delta_e: mean,
+        max_delta_e: max,
+        warning: mean > warn_delta_e,
+    }
+}
+
+// What quantizing the atlas with `settings` would cost each sprite: one
+// palette is built from all of them, as the atlas shares a single palette
+pub fn quantization_report(
+    sprites: &[SourceSprite],
+    settings: &QuantizeSettings,
+    warn_delta_e: f32,
+) -> Result<QuantizationReport, AppError> {
+    let images: Vec<RgbaImage> = sprites.iter().map(|s| s.image.to_rgba8()).collect();
+    let palette = quantize::shared_palette(&images, settings.quality, settings.max_colors)?;
+    if palette.is_empty() {
+        return Err(AppError::encode("Quantization produced an empty palette"));
+    }
+    let palette: Vec<(Rgba<u8>, [f32; 3])> = palette
+        .iter()
+        .map(|c| (Rgba([c.r, c.g, c.b, c.a]), to_lab([c.r, c.g, c.b])))
+        .collect();
+
+    let mut report: Vec<SpriteQuantization> = sprites
+        .par_iter()
+        .zip(&images)
+        .map(|(sprite, img)| quantization_loss(&sprite.name, img, &palette, warn_delta_e))
+        .collect();
+    report.sort_by(|a, b| b.mean_delta_e.total_cmp(&a.mean_delta_e));
+    Ok(QuantizationReport {
+        palette_size: palette.len(),
+        sprites: report,
+    })
+}
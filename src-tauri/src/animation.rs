@@ -0,0 +1,322 @@
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{imageops, Delay, Frame, Rgba, RgbaImage};
+use serde::Deserialize;
+
+use crate::effects::blend_over;
+use crate::error::AppError;
+use crate::image_io::Color;
+use crate::overlay::{apply_checkerboard, Checkerboard};
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StripDirection {
+    #[default]
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Deserialize, Default)]
+pub struct StripLayout {
+    #[serde(default)]
+    pub direction: StripDirection,
+    // Wrap into a grid; defaults to a single row (horizontal) or column
+    #[serde(default)]
+    pub columns: Option<u32>,
+    // Gap between cells
+    #[serde(default)]
+    pub spacing: u32,
+}
+
+pub struct Filmstrip {
+    pub image: RgbaImage,
+    pub frame_width: u32,
+    pub frame_height: u32,
+    pub columns: u32,
+    pub rows: u32,
+}
+
+// Frames go into equal cells sized to the largest frame, centered so frames
+// of differing sizes keep a common registration point. Horizontal strips
+// fill row by row, vertical strips column by column.
+pub fn build_filmstrip(frames: &[RgbaImage], layout: &StripLayout) -> Result<Filmstrip, AppError> {
+    if frames.is_empty() {
+        return Err(AppError::invalid("frames", "No frames to combine"));
+    }
+
+    let count = frames.len() as u32;
+    let frame_width = frames.iter().map(|f| f.width()).max().unwrap_or(1);
+    let frame_height = frames.iter().map(|f| f.height()).max().unwrap_or(1);
+
+    let (columns, rows) = match (layout.direction, layout.columns) {
+        (_, Some(columns)) => {
+            let columns = columns.clamp(1, count);
+            (columns, count.div_ceil(columns))
+        }
+        (StripDirection::Horizontal, None) => (count, 1),
+        (StripDirection::Vertical, None) => (1, count),
+    };
+
+    let width = columns * frame_width + (columns - 1) * layout.spacing;
+    let height = rows * frame_height + (rows - 1) * layout.spacing;
+    let mut image = RgbaImage::new(width, height);
+
+    for (i, frame) in frames.iter().enumerate() {
+        let i = i as u32;
+        let (column, row) = match layout.direction {
+            StripDirection::Horizontal => (i % columns, i / columns),
+            StripDirection::Vertical => (i / rows, i % rows),
+        };
+        let x = column * (frame_width + layout.spacing) + (frame_width - frame.width()) / 2;
+        let y = row * (frame_height + layout.spacing) + (frame_height - frame.height()) / 2;
+        imageops::overlay(&mut image, frame, x as i64, y as i64);
+    }
+
+    Ok(Filmstrip {
+        image,
+        frame_width,
+        frame_height,
+        columns,
+        rows,
+    })
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum PreviewFormat {
+    Gif,
+    Webp,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct PreviewOptions {
+    pub format: PreviewFormat,
+    // 0 loops forever
+    #[serde(default)]
+    pub loop_count: u16,
+    // WebP only
+    #[serde(default = "default_preview_quality")]
+    pub quality: u8,
+    // Flatten onto a checkerboard; GIF otherwise keeps only 1-bit alpha
+    #[serde(default)]
+    pub checkerboard: Option<Checkerboard>,
+}
+
+fn default_preview_quality() -> u8 {
+    80
+}
+
+// Center frames of differing sizes on one shared canvas
+fn common_canvas(frames: &[RgbaImage]) -> Vec<RgbaImage> {
+    let width = frames.iter().map(|f| f.width()).max().unwrap_or(1);
+    let height = frames.iter().map(|f| f.height()).max().unwrap_or(1);
+    frames
+        .iter()
+        .map(|frame| {
+            if frame.dimensions() == (width, height) {
+                return frame.clone();
+            }
+            let mut canvas = RgbaImage::new(width, height);
+            let x = (width - frame.width()) / 2;
+            let y = (height - frame.height()) / 2;
+            imageops::overlay(&mut canvas, frame, x as i64, y as i64);
+            canvas
+        })
+        .collect()
+}
+
+// Animated GIF / WebP of the frames, each shown for its own delay (ms)
+pub fn encode_preview(
+    frames: &[RgbaImage],
+    delays_ms: &[u32],
+    options: &PreviewOptions,
+) -> Result<Vec<u8>, AppError> {
+    if frames.is_empty() {
+        return Err(AppError::invalid("frames", "No frames to encode"));
+    }
+    let mut frames = common_canvas(frames);
+    if let Some(board) = &options.checkerboard {
+        for frame in &mut frames {
+            apply_checkerboard(frame, board);
+        }
+    }
+    let (width, height) = frames[0].dimensions();
+
+    match options.format {
+        PreviewFormat::Gif => {
+            let mut buf = Vec::new();
+            {
+                let mut encoder = GifEncoder::new_with_speed(&mut buf, 10);
+                let repeat = match options.loop_count {
+                    0 => Repeat::Infinite,
+                    n => Repeat::Finite(n),
+                };
+                encoder.set_repeat(repeat).map_err(AppError::encode)?;
+                for (frame, &delay) in frames.into_iter().zip(delays_ms) {
+                    let delay = Delay::from_numer_denom_ms(delay, 1);
+                    encoder
+                        .encode_frame(Frame::from_parts(frame, 0, 0, delay))
+                        .map_err(AppError::encode)?;
+                }
+            }
+            Ok(buf)
+        }
+        PreviewFormat::Webp => {
+            let mut config = webp::WebPConfig::new()
+                .map_err(|_| AppError::encode("Failed to initialize WebP encoder"))?;
+            config.quality = options.quality.min(100) as f32;
+
+            let mut encoder = webp::AnimEncoder::new(width, height, &config);
+            encoder.set_loop_count(options.loop_count as i32);
+            // AnimFrame takes each frame's start time rather than its duration;
+            // libwebp gives the last frame the average of the others
+            let mut timestamp = 0i32;
+            for (frame, &delay) in frames.iter().zip(delays_ms) {
+                encoder.add_frame(webp::AnimFrame::from_rgba(
+                    frame.as_raw(),
+                    width,
+                    height,
+                    timestamp,
+                ));
+                timestamp += delay as i32;
+            }
+            let data = encoder
+                .try_encode()
+                .map_err(|e| AppError::encode(format!("WebP encoding failed: {:?}", e)))?;
+            Ok(data.to_vec())
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum CompareMode {
+    // `other` drawn over `base` at `opacity`, optionally tinted
+    Onion {
+        #[serde(default = "default_onion_opacity")]
+        opacity: f32,
+        #[serde(default)]
+        tint: Option<Color>,
+    },
+    // Heatmap of per-pixel change; deltas at or below `threshold` are ignored
+    Difference {
+        #[serde(default)]
+        threshold: u8,
+    },
+}
+
+fn default_onion_opacity() -> f32 {
+    0.5
+}
+
+pub struct Comparison {
+    pub image: RgbaImage,
+    pub changed_pixels: u64,
+    pub max_delta: u8,
+}
+
+fn pixel_or_clear(img: &RgbaImage, x: u32, y: u32) -> Rgba<u8> {
+    if x < img.width() && y < img.height() {
+        *img.get_pixel(x, y)
+    } else {
+        Rgba([0, 0, 0, 0])
+    }
+}
+
+// Both frames are aligned at the top-left on a canvas covering both
+pub fn compare_frames(base: &RgbaImage, other: &RgbaImage, mode: CompareMode) -> Comparison {
+    let width = base.width().max(other.width());
+    let height = base.height().max(other.height());
+    let mut image = RgbaImage::new(width, height);
+    let mut changed_pixels = 0u64;
+    let mut max_delta = 0u8;
+
+    for (x, y, out) in image.enumerate_pixels_mut() {
+        let a = pixel_or_clear(base, x, y);
+        let b = pixel_or_clear(other, x, y);
+        let delta = (0..4).map(|c| a[c].abs_diff(b[c])).max().unwrap_or(0);
+        max_delta = max_delta.max(delta);
+
+        *out = match mode {
+            CompareMode::Onion { opacity, tint } => {
+                if delta > 0 {
+                    changed_pixels += 1;
+                }
+                let mut top = b;
+                if let Some(tint) = tint {
+                    top = Rgba([tint.r, tint.g, tint.b, b[3]]);
+                }
+                top[3] = (top[3] as f32 * opacity.clamp(0.0, 1.0)).round() as u8;
+                blend_over(top, a)
+            }
+            CompareMode::Difference { threshold } => {
+                if delta > threshold {
+                    changed_pixels += 1;
+                    // Yellow for small changes through to red for large ones
+                    Rgba([255, 255 - delta, 0, 255])
+                } else {
+                    // Unchanged pixels as a faint grey ghost of the base frame
+                    let luma =
+                        ((a[0] as u32 * 299 + a[1] as u32 * 587 + a[2] as u32 * 114) / 1000) as u8;
+                    Rgba([luma, luma, luma, a[3] / 4])
+                }
+            }
+        };
+    }
+
+    Comparison {
+        image,
+        changed_pixels,
+        max_delta,
+    }
+}
+
+pub struct SharedCrop {
+    pub frames: Vec<RgbaImage>,
+    // Top-left of the shared rect within the original frames
+    pub x: u32,
+    pub y: u32,
+    pub source_width: u32,
+    pub source_height: u32,
+}
+
+// Union of every frame's opaque bounds (alpha above `alpha_threshold`)
+fn shared_bounds(frames: &[RgbaImage], alpha_threshold: u8) -> Option<(u32, u32, u32, u32)> {
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for frame in frames {
+        for (x, y, pixel) in frame.enumerate_pixels() {
+            if pixel[3] <= alpha_threshold {
+                continue;
+            }
+            bounds = Some(match bounds {
+                Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+                None => (x, y, x, y),
+            });
+        }
+    }
+    bounds.map(|(x0, y0, x1, y1)| (x0, y0, x1 - x0 + 1, y1 - y0 + 1))
+}
+
+// Trim all frames to one shared rect so registration between frames is kept.
+// Frames are aligned at the top-left; fully transparent input keeps a 1x1 frame.
+pub fn crop_shared_bounds(frames: &[RgbaImage], alpha_threshold: u8) -> SharedCrop {
+    let source_width = frames.iter().map(|f| f.width()).max().unwrap_or(0);
+    let source_height = frames.iter().map(|f| f.height()).max().unwrap_or(0);
+    let (x, y, width, height) = shared_bounds(frames, alpha_threshold).unwrap_or((0, 0, 1, 1));
+
+    let frames = frames
+        .iter()
+        .map(|frame| {
+            let mut cropped = RgbaImage::new(width, height);
+            imageops::overlay(&mut cropped, frame, -(x as i64), -(y as i64));
+            cropped
+        })
+        .collect();
+
+    SharedCrop {
+        frames,
+        x,
+        y,
+        source_width,
+        source_height,
+    }
+}
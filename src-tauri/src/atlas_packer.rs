@@ -1,12 +1,29 @@
-use base64::{engine::general_purpose::STANDARD, Engine};
-use image::{imageops::FilterType, DynamicImage, GenericImage, RgbaImage};
+use crate::alpha::{bleed_colors, premultiply, unpremultiply};
+use crate::binary_meta;
+use crate::budget::Budget;
+use crate::encode::{self, encode_image, encode_png, OutputFormat, PngColorType};
+use crate::error::AppError;
+use crate::image_io::{decode_base64_image, decode_base64_raw, open_image, to_data_url};
+use crate::maxrects::{MaxRects, Region};
+use crate::metadata::{self, PngColorChunks};
+use crate::naming;
+use crate::natural::{natural_cmp, natural_path_cmp};
+use crate::page_profile::{self, PageProfile, SpriteProfile};
+use crate::polygon::{sprite_shape, PolygonOptions, SpriteShape};
+use crate::precompress::Precompression;
+use crate::quantize::QuantizeSettings;
+use crate::tileset::{write_tsx, TileGrid, TilesetOptions};
+use crate::transform::ResizeFilter;
+use crate::uv;
+use image::{imageops, DynamicImage, GenericImage, GenericImageView, ImageFormat, RgbaImage};
 use rectangle_pack::{
     contains_smallest_box, pack_rects, volume_heuristic, GroupedRectsToPlace, RectToInsert,
     TargetBin,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
-use std::io::Cursor;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 
 #[derive(Deserialize)]
 pub struct SpriteInput {
@@ -16,12 +33,172 @@ pub struct SpriteInput {
     pub offset_x: i32,
     #[serde(rename = "offsetY", default)]
     pub offset_y: i32,
+    // Animation this sprite is a frame of
+    #[serde(default)]
+    pub animation: Option<String>,
+    // Position within the animation; frames without one follow the indexed
+    // frames in natural name order (walk_2 before walk_10)
+    #[serde(rename = "frameIndex", default)]
+    pub frame_index: Option<u32>,
+    #[serde(rename = "frameRate", default)]
+    pub frame_rate: Option<f32>,
+    // Normal / emissive / mask textures keyed by atlas suffix, e.g. "_n"
+    #[serde(default)]
+    pub companions: BTreeMap<String, String>,
+    // Usage group such as "ui" or "characters"
+    #[serde(default)]
+    pub group: Option<String>,
+    // Sprites sharing this key stay on one page in multi-page output
+    #[serde(rename = "keepTogether", default)]
+    pub keep_together: Option<String>,
+    // Pin the sprite's top-left to these atlas coordinates
+    #[serde(rename = "fixedPosition", default)]
+    pub fixed_position: Option<Point>,
+    // Gutter around this sprite instead of the global padding, e.g. for
+    // effect textures that need more room for mipmapping
+    #[serde(default)]
+    pub padding: Option<u32>,
+    // Never quantized, e.g. UI art with subtle gradients
+    #[serde(default)]
+    pub lossless: bool,
 }
 
-#[derive(Serialize)]
+#[derive(Deserialize, Clone, Copy)]
+pub struct Point {
+    pub x: u32,
+    pub y: u32,
+}
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AtlasOptions {
+    // Store color channels multiplied by alpha (PMA), as Spine / PIXI expect
+    pub premultiply_alpha: bool,
+    // Pixels of edge color to bleed into transparent space (0 = off)
+    pub color_bleed: u32,
+    // Resampling used when the atlas falls back to a smaller scale or is
+    // exported at another resolution
+    pub scale_filter: ResizeFilter,
+    pub format: OutputFormat,
+    // Run an oxipng pass over PNG output
+    pub lossless_optimize: bool,
+    // Reduce PNG output to an indexed palette
+    pub quantize: Option<QuantizeSettings>,
+    // Strict grid of identical cells instead of bin packing
+    pub tileset: Option<TilesetOptions>,
+    // Spill onto extra pages instead of scaling down when one page is full
+    pub multi_page: bool,
+    // Areas the packer must leave empty, e.g. content of an existing layout
+    pub reserved: Vec<Region>,
+    // Skip sprites that fail to decode and report them instead of failing
+    pub lenient: bool,
+    // Lower bound on a pinned layout's size, so an updated atlas never
+    // shrinks and normalized UVs of unmoved sprites stay valid
+    #[serde(skip)]
+    pub min_size: (u32, u32),
+    // Trim sprites to a traced outline and export it as a triangle mesh
+    pub polygon: Option<PolygonOptions>,
+    // Placement order for a MaxRects pass; unset keeps the default packer
+    pub sort: Option<SortStrategy>,
+    // Frame names in the user's chosen order; animations list their frames in
+    // this order ahead of `frameIndex`
+    pub frame_order: Vec<String>,
+    // With `quantize`, put lossless sprites on pages of their own so the rest
+    // can still be quantized; otherwise a page holding one is not quantized
+    pub lossless_page: bool,
+    // Rules tried in order: a sprite matching one goes to that profile's
+    // pages and format (e.g. opaque sprites on a JPEG page)
+    pub page_profiles: Vec<PageProfile>,
+    // Normalized UVs per frame in the JSON and a binary sidecar
+    pub uvs: bool,
+    // The JSON's content also in the compact binary format
+    pub binary_metadata: bool,
+    // Compressed copies of the JSON written next to it (atlas.json.gz / .br)
+    // wherever the app writes files itself
+    pub precompress_json: Vec<Precompression>,
+    // Exports fail with a report when the output exceeds these limits
+    pub budget: Option<Budget>,
+    // File names of multi-page output and how each JSON refers to its page
+    pub page_naming: PageNaming,
+    // How the page being rendered is referenced, set per page of a group
+    #[serde(skip)]
+    pub page_ref: Option<PageRef>,
+    // sRGB / gAMA / iCCP chunks written into PNG output; unset leaves the
+    // encoder's chunks alone
+    pub png_color: PngColorChunks,
+}
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PageNaming {
+    // File stem of numbered pages: {name} is the group's atlas name ("atlas",
+    // "atlas_ui"), {group} the group, {page} the 1-based page ({page:2} pads).
+    // Unset keeps "{name}-{page}".
+    pub pattern: Option<String>,
+    // Number pages even when a group fits on one
+    pub always_number: bool,
+    // Prepended to meta.image, e.g. "textures/" when the JSON lives elsewhere
+    pub image_prefix: String,
+    // Add meta.layer and meta.layers so a group's pages can be bound as
+    // layers of one array texture
+    pub texture_array: bool,
+}
+
+#[derive(Clone)]
+pub struct PageRef {
+    // File name of the page; meta.image is the naming prefix plus this
+    image: String,
+    layer: Option<u32>,
+    layers: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SortStrategy {
+    Area,
+    MaxSide,
+    Height,
+    Perimeter,
+    Name,
+}
+
+#[derive(Serialize, Clone)]
 pub struct AtlasOutput {
     pub image_base64: String,
     pub json: String,
+    pub size_bytes: usize,
+    // Parallel atlases sharing this layout, one per companion suffix
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub companions: Vec<CompanionAtlas>,
+    // Tiled tileset metadata, only in tileset mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tsx: Option<String>,
+    // Sprites skipped or degraded in lenient mode
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub issues: Vec<SpriteIssue>,
+    // Encoded image for callers that write to disk directly (CLI)
+    #[serde(skip)]
+    pub image_bytes: Vec<u8>,
+    // Binary UV sidecar ("<atlas>.uv"), with `uvs` on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uv_base64: Option<String>,
+    #[serde(skip)]
+    pub uv_bytes: Option<Vec<u8>>,
+    // Binary metadata ("<atlas>.bin"), with `binary_metadata` on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub binary_base64: Option<String>,
+    #[serde(skip)]
+    pub binary_bytes: Option<Vec<u8>>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct CompanionAtlas {
+    pub suffix: String,
+    pub image_name: String,
+    pub image_base64: String,
+    pub size_bytes: usize,
+    #[serde(skip)]
+    pub image_bytes: Vec<u8>,
 }
 
 #[derive(Serialize)]
@@ -42,6 +219,20 @@ struct PhaserFrame {
     pivot: Pivot,
     #[serde(rename = "offset")]
     offset: Offset,
+    // Only for sprites that override the global padding
+    #[serde(skip_serializing_if = "Option::is_none")]
+    padding: Option<u32>,
+    // Polygon mode only: the outline in source-size coordinates, the same
+    // points in atlas pixels, and triangles indexing into both
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vertices: Option<Vec<[f32; 2]>>,
+    #[serde(rename = "verticesUV", skip_serializing_if = "Option::is_none")]
+    vertices_uv: Option<Vec<[f32; 2]>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    triangles: Option<Vec<[u32; 3]>>,
+    // u0, v0, u1, v1 as fractions of the atlas size, with `uvs` on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uv: Option<[f32; 4]>,
 }
 
 #[derive(Serialize)]
@@ -69,158 +260,1433 @@ struct PhaserMeta {
     image: String,
     size: Size,
     scale: f32,
+    // Texture array mode: this page's layer and every layer's image
+    #[serde(skip_serializing_if = "Option::is_none")]
+    layer: Option<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    layers: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct PhaserAnimation {
+    frames: Vec<String>,
+    #[serde(rename = "frameRate")]
+    frame_rate: f32,
 }
 
 #[derive(Serialize)]
 struct PhaserAtlas {
     frames: BTreeMap<String, PhaserFrame>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    animations: BTreeMap<String, PhaserAnimation>,
     meta: PhaserMeta,
 }
 
-pub fn pack_atlas(sprites: Vec<SpriteInput>, padding: u32) -> Result<AtlasOutput, String> {
+const DEFAULT_FRAME_RATE: f32 = 10.0;
+
+#[derive(Clone)]
+pub struct AnimationTag {
+    pub name: String,
+    pub frame_index: Option<u32>,
+    pub frame_rate: Option<f32>,
+}
+
+// A decoded sprite ready for placement
+#[derive(Clone)]
+pub struct SourceSprite {
+    pub name: String,
+    pub image: DynamicImage,
+    pub offset_x: i32,
+    pub offset_y: i32,
+    pub animation: Option<AnimationTag>,
+    // Same size as `image`
+    pub companions: BTreeMap<String, DynamicImage>,
+    pub group: Option<String>,
+    pub keep_together: Option<String>,
+    pub fixed_position: Option<(u32, u32)>,
+    // Overrides the global padding
+    pub padding: Option<u32>,
+    pub lossless: bool,
+}
+
+// Where one sprite's pixels (padding excluded) land in the atlas
+#[derive(Clone, Copy)]
+struct Placement {
+    index: usize,
+    x: u32,
+    y: u32,
+}
+
+struct Layout {
+    scale: f32,
+    width: u32,
+    height: u32,
+    placements: Vec<Placement>,
+}
+
+const MAX_ATLAS_SIZE: u32 = 2048;
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueLevel {
+    // The sprite was packed but part of it (a companion texture) was dropped
+    Warning,
+    // The sprite was left out of the atlas
+    Error,
+}
+
+#[derive(Serialize, Clone)]
+pub struct SpriteIssue {
+    pub name: String,
+    pub level: IssueLevel,
+    pub reason: String,
+    // Routes the issue to the sprite's atlas in grouped output
+    #[serde(skip)]
+    pub group: Option<String>,
+}
+
+// With `lenient`, companion textures that fail to decode or don't match the
+// sprite's size are dropped with a warning instead of failing the sprite
+fn decode_sprite(
+    sprite: &SpriteInput,
+    lenient: bool,
+    issues: &mut Vec<SpriteIssue>,
+) -> Result<SourceSprite, AppError> {
+    let img = decode_base64_image(&sprite.base64)?;
+    let mut companions = BTreeMap::new();
+    for (suffix, base64) in &sprite.companions {
+        let companion = decode_base64_raw(base64).and_then(|companion| {
+            if companion.dimensions() == img.dimensions() {
+                Ok(companion)
+            } else {
+                Err(AppError::invalid(
+                    "companions",
+                    format!(
+                        "size {}x{} does not match the sprite's {}x{}",
+                        companion.width(),
+                        companion.height(),
+                        img.width(),
+                        img.height()
+                    ),
+                ))
+            }
+        });
+        match companion {
+            Ok(companion) => {
+                companions.insert(suffix.clone(), companion);
+            }
+            Err(e) if lenient => issues.push(SpriteIssue {
+                name: sprite.name.clone(),
+                level: IssueLevel::Warning,
+                reason: format!("{} texture dropped: {}", suffix, e),
+                group: sprite.group.clone(),
+            }),
+            Err(e) => return Err(e.context(format!("{}: {} texture", sprite.name, suffix))),
+        }
+    }
+
+    Ok(SourceSprite {
+        name: sprite.name.clone(),
+        image: img,
+        offset_x: sprite.offset_x,
+        offset_y: sprite.offset_y,
+        animation: sprite.animation.as_ref().map(|name| AnimationTag {
+            name: name.clone(),
+            frame_index: sprite.frame_index,
+            frame_rate: sprite.frame_rate,
+        }),
+        companions,
+        group: sprite.group.clone(),
+        keep_together: sprite.keep_together.clone(),
+        fixed_position: sprite.fixed_position.map(|p| (p.x, p.y)),
+        padding: sprite.padding,
+        lossless: sprite.lossless,
+    })
+}
+
+pub fn decode_sprites(sprites: &[SpriteInput]) -> Result<Vec<SourceSprite>, AppError> {
     // Decode all images and store offsets
-    let mut original_images: Vec<(String, DynamicImage, i32, i32)> = Vec::new();
+    let mut original_images = Vec::new();
 
-    for sprite in &sprites {
-        let base64_clean = sprite
-            .base64
-            .strip_prefix("data:image/png;base64,")
-            .unwrap_or(&sprite.base64);
-        let bytes = STANDARD.decode(base64_clean).map_err(|e| e.to_string())?;
-        let img = image::load_from_memory(&bytes).map_err(|e| e.to_string())?;
-        original_images.push((sprite.name.clone(), img, sprite.offset_x, sprite.offset_y));
+    for sprite in sprites {
+        original_images.push(decode_sprite(sprite, false, &mut Vec::new())?);
     }
 
     if original_images.is_empty() {
-        return Err("No images to pack".to_string());
+        return Err(AppError::invalid("sprites", "No images to pack"));
+    }
+
+    Ok(original_images)
+}
+
+// Skip sprites that fail to decode instead of aborting, reporting each one.
+// Fails only when nothing is left to pack.
+pub fn decode_sprites_lenient(
+    sprites: &[SpriteInput],
+) -> Result<(Vec<SourceSprite>, Vec<SpriteIssue>), AppError> {
+    let mut decoded = Vec::new();
+    let mut issues = Vec::new();
+
+    for sprite in sprites {
+        match decode_sprite(sprite, true, &mut issues) {
+            Ok(source) => decoded.push(source),
+            Err(e) => issues.push(SpriteIssue {
+                name: sprite.name.clone(),
+                level: IssueLevel::Error,
+                reason: e.to_string(),
+                group: sprite.group.clone(),
+            }),
+        }
+    }
+
+    if decoded.is_empty() {
+        return Err(match issues.first() {
+            Some(issue) => AppError::decode(format!(
+                "No sprite could be decoded ({}: {})",
+                issue.name, issue.reason
+            )),
+            None => AppError::invalid("sprites", "No images to pack"),
+        });
+    }
+
+    Ok((decoded, issues))
+}
+
+// How a folder (tree) of images becomes sprites
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DirImport {
+    // Descend into sub-folders
+    pub recursive: bool,
+    // Joins the folders and file stem of a nested image into its frame name,
+    // e.g. "enemies/slime/walk_0"
+    pub separator: String,
+    // Put nested images in the atlas group named after their top-level folder
+    pub group_by_folder: bool,
+}
+
+impl Default for DirImport {
+    fn default() -> Self {
+        DirImport {
+            recursive: false,
+            separator: "/".to_string(),
+            group_by_folder: false,
+        }
+    }
+}
+
+fn collect_image_paths(
+    dir: &Path,
+    recursive: bool,
+    paths: &mut Vec<PathBuf>,
+) -> Result<(), AppError> {
+    let entries = std::fs::read_dir(dir).map_err(|e| AppError::from(e).context(dir.display()))?;
+    for entry in entries {
+        let Ok(path) = entry.map(|e| e.path()) else {
+            continue;
+        };
+        if path.is_dir() {
+            // Skip hidden folders such as .git
+            let hidden = path
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with('.'));
+            if recursive && !hidden {
+                collect_image_paths(&path, recursive, paths)?;
+            }
+        } else if path.is_file() && ImageFormat::from_path(&path).is_ok() {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+// Every decodable image in `dir` (and its sub-folders when recursive), in
+// natural path order. Images directly in `dir` are named by file stem, nested ones by
+// their relative path.
+pub fn load_sprite_dir(dir: &Path, import: &DirImport) -> Result<Vec<SourceSprite>, AppError> {
+    let mut paths = Vec::new();
+    collect_image_paths(dir, import.recursive, &mut paths)?;
+    paths.sort_by(|a, b| natural_path_cmp(a, b));
+
+    paths
+        .iter()
+        .map(|path| {
+            let image = open_image(path)?;
+            let relative = path.strip_prefix(dir).unwrap_or(path);
+            let mut parts: Vec<String> = relative
+                .parent()
+                .into_iter()
+                .flat_map(|p| p.iter())
+                .map(|c| c.to_string_lossy().into_owned())
+                .collect();
+            let group = if import.group_by_folder {
+                parts.first().cloned()
+            } else {
+                None
+            };
+            parts.push(
+                path.file_stem()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+            );
+            Ok(SourceSprite {
+                name: parts.join(&import.separator),
+                image,
+                offset_x: 0,
+                offset_y: 0,
+                animation: None,
+                companions: BTreeMap::new(),
+                group,
+                keep_together: None,
+                fixed_position: None,
+                padding: None,
+                lossless: false,
+            })
+        })
+        .collect()
+}
+
+// Resample in premultiplied space so fully transparent texels (usually black)
+// don't bleed dark fringes into the sprite's edges
+fn resize_sprite(
+    img: &DynamicImage,
+    width: u32,
+    height: u32,
+    filter: ResizeFilter,
+) -> DynamicImage {
+    let mut rgba = img.to_rgba8();
+    premultiply(&mut rgba);
+    let mut resized = imageops::resize(&rgba, width, height, filter.into());
+    unpremultiply(&mut resized);
+    DynamicImage::ImageRgba8(resized)
+}
+
+// Companions hold data rather than color, so they are resized as-is
+fn resize_companions(
+    sprite: &SourceSprite,
+    width: u32,
+    height: u32,
+    options: &AtlasOptions,
+) -> BTreeMap<String, DynamicImage> {
+    sprite
+        .companions
+        .iter()
+        .map(|(suffix, img)| {
+            (suffix.clone(), img.resize_exact(width, height, options.scale_filter.into()))
+        })
+        .collect()
+}
+
+fn scale_sprites(sprites: &[SourceSprite], scale: f32, options: &AtlasOptions) -> Vec<SourceSprite> {
+    if scale == 1.0 {
+        return sprites.to_vec();
+    }
+
+    sprites.iter().map(|sprite| {
+        let img = &sprite.image;
+        let new_width = ((img.width() as f32) * scale).round() as u32;
+        let new_height = ((img.height() as f32) * scale).round() as u32;
+        let scaled_img =
+            resize_sprite(img, new_width.max(1), new_height.max(1), options.scale_filter);
+        // Scale offsets proportionally
+        SourceSprite {
+            name: sprite.name.clone(),
+            image: scaled_img,
+            offset_x: ((sprite.offset_x as f32) * scale).round() as i32,
+            offset_y: ((sprite.offset_y as f32) * scale).round() as i32,
+            animation: sprite.animation.clone(),
+            companions: resize_companions(sprite, new_width.max(1), new_height.max(1), options),
+            group: sprite.group.clone(),
+            keep_together: sprite.keep_together.clone(),
+            fixed_position: sprite.fixed_position.map(|(x, y)| {
+                ((x as f32 * scale).round() as u32, (y as f32 * scale).round() as u32)
+            }),
+            padding: sprite.padding,
+            lossless: sprite.lossless,
+        }
+    }).collect()
+}
+
+// (width, height, padding) per sprite, `padding` unless overridden
+fn padded_sizes<'a>(
+    images: impl IntoIterator<Item = &'a SourceSprite>,
+    padding: u32,
+) -> Vec<(u32, u32, u32)> {
+    images
+        .into_iter()
+        .map(|s| (s.image.width(), s.image.height(), s.padding.unwrap_or(padding)))
+        .collect()
+}
+
+// Smallest power-of-two square that could hold `sizes` (width, height,
+// padding) if packing were perfect; reported when packing fails
+fn estimated_size(sizes: &[(u32, u32, u32)]) -> u32 {
+    let mut area = 0u64;
+    let mut side = 1u32;
+    for &(w, h, padding) in sizes {
+        let (w, h) = (w + padding * 2, h + padding * 2);
+        area += w as u64 * h as u64;
+        side = side.max(w).max(h);
+    }
+    ((area as f64).sqrt().ceil() as u32).max(side).next_power_of_two()
+}
+
+// Positions within `sprites` in placement order: largest first by the chosen
+// measure, or alphabetical. Ties keep input order.
+fn placement_order(sprites: &[&SourceSprite], strategy: SortStrategy) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..sprites.len()).collect();
+    let measure = |i: usize| {
+        let (w, h) = (sprites[i].image.width() as u64, sprites[i].image.height() as u64);
+        match strategy {
+            SortStrategy::Area => w * h,
+            SortStrategy::MaxSide => w.max(h),
+            SortStrategy::Height => h,
+            SortStrategy::Perimeter => w + h,
+            SortStrategy::Name => 0,
+        }
+    };
+    if strategy == SortStrategy::Name {
+        order.sort_by(|&a, &b| sprites[a].name.cmp(&sprites[b].name));
+    } else {
+        order.sort_by_key(|&i| std::cmp::Reverse(measure(i)));
+    }
+    order
+}
+
+// MaxRects placement in the given order, growing a square bin up to `max_size`
+fn try_pack_ordered(sizes: &[(u32, u32, u32)], order: &[usize], max_size: u32) -> Option<Layout> {
+    let mut bin_size = 256u32;
+    while bin_size <= max_size {
+        let mut bin = MaxRects::new(bin_size, bin_size);
+        let placements: Option<Vec<Placement>> = order
+            .iter()
+            .map(|&index| {
+                let (w, h, padding) = sizes[index];
+                bin.insert(w + padding * 2, h + padding * 2).map(|r| Placement {
+                    index,
+                    x: r.x + padding,
+                    y: r.y + padding,
+                })
+            })
+            .collect();
+
+        if let Some(placements) = placements {
+            let width = placements
+                .iter()
+                .map(|p| p.x + sizes[p.index].0 + sizes[p.index].2)
+                .max()
+                .unwrap_or(0);
+            let height = placements
+                .iter()
+                .map(|p| p.y + sizes[p.index].1 + sizes[p.index].2)
+                .max()
+                .unwrap_or(0);
+            return Some(Layout { scale: 1.0, width, height, placements });
+        }
+        bin_size *= 2;
+    }
+    None
+}
+
+// Pack at the sprites' current size with the configured order; placement
+// indices refer to positions within `sprites`
+fn pack_sprites(
+    sprites: &[&SourceSprite],
+    padding: u32,
+    sort: Option<SortStrategy>,
+) -> Option<Layout> {
+    let sizes = padded_sizes(sprites.iter().copied(), padding);
+    match sort {
+        Some(strategy) => {
+            try_pack_ordered(&sizes, &placement_order(sprites, strategy), MAX_ATLAS_SIZE)
+        }
+        None => try_pack(&sizes, MAX_ATLAS_SIZE),
     }
+}
+
+// Pack at the sprites' current size, growing a square bin up to `max_size`
+fn try_pack(sizes: &[(u32, u32, u32)], max_size: u32) -> Option<Layout> {
+    // Prepare rectangles for packing
+    let mut rects_to_place: GroupedRectsToPlace<usize, ()> = GroupedRectsToPlace::new();
+    for (i, &(width, height, padding)) in sizes.iter().enumerate() {
+        rects_to_place.push_rect(
+            i,
+            None,
+            RectToInsert::new(width + padding * 2, height + padding * 2, 1),
+        );
+    }
+
+    // Try different bin sizes until we find one that fits
+    let mut bin_size = 256u32;
 
-    let max_size = 2048u32;
+    let placements = loop {
+        let mut target_bins = BTreeMap::new();
+        target_bins.insert(0, TargetBin::new(bin_size, bin_size, 1));
+
+        match pack_rects(
+            &rects_to_place,
+            &mut target_bins,
+            &volume_heuristic,
+            &contains_smallest_box,
+        ) {
+            Ok(placements) => break placements,
+            Err(_) => {
+                bin_size *= 2;
+                if bin_size > max_size {
+                    return None; // Can't fit at this scale
+                }
+            }
+        }
+    };
+
+    // Find actual bounds
+    let mut max_x = 0u32;
+    let mut max_y = 0u32;
+
+    #[allow(clippy::for_kv_map)]
+    for (_, (_, loc)) in placements.packed_locations() {
+        max_x = max_x.max(loc.x() + loc.width());
+        max_y = max_y.max(loc.y() + loc.height());
+    }
+
+    let placements = placements
+        .packed_locations()
+        .iter()
+        .map(|(rect_id, (_, loc))| Placement {
+            index: *rect_id,
+            x: loc.x() + sizes[*rect_id].2,
+            y: loc.y() + sizes[*rect_id].2,
+        })
+        .collect();
+
+    Some(Layout { scale: 1.0, width: max_x, height: max_y, placements })
+}
+
+// Pinned sprites keep their coordinates and, like reserved regions, are
+// marked as occupied before the rest are placed in `sort` order. Grows the
+// bin until everything fits or MAX_ATLAS_SIZE is reached.
+fn pack_constrained(
+    images: &[SourceSprite],
+    padding: u32,
+    reserved: &[Region],
+    min_size: (u32, u32),
+    sort: SortStrategy,
+) -> Result<Layout, AppError> {
+    let mut obstacles = reserved.to_vec();
+    let mut pinned = Vec::new();
+    let mut free = Vec::new();
+    for (index, sprite) in images.iter().enumerate() {
+        let padding = sprite.padding.unwrap_or(padding);
+        match sprite.fixed_position {
+            Some((x, y)) => {
+                let rect = Region {
+                    x: x.saturating_sub(padding),
+                    y: y.saturating_sub(padding),
+                    width: sprite.image.width() + padding * 2,
+                    height: sprite.image.height() + padding * 2,
+                };
+                if let Some(other) = obstacles.iter().find(|o| o.intersects(&rect)) {
+                    return Err(AppError::invalid(
+                        "fixedPosition",
+                        format!(
+                            "Pinned sprite \"{}\" overlaps a reserved or pinned area at {},{}",
+                            sprite.name, other.x, other.y
+                        ),
+                    ));
+                }
+                obstacles.push(rect);
+                pinned.push(Placement { index, x, y });
+            }
+            None => free.push(index),
+        }
+    }
+    let unpinned: Vec<&SourceSprite> = free.iter().map(|&i| &images[i]).collect();
+    let free: Vec<usize> = placement_order(&unpinned, sort).into_iter().map(|p| free[p]).collect();
+
+    let extent = obstacles
+        .iter()
+        .map(|r| r.right().max(r.bottom()))
+        .fold(min_size.0.max(min_size.1), u32::max);
+    if extent > MAX_ATLAS_SIZE {
+        return Err(AppError::invalid(
+            "reserved",
+            format!("Pinned and reserved areas exceed the {}px atlas", MAX_ATLAS_SIZE),
+        ));
+    }
+
+    let mut bin_size = extent.next_power_of_two().max(256);
+    while bin_size <= MAX_ATLAS_SIZE {
+        let mut bin = MaxRects::new(bin_size, bin_size);
+        for obstacle in &obstacles {
+            bin.occupy(*obstacle);
+        }
+
+        let mut placements = pinned.clone();
+        let all_placed = free.iter().all(|&index| {
+            let sprite = &images[index];
+            let padding = sprite.padding.unwrap_or(padding);
+            let w = sprite.image.width() + padding * 2;
+            let h = sprite.image.height() + padding * 2;
+            bin.insert(w, h).map(|r| placements.push(Placement {
+                index,
+                x: r.x + padding,
+                y: r.y + padding,
+            })).is_some()
+        });
+
+        if all_placed {
+            let mut width = obstacles.iter().map(Region::right).fold(min_size.0, u32::max);
+            let mut height = obstacles.iter().map(Region::bottom).fold(min_size.1, u32::max);
+            for p in &placements {
+                let sprite = &images[p.index];
+                let padding = sprite.padding.unwrap_or(padding);
+                width = width.max(p.x + sprite.image.width() + padding);
+                height = height.max(p.y + sprite.image.height() + padding);
+            }
+            return Ok(Layout { scale: 1.0, width, height, placements });
+        }
+        bin_size *= 2;
+    }
+
+    Err(AppError::packing(
+        "Sprites do not fit around the pinned and reserved areas",
+        estimated_size(&padded_sizes(images, padding)).max(extent.next_power_of_two()),
+    ))
+}
+
+// Pack at full size, falling back to progressively smaller scales. Returns the
+// layout together with the (possibly scaled) sprites it was computed for.
+fn layout_with_fallback(
+    originals: &[SourceSprite],
+    padding: u32,
+    options: &AtlasOptions,
+) -> Result<(Layout, Vec<SourceSprite>), AppError> {
+    // Pinned coordinates and reserved regions describe an existing full-size
+    // layout, so they rule out the scale fallback
+    if !options.reserved.is_empty() || originals.iter().any(|s| s.fixed_position.is_some()) {
+        let sort = options.sort.unwrap_or(SortStrategy::Area);
+        let layout =
+            pack_constrained(originals, padding, &options.reserved, options.min_size, sort)?;
+        return Ok((layout, originals.to_vec()));
+    }
 
     // Try with different scale factors: 100%, 90%, 80%, 70%, 60%, 50%, 40%, 30%, 25%, 20%
     let scale_factors = [1.0f32, 0.9, 0.8, 0.7, 0.6, 0.5, 0.4, 0.3, 0.25, 0.2];
 
     for &scale in &scale_factors {
         // Scale images if needed
-        let images: Vec<(String, DynamicImage, i32, i32)> = if scale < 1.0 {
-            original_images.iter().map(|(name, img, ox, oy)| {
-                let new_width = ((img.width() as f32) * scale).round() as u32;
-                let new_height = ((img.height() as f32) * scale).round() as u32;
-                let scaled_img = img.resize_exact(
-                    new_width.max(1),
-                    new_height.max(1),
-                    FilterType::Lanczos3
-                );
-                // Scale offsets proportionally
-                let scaled_ox = ((*ox as f32) * scale).round() as i32;
-                let scaled_oy = ((*oy as f32) * scale).round() as i32;
-                (name.clone(), scaled_img, scaled_ox, scaled_oy)
-            }).collect()
-        } else {
-            original_images.iter().map(|(name, img, ox, oy)| {
-                (name.clone(), img.clone(), *ox, *oy)
-            }).collect()
-        };
+        let images = scale_sprites(originals, scale, options);
 
-        // Prepare rectangles for packing
-        let mut rects_to_place: GroupedRectsToPlace<usize, ()> = GroupedRectsToPlace::new();
-        for (i, (_, img, _, _)) in images.iter().enumerate() {
-            rects_to_place.push_rect(
-                i,
-                None,
-                RectToInsert::new(
-                    img.width() + padding * 2,
-                    img.height() + padding * 2,
-                    1,
-                ),
-            );
+        let refs: Vec<&SourceSprite> = images.iter().collect();
+        if let Some(mut layout) = pack_sprites(&refs, padding, options.sort) {
+            layout.scale = scale;
+            return Ok((layout, images));
         }
+    }
 
-        // Try different bin sizes until we find one that fits
-        let mut bin_size = 256u32;
-
-        let pack_result = loop {
-            let mut target_bins = BTreeMap::new();
-            target_bins.insert(0, TargetBin::new(bin_size, bin_size, 1));
-
-            match pack_rects(
-                &rects_to_place,
-                &mut target_bins,
-                &volume_heuristic,
-                &contains_smallest_box,
-            ) {
-                Ok(placements) => break Some(placements),
-                Err(_) => {
-                    bin_size *= 2;
-                    if bin_size > max_size {
-                        break None; // Can't fit at this scale, try smaller
-                    }
-                }
+    Err(AppError::packing(
+        "Images too large to pack even at 20% scale",
+        estimated_size(&padded_sizes(originals, padding)),
+    ))
+}
+
+// Frame names per animation ordered by frame index. The frame rate is the
+// first one any frame specifies.
+fn collect_animations(
+    images: &[SourceSprite],
+    frame_order: &[String],
+) -> BTreeMap<String, PhaserAnimation> {
+    let rank: HashMap<&str, usize> = frame_order
+        .iter()
+        .enumerate()
+        .map(|(i, name)| (name.as_str(), i))
+        .collect();
+    let mut tagged: BTreeMap<&str, Vec<&SourceSprite>> = BTreeMap::new();
+    for sprite in images {
+        if let Some(tag) = &sprite.animation {
+            tagged.entry(tag.name.as_str()).or_default().push(sprite);
+        }
+    }
+
+    tagged
+        .into_iter()
+        .map(|(name, mut frames)| {
+            // Explicit order first, then frame index, then natural name order
+            let key = |s: &SourceSprite| {
+                let ranked = rank.get(s.name.as_str()).copied();
+                let index = s.animation.as_ref().and_then(|t| t.frame_index);
+                (ranked.is_none(), ranked, index.is_none(), index)
+            };
+            frames.sort_by(|a, b| {
+                key(a)
+                    .cmp(&key(b))
+                    .then_with(|| natural_cmp(&a.name, &b.name))
+            });
+            let frame_rate = frames
+                .iter()
+                .find_map(|s| s.animation.as_ref().and_then(|t| t.frame_rate))
+                .unwrap_or(DEFAULT_FRAME_RATE);
+            let animation = PhaserAnimation {
+                frames: frames.iter().map(|s| s.name.clone()).collect(),
+                frame_rate,
+            };
+            (name.to_string(), animation)
+        })
+        .collect()
+}
+
+// "atlas.png" + "_n" -> "atlas_n.png"
+fn companion_name(image_name: &str, suffix: &str) -> String {
+    match image_name.rsplit_once('.') {
+        Some((stem, ext)) => format!("{}{}.{}", stem, suffix, ext),
+        None => format!("{}{}", image_name, suffix),
+    }
+}
+
+// Optional oxipng pass, interlacing, chunk stripping and color chunks for
+// PNG output; other formats pass through untouched
+fn finish_png(bytes: Vec<u8>, options: &AtlasOptions) -> Result<Vec<u8>, AppError> {
+    if !matches!(options.format, OutputFormat::Png { .. }) {
+        return Ok(bytes);
+    }
+    let optimize = options.lossless_optimize.then_some(4);
+    let mut bytes = encode::finish_png(bytes, &options.format, optimize)?;
+    if !options.png_color.is_empty() {
+        bytes = metadata::set_png_color_chunks(&bytes, &options.png_color)?;
+    }
+    Ok(bytes)
+}
+
+// Companions hold data (normals, masks) rather than color, so alpha
+// premultiplication and palette reduction are not applied to them.
+fn render_companions(
+    images: &[SourceSprite],
+    layout: &Layout,
+    options: &AtlasOptions,
+    image_name: &str,
+) -> Result<Vec<CompanionAtlas>, AppError> {
+    let suffixes: std::collections::BTreeSet<&String> =
+        images.iter().flat_map(|s| s.companions.keys()).collect();
+
+    let mut atlases = Vec::with_capacity(suffixes.len());
+    for suffix in suffixes {
+        let mut output = RgbaImage::new(layout.width, layout.height);
+        for placement in &layout.placements {
+            if let Some(img) = images[placement.index].companions.get(suffix) {
+                output
+                    .copy_from(&img.to_rgba8(), placement.x, placement.y)
+                    .map_err(AppError::encode)?;
             }
+        }
+        if options.color_bleed > 0 {
+            bleed_colors(&mut output, options.color_bleed);
+        }
+
+        let bytes = finish_png(encode_image(&output, &options.format)?, options)?;
+        atlases.push(CompanionAtlas {
+            suffix: suffix.clone(),
+            image_name: companion_name(image_name, suffix),
+            image_base64: to_data_url(&bytes, options.format.mime_type()),
+            size_bytes: bytes.len(),
+            image_bytes: bytes,
+        });
+    }
+
+    Ok(atlases)
+}
+
+// Crop each sprite (and its companions) to its traced outline. Pinned sprites
+// are left alone since their position refers to the untrimmed image.
+fn trim_to_polygons(
+    originals: &[SourceSprite],
+    options: &PolygonOptions,
+) -> (Vec<SourceSprite>, Vec<Option<SpriteShape>>) {
+    originals
+        .iter()
+        .map(|sprite| {
+            let shape = match sprite.fixed_position {
+                Some(_) => None,
+                None => sprite_shape(&sprite.image.to_rgba8(), options),
+            };
+            let Some(shape) = shape else {
+                return (sprite.clone(), None);
+            };
+            let crop = |img: &DynamicImage| {
+                img.crop_imm(shape.trim_x, shape.trim_y, shape.width, shape.height)
+            };
+            let trimmed = SourceSprite {
+                image: crop(&sprite.image),
+                companions: sprite
+                    .companions
+                    .iter()
+                    .map(|(suffix, img)| (suffix.clone(), crop(img)))
+                    .collect(),
+                ..sprite.clone()
+            };
+            (trimmed, Some(shape))
+        })
+        .unzip()
+}
+
+fn round_point(x: f32, y: f32) -> [f32; 2] {
+    [(x * 100.0).round() / 100.0, (y * 100.0).round() / 100.0]
+}
+
+// `shapes` is indexed like `images` and empty outside polygon mode
+fn render_atlas(
+    images: &[SourceSprite],
+    layout: &Layout,
+    options: &AtlasOptions,
+    image_name: &str,
+    shapes: &[Option<SpriteShape>],
+) -> Result<AtlasOutput, AppError> {
+    // Create output image
+    let mut output = RgbaImage::new(layout.width, layout.height);
+    let mut frames = BTreeMap::new();
+
+    for placement in &layout.placements {
+        let sprite = &images[placement.index];
+
+        let x = placement.x;
+        let y = placement.y;
+        let w = sprite.image.width();
+        let h = sprite.image.height();
+
+        // Copy image to atlas
+        output.copy_from(&sprite.image.to_rgba8(), x, y).map_err(AppError::encode)?;
+
+        // Add frame to JSON with offset
+        let mut frame = PhaserFrame {
+            frame: FrameRect { x, y, w, h },
+            rotated: false,
+            trimmed: false,
+            sprite_source_size: FrameRect { x: 0, y: 0, w, h },
+            source_size: Size { w, h },
+            pivot: Pivot { x: 0.5, y: 0.5 },
+            offset: Offset { x: sprite.offset_x, y: sprite.offset_y },
+            padding: sprite.padding,
+            vertices: None,
+            vertices_uv: None,
+            triangles: None,
+            uv: options
+                .uvs
+                .then(|| uv::normalized(x, y, w, h, (layout.width, layout.height))),
         };
+        if let Some(shape) = shapes.get(placement.index).and_then(Option::as_ref) {
+            // Shapes are measured on the unscaled sprite
+            let scale = |v: u32| (v as f32 * layout.scale).round() as u32;
+            let (trim_x, trim_y) = (scale(shape.trim_x), scale(shape.trim_y));
+            let (sx, sy) = (w as f32 / shape.width as f32, h as f32 / shape.height as f32);
+            frame.trimmed = true;
+            frame.sprite_source_size = FrameRect { x: trim_x, y: trim_y, w, h };
+            frame.source_size = Size {
+                w: scale(shape.source_width).max(trim_x + w),
+                h: scale(shape.source_height).max(trim_y + h),
+            };
+            frame.vertices = Some(
+                shape
+                    .vertices
+                    .iter()
+                    .map(|v| round_point(trim_x as f32 + v[0] * sx, trim_y as f32 + v[1] * sy))
+                    .collect(),
+            );
+            frame.vertices_uv = Some(
+                shape
+                    .vertices
+                    .iter()
+                    .map(|v| round_point(x as f32 + v[0] * sx, y as f32 + v[1] * sy))
+                    .collect(),
+            );
+            frame.triangles = Some(shape.triangles.clone());
+        }
+        frames.insert(sprite.name.clone(), frame);
+    }
+
+    if options.color_bleed > 0 {
+        bleed_colors(&mut output, options.color_bleed);
+    }
+    if options.premultiply_alpha {
+        premultiply(&mut output);
+    }
+
+    // Encode output image
+    let quantize = options.quantize.as_ref().filter(|_| !images.iter().any(|s| s.lossless));
+    let bytes = match (options.format, quantize) {
+        (OutputFormat::Png { color_type, .. }, Some(settings)) => encode_png(
+            &output,
+            color_type.unwrap_or(PngColorType::Indexed8),
+            settings,
+        )?,
+        _ => encode_image(&output, &options.format)?,
+    };
+    let bytes = finish_png(bytes, options)?;
+    let image_base64 = to_data_url(&bytes, options.format.mime_type());
 
-        // If packing succeeded at this scale
-        if let Some(placements) = pack_result {
-            // Find actual bounds
-            let mut max_x = 0u32;
-            let mut max_y = 0u32;
+    // Generate Phaser JSON
+    let atlas = PhaserAtlas {
+        frames,
+        animations: collect_animations(images, &options.frame_order),
+        meta: PhaserMeta {
+            image: format!(
+                "{}{}",
+                options.page_naming.image_prefix,
+                options.page_ref.as_ref().map_or(image_name, |r| &r.image)
+            ),
+            size: Size { w: layout.width, h: layout.height },
+            scale: layout.scale,
+            layer: options.page_ref.as_ref().and_then(|r| r.layer),
+            layers: options.page_ref.as_ref().map_or_else(Vec::new, |r| {
+                r.layers
+                    .iter()
+                    .map(|l| format!("{}{}", options.page_naming.image_prefix, l))
+                    .collect()
+            }),
+        },
+    };
+    let json = serde_json::to_string_pretty(&atlas).map_err(AppError::encode)?;
+    let uv_bytes = options.uvs.then(|| {
+        let uvs: Vec<(&str, [f32; 4])> = atlas
+            .frames
+            .iter()
+            .filter_map(|(name, frame)| Some((name.as_str(), frame.uv?)))
+            .collect();
+        uv::encode_sidecar(&uvs)
+    });
+    let binary_bytes = match options.binary_metadata {
+        true => Some(binary_meta::from_json(&json)?),
+        false => None,
+    };
+
+    Ok(AtlasOutput {
+        image_base64,
+        json,
+        size_bytes: bytes.len(),
+        companions: render_companions(images, layout, options, image_name)?,
+        tsx: None,
+        issues: Vec::new(),
+        image_bytes: bytes,
+        uv_base64: uv_bytes.as_ref().map(|b| to_data_url(b, "application/octet-stream")),
+        uv_bytes,
+        binary_base64: binary_bytes.as_ref().map(|b| to_data_url(b, "application/octet-stream")),
+        binary_bytes,
+    })
+}
+
+pub struct UnscaledLayout {
+    pub width: u32,
+    pub height: u32,
+    // Top-left corner of each sprite, in input order
+    pub positions: Vec<(u32, u32)>,
+}
+
+// Full size only, for content that must not be resampled (font glyphs)
+pub fn pack_unscaled(
+    sprites: &[SourceSprite],
+    padding: u32,
+    sort: SortStrategy,
+) -> Result<UnscaledLayout, AppError> {
+    let refs: Vec<&SourceSprite> = sprites.iter().collect();
+    let layout = pack_sprites(&refs, padding, Some(sort)).ok_or_else(|| {
+        AppError::packing(
+            format!("Sprites do not fit in a {}px atlas", MAX_ATLAS_SIZE),
+            estimated_size(&padded_sizes(sprites, padding)),
+        )
+    })?;
+    let mut positions = vec![(0, 0); sprites.len()];
+    for p in &layout.placements {
+        positions[p.index] = (p.x, p.y);
+    }
+    Ok(UnscaledLayout {
+        width: layout.width,
+        height: layout.height,
+        positions,
+    })
+}
+
+// `image_name` is recorded in the JSON meta
+pub fn pack_atlas(
+    originals: &[SourceSprite],
+    padding: u32,
+    options: &AtlasOptions,
+    image_name: &str,
+) -> Result<AtlasOutput, AppError> {
+    if originals.is_empty() {
+        return Err(AppError::invalid("sprites", "No images to pack"));
+    }
+    if let Some(tileset) = &options.tileset {
+        return pack_tileset(originals, tileset, options, image_name);
+    }
+    let (trimmed, shapes) = match &options.polygon {
+        Some(polygon) => trim_to_polygons(originals, polygon),
+        None => (Vec::new(), Vec::new()),
+    };
+    let originals = if shapes.is_empty() { originals } else { &trimmed };
+    let (layout, images) = layout_with_fallback(originals, padding, options)?;
+    render_atlas(&images, &layout, options, image_name, &shapes)
+}
+
+// Tiles keep their input order as Tiled IDs. Sprites smaller than a cell sit at
+// its top-left so every frame is exactly one cell; padding is replaced by the
+// grid's margin and spacing, and there is no scale fallback.
+fn pack_tileset(
+    originals: &[SourceSprite],
+    tileset: &TilesetOptions,
+    options: &AtlasOptions,
+    image_name: &str,
+) -> Result<AtlasOutput, AppError> {
+    let sizes: Vec<(u32, u32)> =
+        originals.iter().map(|s| (s.image.width(), s.image.height())).collect();
+    let grid = TileGrid::new(tileset, &sizes)?;
+    let (width, height) = grid.image_size();
+    if width > MAX_ATLAS_SIZE || height > MAX_ATLAS_SIZE {
+        return Err(AppError::packing(
+            format!(
+                "Tileset of {}x{} exceeds the {}px atlas limit",
+                width, height, MAX_ATLAS_SIZE
+            ),
+            width.max(height).next_power_of_two(),
+        ));
+    }
+
+    let mut images = Vec::with_capacity(originals.len());
+    let to_cell = |img: &DynamicImage| -> Result<DynamicImage, AppError> {
+        let mut cell = RgbaImage::new(grid.tile_width, grid.tile_height);
+        cell.copy_from(&img.to_rgba8(), 0, 0).map_err(AppError::encode)?;
+        Ok(DynamicImage::ImageRgba8(cell))
+    };
+    for sprite in originals {
+        let mut companions = BTreeMap::new();
+        for (suffix, img) in &sprite.companions {
+            companions.insert(suffix.clone(), to_cell(img)?);
+        }
+        images.push(SourceSprite {
+            image: to_cell(&sprite.image)?,
+            companions,
+            ..sprite.clone()
+        });
+    }
 
-            for (_, (_, loc)) in placements.packed_locations() {
-                max_x = max_x.max(loc.x() + loc.width());
-                max_y = max_y.max(loc.y() + loc.height());
+    let placements = (0..images.len())
+        .map(|index| {
+            let (x, y) = grid.position(index as u32);
+            Placement { index, x, y }
+        })
+        .collect();
+    let layout = Layout { scale: 1.0, width, height, placements };
+
+    let mut output = render_atlas(&images, &layout, options, image_name, &[])?;
+    let names: Vec<String> = images.iter().map(|s| s.name.clone()).collect();
+    output.tsx = Some(write_tsx(&grid, image_name, &names));
+    Ok(output)
+}
+
+pub const DEFAULT_GROUP: &str = "default";
+
+#[derive(Serialize, Clone)]
+pub struct GroupedAtlas {
+    pub group: String,
+    // 1-based page within the group; always 1 without multi-page output
+    pub page: u32,
+    pub image_name: String,
+    // Holds lossless sprites, so it was not quantized
+    pub lossless: bool,
+    // Page profile whose settings encoded this page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<SpriteProfile>,
+    #[serde(flatten)]
+    pub atlas: AtlasOutput,
+}
+
+// "atlas" for the default group, "atlas_<group>" otherwise, with characters
+// that are unsafe in file names replaced. Numbered pages follow the naming
+// pattern, "-<page>" appended by default.
+fn group_image_name(
+    group: &str,
+    page: Option<u32>,
+    extension: &str,
+    naming: &PageNaming,
+) -> Result<String, AppError> {
+    let mut name = "atlas".to_string();
+    if group != DEFAULT_GROUP {
+        name.push('_');
+        name.extend(group.chars().map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' }
+        }));
+    }
+    let stem = match (page, &naming.pattern) {
+        (None, _) => name,
+        (Some(page), None) => format!("{}-{}", name, page),
+        (Some(page), Some(pattern)) => {
+            let stem = naming::fill_pattern(pattern, "pageNaming.pattern", |key| match key {
+                "name" => Some(name.clone()),
+                "group" => Some(group.to_string()),
+                "page" => Some(page.to_string()),
+                _ => None,
+            })?;
+            if stem.is_empty() || stem.contains(['/', '\\']) {
+                return Err(AppError::invalid(
+                    "pageNaming.pattern",
+                    format!("\"{}\" is not a valid file name", stem),
+                ));
             }
+            stem
+        }
+    };
+    Ok(format!("{}.{}", stem, extension))
+}
 
-            // Create output image
-            let mut output = RgbaImage::new(max_x, max_y);
-            let mut frames = BTreeMap::new();
-
-            for (rect_id, (_, loc)) in placements.packed_locations() {
-                let (name, img, offset_x, offset_y) = &images[*rect_id];
-
-                let x = loc.x() + padding;
-                let y = loc.y() + padding;
-                let w = img.width();
-                let h = img.height();
-
-                // Copy image to atlas
-                output.copy_from(&img.to_rgba8(), x, y).map_err(|e| e.to_string())?;
-
-                // Add frame to JSON with offset
-                frames.insert(
-                    name.clone(),
-                    PhaserFrame {
-                        frame: FrameRect { x, y, w, h },
-                        rotated: false,
-                        trimmed: false,
-                        sprite_source_size: FrameRect { x: 0, y: 0, w, h },
-                        source_size: Size { w, h },
-                        pivot: Pivot { x: 0.5, y: 0.5 },
-                        offset: Offset { x: *offset_x, y: *offset_y },
-                    },
-                );
+// Sprites that must share a page: an explicit keep-together key, otherwise
+// the frames of one animation. Everything else is a unit of its own.
+fn page_units(sprites: &[SourceSprite]) -> Vec<Vec<usize>> {
+    let mut keyed: Vec<(&str, Vec<usize>)> = Vec::new();
+    let mut units = Vec::new();
+    for (i, sprite) in sprites.iter().enumerate() {
+        let key = sprite
+            .keep_together
+            .as_deref()
+            .or(sprite.animation.as_ref().map(|a| a.name.as_str()));
+        match key {
+            Some(key) => match keyed.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, members)) => members.push(i),
+                None => keyed.push((key, vec![i])),
+            },
+            None => units.push(vec![i]),
+        }
+    }
+    units.extend(keyed.into_iter().map(|(_, members)| members));
+    units
+}
+
+// First-fit of whole units onto full-size pages, largest units first.
+// Returns sprite indices per page.
+fn split_pages(
+    sprites: &[SourceSprite],
+    padding: u32,
+    sort: Option<SortStrategy>,
+) -> Result<Vec<Vec<usize>>, AppError> {
+    let sizes = padded_sizes(sprites, padding);
+    let area = |unit: &[usize]| -> u64 {
+        unit.iter()
+            .map(|&i| {
+                let (w, h, padding) = sizes[i];
+                (w + padding * 2) as u64 * (h + padding * 2) as u64
+            })
+            .sum()
+    };
+    let fits = |indices: &[usize]| {
+        let page: Vec<&SourceSprite> = indices.iter().map(|&i| &sprites[i]).collect();
+        pack_sprites(&page, padding, sort).is_some()
+    };
+
+    let mut units = page_units(sprites);
+    units.sort_by_key(|unit| std::cmp::Reverse(area(unit)));
+
+    let mut pages: Vec<Vec<usize>> = Vec::new();
+    for unit in units {
+        let slot = pages.iter().position(|page| {
+            let mut candidate = page.clone();
+            candidate.extend_from_slice(&unit);
+            fits(&candidate)
+        });
+        match slot {
+            Some(p) => pages[p].extend_from_slice(&unit),
+            None if fits(&unit) => pages.push(unit),
+            None => {
+                return Err(AppError::packing(
+                    format!(
+                        "\"{}\" and the sprites kept with it do not fit on one {}px page",
+                        sprites[unit[0]].name, MAX_ATLAS_SIZE
+                    ),
+                    estimated_size(&unit.iter().map(|&i| sizes[i]).collect::<Vec<_>>()),
+                ))
             }
+        }
+    }
 
-            // Encode output image
-            let mut buf = Cursor::new(Vec::new());
-            output
-                .write_to(&mut buf, image::ImageFormat::Png)
-                .map_err(|e| e.to_string())?;
-            let image_base64 = format!("data:image/png;base64,{}", STANDARD.encode(buf.get_ref()));
-
-            // Generate Phaser JSON
-            let atlas = PhaserAtlas {
-                frames,
-                meta: PhaserMeta {
-                    image: "atlas.png".to_string(),
-                    size: Size { w: max_x, h: max_y },
-                    scale,
-                },
+    // Keep input order within each page for stable output
+    for page in &mut pages {
+        page.sort_unstable();
+    }
+    Ok(pages)
+}
+
+// Indices of `sprites` per set of pages, each with its page profile: the
+// regular pages, one set per profile, then lossless sprites when
+// `lossless_page` applies. Whole page units move, so sprites kept together
+// stay together.
+fn split_parts(
+    sprites: &[SourceSprite],
+    options: &AtlasOptions,
+) -> Vec<(Option<usize>, Vec<usize>)> {
+    let profiles = &options.page_profiles;
+    let lossless_page = options.lossless_page && options.quantize.is_some();
+    let mut parts: Vec<(Option<usize>, Vec<usize>)> = std::iter::once(None)
+        .chain((0..profiles.len()).map(Some))
+        .chain(lossless_page.then_some(None))
+        .map(|profile| (profile, Vec::new()))
+        .collect();
+    for unit in page_units(sprites) {
+        let slot = if lossless_page && unit.iter().any(|&i| sprites[i].lossless) {
+            parts.len() - 1
+        } else {
+            page_profile::assign(sprites, &unit, profiles).map_or(0, |p| p + 1)
+        };
+        parts[slot].1.extend(unit);
+    }
+    for (_, part) in &mut parts {
+        part.sort_unstable();
+    }
+    parts.retain(|(_, part)| !part.is_empty());
+    parts
+}
+
+// One atlas per sprite group (untagged sprites form the default group), all
+// packed with the same settings, and split into pages when `multi_page` is
+// set. Sprites matching a page profile, then lossless sprites with
+// `lossless_page`, follow on pages of their own. Groups keep first-seen order.
+pub fn pack_atlas_groups(
+    originals: &[SourceSprite],
+    padding: u32,
+    options: &AtlasOptions,
+) -> Result<Vec<GroupedAtlas>, AppError> {
+    let mut groups: Vec<(String, Vec<SourceSprite>)> = Vec::new();
+    for sprite in originals {
+        let name = sprite.group.as_deref().unwrap_or(DEFAULT_GROUP);
+        match groups.iter_mut().find(|(group, _)| group == name) {
+            Some((_, sprites)) => sprites.push(sprite.clone()),
+            None => groups.push((name.to_string(), vec![sprite.clone()])),
+        }
+    }
+
+    let mut outputs = Vec::new();
+    for (group, sprites) in groups {
+        let in_group = |e: AppError| e.context(format!("Group \"{}\"", group));
+        let mut pages = Vec::new();
+        for (profile, part) in split_parts(&sprites, options) {
+            if options.multi_page && options.tileset.is_none() {
+                let subset: Vec<SourceSprite> = part.iter().map(|&i| sprites[i].clone()).collect();
+                let split = split_pages(&subset, padding, options.sort).map_err(in_group)?;
+                pages.extend(split.into_iter().map(|page| {
+                    (profile, page.into_iter().map(|i| part[i]).collect::<Vec<usize>>())
+                }));
+            } else {
+                pages.push((profile, part));
+            }
+        }
+        let numbered = pages.len() > 1 || options.page_naming.always_number;
+        let formats: Vec<&OutputFormat> = pages
+            .iter()
+            .map(|(profile, _)| {
+                profile.map_or(&options.format, |p| &options.page_profiles[p].format)
+            })
+            .collect();
+        let names = formats
+            .iter()
+            .enumerate()
+            .map(|(n, format)| {
+                let page = numbered.then_some(n as u32 + 1);
+                group_image_name(&group, page, format.extension(), &options.page_naming)
+            })
+            .collect::<Result<Vec<String>, AppError>>()
+            .map_err(in_group)?;
+
+        for (n, ((profile, indices), image_name)) in pages.into_iter().zip(&names).enumerate() {
+            let page = n as u32 + 1;
+            if outputs.iter().any(|o: &GroupedAtlas| &o.image_name == image_name) {
+                return Err(AppError::invalid(
+                    "pageNaming.pattern",
+                    format!("Two pages would both be written to \"{}\"", image_name),
+                ));
+            }
+            let mut page_options = match profile.map(|p| &options.page_profiles[p]) {
+                Some(p) => Cow::Owned(AtlasOptions {
+                    format: p.format,
+                    quantize: p.quantize,
+                    ..options.clone()
+                }),
+                None => Cow::Borrowed(options),
             };
-            let json = serde_json::to_string_pretty(&atlas).map_err(|e| e.to_string())?;
+            if options.page_naming.texture_array {
+                page_options.to_mut().page_ref = Some(PageRef {
+                    image: image_name.clone(),
+                    layer: Some(n as u32),
+                    layers: names.clone(),
+                });
+            }
+            let image_name = image_name.clone();
+            let page_sprites: Vec<SourceSprite> =
+                indices.iter().map(|&i| sprites[i].clone()).collect();
+            let lossless = page_sprites.iter().any(|s| s.lossless);
+            let atlas =
+                pack_atlas(&page_sprites, padding, &page_options, &image_name).map_err(in_group)?;
+            outputs.push(GroupedAtlas {
+                group: group.clone(),
+                page,
+                image_name,
+                lossless,
+                profile: profile.map(|p| options.page_profiles[p].profile),
+                atlas,
+            });
+        }
+    }
+
+    Ok(outputs)
+}
 
-            return Ok(AtlasOutput { image_base64, json });
+// Each issue goes to the first page of its sprite's group, or to the first
+// atlas when that group produced none
+pub fn attach_issues(atlases: &mut [GroupedAtlas], issues: Vec<SpriteIssue>) {
+    for issue in issues {
+        let group = issue.group.as_deref().unwrap_or(DEFAULT_GROUP);
+        let slot = atlases.iter().position(|a| a.group == group).unwrap_or(0);
+        if let Some(atlas) = atlases.get_mut(slot) {
+            atlas.atlas.issues.push(issue);
         }
     }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct AtlasVariant {
+    pub scale: f32,
+    // Appended to file names, e.g. "@2x" -> atlas@2x.png
+    pub suffix: String,
+}
+
+#[derive(Serialize)]
+pub struct AtlasVariantOutput {
+    pub scale: f32,
+    pub suffix: String,
+    pub image_name: String,
+    pub image_base64: String,
+    pub json: String,
+    pub size_bytes: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub companions: Vec<CompanionAtlas>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub issues: Vec<SpriteIssue>,
+    #[serde(skip)]
+    pub image_bytes: Vec<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uv_base64: Option<String>,
+    #[serde(skip)]
+    pub uv_bytes: Option<Vec<u8>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub binary_base64: Option<String>,
+    #[serde(skip)]
+    pub binary_bytes: Option<Vec<u8>>,
+}
+
+// Scale an existing layout so every variant keeps the same relative UVs.
+// Edges are floored so neighbouring sprites never overlap after rounding.
+fn scale_layout(
+    layout: &Layout,
+    images: &[SourceSprite],
+    scale: f32,
+    options: &AtlasOptions,
+) -> (Layout, Vec<SourceSprite>) {
+    let mut scaled_images = images.to_vec();
+    let mut placements = Vec::with_capacity(layout.placements.len());
+
+    for placement in &layout.placements {
+        let sprite = &images[placement.index];
+        let x0 = (placement.x as f32 * scale).floor() as u32;
+        let y0 = (placement.y as f32 * scale).floor() as u32;
+        let x1 = ((placement.x + sprite.image.width()) as f32 * scale).floor() as u32;
+        let y1 = ((placement.y + sprite.image.height()) as f32 * scale).floor() as u32;
+
+        let (w, h) = ((x1 - x0).max(1), (y1 - y0).max(1));
+        scaled_images[placement.index] = SourceSprite {
+            name: sprite.name.clone(),
+            image: resize_sprite(&sprite.image, w, h, options.scale_filter),
+            offset_x: ((sprite.offset_x as f32) * scale).round() as i32,
+            offset_y: ((sprite.offset_y as f32) * scale).round() as i32,
+            animation: sprite.animation.clone(),
+            companions: resize_companions(sprite, w, h, options),
+            group: sprite.group.clone(),
+            keep_together: sprite.keep_together.clone(),
+            fixed_position: sprite.fixed_position,
+            padding: sprite.padding,
+            lossless: sprite.lossless,
+        };
+        placements.push(Placement { index: placement.index, x: x0, y: y0 });
+    }
+
+    // Sprites clamped to 1px may poke past the scaled bounds
+    let width = placements
+        .iter()
+        .map(|p| p.x + scaled_images[p.index].image.width())
+        .fold((layout.width as f32 * scale).ceil() as u32, u32::max);
+    let height = placements
+        .iter()
+        .map(|p| p.y + scaled_images[p.index].image.height())
+        .fold((layout.height as f32 * scale).ceil() as u32, u32::max);
+
+    let layout = Layout { scale: layout.scale * scale, width, height, placements };
+    (layout, scaled_images)
+}
+
+// Produce several resolutions in one pass (e.g. @1x/@2x/@0.5x). With
+// `share_layout` all variants are derived from a single full-size layout;
+// otherwise every scale is packed on its own.
+pub fn pack_atlas_variants(
+    originals: &[SourceSprite],
+    padding: u32,
+    options: &AtlasOptions,
+    variants: &[AtlasVariant],
+    share_layout: bool,
+) -> Result<Vec<AtlasVariantOutput>, AppError> {
+    if variants.iter().any(|v| v.scale <= 0.0) {
+        return Err(AppError::invalid("variants", "Variant scales must be positive"));
+    }
+    if options.tileset.is_some() {
+        return Err(AppError::invalid(
+            "variants",
+            "Scale variants are not supported in tileset mode",
+        ));
+    }
+
+    let (trimmed, shapes) = match &options.polygon {
+        Some(polygon) => trim_to_polygons(originals, polygon),
+        None => (Vec::new(), Vec::new()),
+    };
+    let originals = if shapes.is_empty() { originals } else { &trimmed };
+
+    let shared = if share_layout {
+        Some(layout_with_fallback(originals, padding, options)?)
+    } else {
+        None
+    };
+
+    let mut outputs = Vec::with_capacity(variants.len());
+    for variant in variants {
+        let (layout, images) = match &shared {
+            Some((layout, images)) => scale_layout(layout, images, variant.scale, options),
+            None => {
+                let scaled = scale_sprites(originals, variant.scale, options);
+                let (mut layout, images) = layout_with_fallback(&scaled, padding, options)?;
+                layout.scale *= variant.scale;
+                (layout, images)
+            }
+        };
+
+        let image_name = format!("atlas{}.{}", variant.suffix, options.format.extension());
+        let atlas = render_atlas(&images, &layout, options, &image_name, &shapes)?;
+        outputs.push(AtlasVariantOutput {
+            scale: variant.scale,
+            suffix: variant.suffix.clone(),
+            image_name,
+            image_base64: atlas.image_base64,
+            json: atlas.json,
+            size_bytes: atlas.size_bytes,
+            companions: atlas.companions,
+            issues: Vec::new(),
+            image_bytes: atlas.image_bytes,
+            uv_base64: atlas.uv_base64,
+            uv_bytes: atlas.uv_bytes,
+            binary_base64: atlas.binary_base64,
+            binary_bytes: atlas.binary_bytes,
+        });
+    }
 
-    Err("Images too large to pack even at 20% scale".to_string())
+    Ok(outputs)
 }
@@ -0,0 +1,300 @@
+use image::RgbaImage;
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BcFormat {
+    // RGB + 1-bit alpha, 8 bytes per block
+    Bc1,
+    // RGB + interpolated alpha, 16 bytes per block
+    Bc3,
+    // High quality RGBA (mode 6 only), 16 bytes per block
+    Bc7,
+}
+
+impl BcFormat {
+    pub fn block_size(self) -> usize {
+        match self {
+            BcFormat::Bc1 => 8,
+            BcFormat::Bc3 | BcFormat::Bc7 => 16,
+        }
+    }
+}
+
+// The 4x4 block at (bx, by); edge blocks repeat the last row/column
+fn read_block(img: &RgbaImage, bx: u32, by: u32) -> [[u8; 4]; 16] {
+    let mut block = [[0u8; 4]; 16];
+    for (i, texel) in block.iter_mut().enumerate() {
+        let x = (bx * 4 + i as u32 % 4).min(img.width() - 1);
+        let y = (by * 4 + i as u32 / 4).min(img.height() - 1);
+        *texel = img.get_pixel(x, y).0;
+    }
+    block
+}
+
+// Dominant direction of the points via a few rounds of power iteration
+fn principal_axis<const N: usize>(points: &[[f32; N]]) -> ([f32; N], [f32; N]) {
+    let mut mean = [0f32; N];
+    for p in points {
+        for c in 0..N {
+            mean[c] += p[c] / points.len() as f32;
+        }
+    }
+
+    let mut cov = [[0f32; N]; N];
+    for p in points {
+        for i in 0..N {
+            for j in 0..N {
+                cov[i][j] += (p[i] - mean[i]) * (p[j] - mean[j]);
+            }
+        }
+    }
+
+    let mut axis = [1f32; N];
+    for _ in 0..8 {
+        let mut next = [0f32; N];
+        for i in 0..N {
+            for j in 0..N {
+                next[i] += cov[i][j] * axis[j];
+            }
+        }
+        let len = next.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if len < 1e-6 {
+            break;
+        }
+        axis = next.map(|v| v / len);
+    }
+
+    (mean, axis)
+}
+
+// Endpoints of the principal axis line, spanning the projected points
+fn endpoints<const N: usize>(points: &[[f32; N]]) -> ([f32; N], [f32; N]) {
+    let (mean, axis) = principal_axis(points);
+    let (mut min, mut max) = (f32::MAX, f32::MIN);
+    for p in points {
+        let t: f32 = (0..N).map(|c| (p[c] - mean[c]) * axis[c]).sum();
+        min = min.min(t);
+        max = max.max(t);
+    }
+
+    let at = |t: f32| {
+        let mut e = [0f32; N];
+        for c in 0..N {
+            e[c] = (mean[c] + axis[c] * t).clamp(0.0, 255.0);
+        }
+        e
+    };
+    (at(max), at(min))
+}
+
+fn distance_sq<const N: usize>(a: &[f32; N], b: &[f32; N]) -> f32 {
+    (0..N).map(|c| (a[c] - b[c]) * (a[c] - b[c])).sum()
+}
+
+fn nearest<const N: usize>(palette: &[[f32; N]], p: &[f32; N]) -> usize {
+    (0..palette.len())
+        .min_by(|&a, &b| distance_sq(&palette[a], p).total_cmp(&distance_sq(&palette[b], p)))
+        .unwrap_or(0)
+}
+
+fn to_565(c: [f32; 3]) -> u16 {
+    let r = (c[0].clamp(0.0, 255.0) * 31.0 / 255.0).round() as u16;
+    let g = (c[1].clamp(0.0, 255.0) * 63.0 / 255.0).round() as u16;
+    let b = (c[2].clamp(0.0, 255.0) * 31.0 / 255.0).round() as u16;
+    (r << 11) | (g << 5) | b
+}
+
+fn from_565(c: u16) -> [f32; 3] {
+    let r = ((c >> 11) & 31) as f32;
+    let g = ((c >> 5) & 63) as f32;
+    let b = (c & 31) as f32;
+    [r * 255.0 / 31.0, g * 255.0 / 63.0, b * 255.0 / 31.0]
+}
+
+fn lerp3(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    [0, 1, 2].map(|c| a[c] + (b[c] - a[c]) * t)
+}
+
+// BC1 color block. `allow_transparent` enables the 3-color + transparent mode
+// for texels with alpha < 128 (BC1 only; BC3 always decodes 4-color mode).
+fn encode_color_block(block: &[[u8; 4]; 16], allow_transparent: bool) -> [u8; 8] {
+    let transparent: Vec<bool> = block
+        .iter()
+        .map(|t| allow_transparent && t[3] < 128)
+        .collect();
+    let opaque: Vec<[f32; 3]> = block
+        .iter()
+        .zip(&transparent)
+        .filter(|(_, &t)| !t)
+        .map(|(t, _)| [t[0] as f32, t[1] as f32, t[2] as f32])
+        .collect();
+
+    let mut out = [0u8; 8];
+    if opaque.is_empty() {
+        // Fully transparent: 3-color mode with every index = 3
+        out[2..4].copy_from_slice(&0xFFFFu16.to_le_bytes());
+        out[4..8].copy_from_slice(&[0xFF; 4]);
+        return out;
+    }
+
+    let (hi, lo) = endpoints(&opaque);
+    let (mut c0, mut c1) = (to_565(hi), to_565(lo));
+    let three_color = transparent.iter().any(|&t| t);
+
+    // Endpoint order selects the mode: c0 > c1 is 4-color, c0 <= c1 is 3-color
+    if (three_color && c0 > c1) || (!three_color && c0 < c1) {
+        std::mem::swap(&mut c0, &mut c1);
+    }
+    let (e0, e1) = (from_565(c0), from_565(c1));
+    let palette: Vec<[f32; 3]> = if three_color {
+        vec![e0, e1, lerp3(e0, e1, 0.5)]
+    } else if c0 == c1 {
+        vec![e0]
+    } else {
+        vec![e0, e1, lerp3(e0, e1, 1.0 / 3.0), lerp3(e0, e1, 2.0 / 3.0)]
+    };
+
+    let mut indices = 0u32;
+    for (i, texel) in block.iter().enumerate() {
+        let index = if transparent[i] {
+            3
+        } else {
+            nearest(
+                &palette,
+                &[texel[0] as f32, texel[1] as f32, texel[2] as f32],
+            ) as u32
+        };
+        indices |= index << (2 * i);
+    }
+
+    out[0..2].copy_from_slice(&c0.to_le_bytes());
+    out[2..4].copy_from_slice(&c1.to_le_bytes());
+    out[4..8].copy_from_slice(&indices.to_le_bytes());
+    out
+}
+
+// BC4-style 8-value interpolated alpha block used by BC3
+fn encode_alpha_block(block: &[[u8; 4]; 16]) -> [u8; 8] {
+    let a0 = block.iter().map(|t| t[3]).max().unwrap_or(255);
+    let a1 = block.iter().map(|t| t[3]).min().unwrap_or(255);
+
+    let palette: Vec<[f32; 1]> = (0..8)
+        .map(|i| match i {
+            0 => [a0 as f32],
+            1 => [a1 as f32],
+            _ => [((8 - i) as f32 * a0 as f32 + (i - 1) as f32 * a1 as f32) / 7.0],
+        })
+        .collect();
+
+    let mut indices = 0u64;
+    if a0 > a1 {
+        for (i, texel) in block.iter().enumerate() {
+            indices |= (nearest(&palette, &[texel[3] as f32]) as u64) << (3 * i);
+        }
+    }
+
+    let mut out = [0u8; 8];
+    out[0] = a0;
+    out[1] = a1;
+    out[2..8].copy_from_slice(&indices.to_le_bytes()[0..6]);
+    out
+}
+
+// Little-endian bit writer for BC7's 128-bit blocks
+struct BitWriter {
+    bits: u128,
+    pos: u32,
+}
+
+impl BitWriter {
+    fn push(&mut self, value: u32, count: u32) {
+        self.bits |= ((value as u128) & ((1u128 << count) - 1)) << self.pos;
+        self.pos += count;
+    }
+}
+
+const BC7_WEIGHTS4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+// Quantize an endpoint to 7 bits per channel plus a shared p-bit
+fn quantize_bc7_endpoint(e: [f32; 4]) -> ([u32; 4], u32) {
+    let mut best = ([0u32; 4], 0u32, f32::MAX);
+    for p in 0..2u32 {
+        let mut q = [0u32; 4];
+        let mut error = 0.0;
+        for c in 0..4 {
+            q[c] = ((e[c] - p as f32) / 2.0).round().clamp(0.0, 127.0) as u32;
+            let decoded = ((q[c] << 1) | p) as f32;
+            error += (decoded - e[c]) * (decoded - e[c]);
+        }
+        if error < best.2 {
+            best = (q, p, error);
+        }
+    }
+    (best.0, best.1)
+}
+
+// BC7 mode 6: one subset, RGBA 7.7.7.7 endpoints with p-bits, 4-bit indices
+fn encode_bc7_block(block: &[[u8; 4]; 16]) -> [u8; 16] {
+    let texels: Vec<[f32; 4]> = block.iter().map(|t| t.map(|v| v as f32)).collect();
+    let (hi, lo) = endpoints(&texels);
+
+    let (mut q0, mut p0) = quantize_bc7_endpoint(hi);
+    let (mut q1, mut p1) = quantize_bc7_endpoint(lo);
+
+    let decode = |q: [u32; 4], p: u32| q.map(|v| (v << 1) | p);
+    let palette_for = |e0: [u32; 4], e1: [u32; 4]| -> Vec<[f32; 4]> {
+        BC7_WEIGHTS4
+            .iter()
+            .map(|&w| [0, 1, 2, 3].map(|c| (((64 - w) * e0[c] + w * e1[c] + 32) >> 6) as f32))
+            .collect()
+    };
+
+    let palette = palette_for(decode(q0, p0), decode(q1, p1));
+    let mut indices: Vec<u32> = texels.iter().map(|t| nearest(&palette, t) as u32).collect();
+
+    // The anchor texel's index MSB is implicit 0: swap endpoints if needed
+    if indices[0] >= 8 {
+        std::mem::swap(&mut q0, &mut q1);
+        std::mem::swap(&mut p0, &mut p1);
+        for index in &mut indices {
+            *index = 15 - *index;
+        }
+    }
+
+    let mut writer = BitWriter { bits: 0, pos: 0 };
+    writer.push(1 << 6, 7); // mode 6
+    for c in 0..4 {
+        writer.push(q0[c], 7);
+        writer.push(q1[c], 7);
+    }
+    writer.push(p0, 1);
+    writer.push(p1, 1);
+    for (i, &index) in indices.iter().enumerate() {
+        writer.push(index, if i == 0 { 3 } else { 4 });
+    }
+
+    writer.bits.to_le_bytes()
+}
+
+pub fn compress(img: &RgbaImage, format: BcFormat) -> Vec<u8> {
+    let blocks_x = img.width().div_ceil(4);
+    let blocks_y = img.height().div_ceil(4);
+    let mut out = Vec::with_capacity((blocks_x * blocks_y) as usize * format.block_size());
+
+    for by in 0..blocks_y {
+        for bx in 0..blocks_x {
+            let block = read_block(img, bx, by);
+            match format {
+                BcFormat::Bc1 => out.extend_from_slice(&encode_color_block(&block, true)),
+                BcFormat::Bc3 => {
+                    out.extend_from_slice(&encode_alpha_block(&block));
+                    out.extend_from_slice(&encode_color_block(&block, false));
+                }
+                BcFormat::Bc7 => out.extend_from_slice(&encode_bc7_block(&block)),
+            }
+        }
+    }
+
+    out
+}
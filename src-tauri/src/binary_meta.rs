@@ -0,0 +1,157 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+use crate::error::AppError;
+
+// Compact binary form of the atlas JSON for runtimes where parsing JSON is too
+// slow. Little-endian; `str` is a u16 byte length followed by UTF-8.
+//   header:    magic "TPAT", u32 version (1), u32 width, u32 height,
+//              f32 scale, str image
+//   frames:    u32 count, then per frame in name order: str name,
+//              u32 x, y, w, h, u32 trim x, trim y, u32 source w, h,
+//              i32 offset x, y, f32 pivot x, y, u8 flags (1 rotated, 2 trimmed)
+//   animations: u32 count, then per animation in name order: str name,
+//              f32 frame rate, u32 frame count, u32 frame index per frame
+// Polygon meshes and UVs are not included.
+const MAGIC: &[u8; 4] = b"TPAT";
+const VERSION: u32 = 1;
+
+const ROTATED: u8 = 1;
+const TRIMMED: u8 = 2;
+
+#[derive(Deserialize)]
+struct Rect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize)]
+struct Size {
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize)]
+struct Point<T> {
+    x: T,
+    y: T,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Frame {
+    frame: Rect,
+    rotated: bool,
+    trimmed: bool,
+    sprite_source_size: Rect,
+    source_size: Size,
+    pivot: Point<f32>,
+    offset: Point<i32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Animation {
+    frames: Vec<String>,
+    frame_rate: f32,
+}
+
+#[derive(Deserialize)]
+struct Meta {
+    image: String,
+    size: Size,
+    scale: f32,
+}
+
+#[derive(Deserialize)]
+struct Atlas {
+    frames: BTreeMap<String, Frame>,
+    #[serde(default)]
+    animations: BTreeMap<String, Animation>,
+    meta: Meta,
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u32(&mut self, v: u32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn i32(&mut self, v: i32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn f32(&mut self, v: f32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn str(&mut self, s: &str) -> Result<(), AppError> {
+        let len = u16::try_from(s.len())
+            .map_err(|_| AppError::encode(format!("\"{}\" is too long for binary metadata", s)))?;
+        self.0.extend_from_slice(&len.to_le_bytes());
+        self.0.extend_from_slice(s.as_bytes());
+        Ok(())
+    }
+}
+
+// Convert atlas JSON in this app's Phaser layout to the binary format
+pub fn from_json(json: &str) -> Result<Vec<u8>, AppError> {
+    let atlas: Atlas = serde_json::from_str(json).map_err(AppError::decode)?;
+    let index: BTreeMap<&str, u32> = atlas
+        .frames
+        .keys()
+        .enumerate()
+        .map(|(i, name)| (name.as_str(), i as u32))
+        .collect();
+
+    let mut out = Writer(MAGIC.to_vec());
+    out.u32(VERSION);
+    out.u32(atlas.meta.size.w);
+    out.u32(atlas.meta.size.h);
+    out.f32(atlas.meta.scale);
+    out.str(&atlas.meta.image)?;
+
+    out.u32(atlas.frames.len() as u32);
+    for (name, f) in &atlas.frames {
+        out.str(name)?;
+        for v in [f.frame.x, f.frame.y, f.frame.w, f.frame.h] {
+            out.u32(v);
+        }
+        out.u32(f.sprite_source_size.x);
+        out.u32(f.sprite_source_size.y);
+        out.u32(f.source_size.w);
+        out.u32(f.source_size.h);
+        out.i32(f.offset.x);
+        out.i32(f.offset.y);
+        out.f32(f.pivot.x);
+        out.f32(f.pivot.y);
+        let mut flags = 0;
+        if f.rotated {
+            flags |= ROTATED;
+        }
+        if f.trimmed {
+            flags |= TRIMMED;
+        }
+        out.0.push(flags);
+    }
+
+    out.u32(atlas.animations.len() as u32);
+    for (name, animation) in &atlas.animations {
+        out.str(name)?;
+        out.f32(animation.frame_rate);
+        out.u32(animation.frames.len() as u32);
+        for frame in &animation.frames {
+            let i = index.get(frame.as_str()).ok_or_else(|| {
+                AppError::decode(format!(
+                    "Animation \"{}\" refers to missing frame \"{}\"",
+                    name, frame
+                ))
+            })?;
+            out.u32(*i);
+        }
+    }
+    Ok(out.0)
+}
@@ -0,0 +1,93 @@
+use image::{ColorType, DynamicImage, Rgba32FImage, RgbaImage};
+use rayon::prelude::*;
+use serde::Deserialize;
+
+use crate::color_profile::linear_to_srgb;
+
+// 4x4 ordered dither thresholds, in sixteenths
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+// Largest half-float value; anything brighter is treated as this
+const MAX_HDR: f32 = 65504.0;
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ToneMap {
+    // Cut everything above white
+    #[default]
+    Clamp,
+    // x / (1 + x): keeps highlight detail, flattens contrast
+    Reinhard,
+    // Filmic curve approximating the ACES reference transform
+    Aces,
+}
+
+// How 16-bit and floating point sources are brought down to 8 bits
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(default)]
+pub struct DepthOptions {
+    pub tone_map: ToneMap,
+    // Exposure adjustment in stops, applied to HDR sources before tone mapping
+    pub exposure: f32,
+    // Ordered dithering instead of rounding, to hide banding in smooth gradients
+    pub dither: bool,
+}
+
+pub fn is_float(img: &DynamicImage) -> bool {
+    matches!(img.color(), ColorType::Rgb32F | ColorType::Rgba32F)
+}
+
+fn aces(x: f32) -> f32 {
+    (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+}
+
+// HDR formats (EXR, Radiance) hold scene-linear light, not display values.
+// The result is sRGB encoded with values in 0-1.
+pub fn tone_map(img: &DynamicImage, options: &DepthOptions) -> Rgba32FImage {
+    let scale = options.exposure.exp2();
+    let mut out = img.to_rgba32f();
+    out.par_chunks_mut(4).for_each(|p| {
+        for v in &mut p[..3] {
+            // NaN becomes 0 here
+            let x = (*v * scale).clamp(0.0, MAX_HDR);
+            let mapped = match options.tone_map {
+                ToneMap::Clamp => x,
+                ToneMap::Reinhard => x / (1.0 + x),
+                ToneMap::Aces => aces(x),
+            };
+            *v = linear_to_srgb(mapped);
+        }
+        p[3] = p[3].clamp(0.0, 1.0);
+    });
+    out
+}
+
+// 8-bit output for any source; 8-bit images pass through untouched. Float
+// images must already be display encoded (see `tone_map`).
+pub fn reduce_to_8bit(img: DynamicImage, dither: bool) -> DynamicImage {
+    if matches!(
+        img.color(),
+        ColorType::L8 | ColorType::La8 | ColorType::Rgb8 | ColorType::Rgba8
+    ) {
+        return img;
+    }
+    let src = img.to_rgba32f();
+    let width = src.width() as usize;
+    let mut out = RgbaImage::new(src.width(), src.height());
+    out.par_chunks_mut(4)
+        .zip(src.par_chunks(4))
+        .enumerate()
+        .for_each(|(i, (o, s))| {
+            let offset = if dither {
+                let (x, y) = (i % width, i / width);
+                (BAYER[y % 4][x % 4] as f32 + 0.5) / 16.0 - 0.5
+            } else {
+                0.0
+            };
+            for c in 0..3 {
+                o[c] = (s[c] * 255.0 + offset).round().clamp(0.0, 255.0) as u8;
+            }
+            o[3] = (s[3] * 255.0).round().clamp(0.0, 255.0) as u8;
+        });
+    DynamicImage::ImageRgba8(out)
+}
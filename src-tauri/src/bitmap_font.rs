@@ -0,0 +1,324 @@
+use ab_glyph::{point, Font, FontVec, GlyphId, OutlineCurve, PxScale, ScaleFont};
+use image::{imageops, DynamicImage, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::atlas_packer::{pack_unscaled, SortStrategy, SourceSprite, UnscaledLayout};
+use crate::error::AppError;
+use crate::image_io::encode_png_base64;
+use crate::sdf::{self, SdfOptions, Shape};
+
+const MAX_FONT_SIZE: f32 = 512.0;
+
+// Kerning is looked up for every pair of characters; larger sets are mostly
+// CJK, which fonts rarely kern
+const MAX_KERNING_CHARS: usize = 1024;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BitmapFontOptions {
+    // Em size in pixels
+    pub size: f32,
+    // Characters to include; duplicates are ignored
+    pub charset: String,
+    // Empty pixels around each glyph in the atlas
+    pub padding: u32,
+    // Name written to the .fnt `info` line, the font file name by default
+    pub face: Option<String>,
+    // Page file the .fnt refers to
+    pub image_name: String,
+    // Render glyphs as distance fields instead of coverage
+    pub distance_field: Option<SdfOptions>,
+}
+
+impl Default for BitmapFontOptions {
+    fn default() -> Self {
+        BitmapFontOptions {
+            size: 32.0,
+            // Printable ASCII
+            charset: (' '..='~').collect(),
+            padding: 1,
+            face: None,
+            image_name: "font.png".to_string(),
+            distance_field: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct BitmapFontOutput {
+    pub image_base64: String,
+    // BMFont text format
+    pub fnt: String,
+    pub width: u32,
+    pub height: u32,
+    pub glyph_count: usize,
+    // Requested characters the font has no glyph for
+    pub missing: String,
+}
+
+struct RasterGlyph {
+    ch: char,
+    id: GlyphId,
+    // None for glyphs without pixels, such as space
+    image: Option<RgbaImage>,
+    x_offset: i32,
+    y_offset: i32,
+    x_advance: i32,
+}
+
+// The glyph's outline as a distance field, with the field's margin on every
+// side; the offsets move back by that margin
+fn render_field(
+    font: &FontVec,
+    scale: PxScale,
+    base: f32,
+    glyph: &mut RasterGlyph,
+    field: &SdfOptions,
+) {
+    let Some(outline) = font.outline(glyph.id) else {
+        return;
+    };
+    let scaled = font.as_scaled(scale);
+    let (sx, sy) = (scaled.h_scale_factor(), scaled.v_scale_factor());
+    let to_px = |p: ab_glyph::Point| (p.x * sx, base - p.y * sy);
+
+    let mut shape = Shape::default();
+    let mut contour: Vec<Vec<(f32, f32)>> = Vec::new();
+    for curve in &outline.curves {
+        let edge = match *curve {
+            OutlineCurve::Line(a, b) => vec![to_px(a), to_px(b)],
+            OutlineCurve::Quad(a, b, c) => sdf::flatten_quad(to_px(a), to_px(b), to_px(c)),
+            OutlineCurve::Cubic(a, b, c, d) => {
+                sdf::flatten_cubic(to_px(a), to_px(b), to_px(c), to_px(d))
+            }
+        };
+        // A curve that doesn't continue the previous one starts a new contour
+        if contour
+            .last()
+            .is_some_and(|prev| prev[prev.len() - 1] != edge[0])
+        {
+            shape.add_contour(std::mem::take(&mut contour));
+        }
+        contour.push(edge);
+    }
+    shape.add_contour(contour);
+
+    let Some((min, max)) = shape.bounds() else {
+        return;
+    };
+    let margin = field.margin() as f32;
+    let (x0, y0) = ((min.0 - margin).floor(), (min.1 - margin).floor());
+    let width = ((max.0 + margin).ceil() - x0) as u32;
+    let height = ((max.1 + margin).ceil() - y0) as u32;
+    shape.transform(|(x, y)| (x - x0, y - y0));
+
+    glyph.image = Some(sdf::render(&shape, width, height, field));
+    glyph.x_offset = x0 as i32;
+    glyph.y_offset = y0 as i32;
+}
+
+// White glyphs with coverage in alpha (or a distance field), positioned on a
+// baseline `base` pixels below the top of the line
+fn rasterize(
+    font: &FontVec,
+    scale: PxScale,
+    base: f32,
+    ch: char,
+    field: Option<&SdfOptions>,
+) -> RasterGlyph {
+    let scaled = font.as_scaled(scale);
+    let id = font.glyph_id(ch);
+    let mut glyph = RasterGlyph {
+        ch,
+        id,
+        image: None,
+        x_offset: 0,
+        y_offset: 0,
+        x_advance: scaled.h_advance(id).round() as i32,
+    };
+    if let Some(field) = field {
+        render_field(font, scale, base, &mut glyph, field);
+        return glyph;
+    }
+
+    let outline = font.outline_glyph(id.with_scale_and_position(scale, point(0.0, base)));
+    if let Some(outline) = outline {
+        let bounds = outline.px_bounds();
+        let (width, height) = (bounds.width() as u32, bounds.height() as u32);
+        if width > 0 && height > 0 {
+            let mut image = RgbaImage::new(width, height);
+            outline.draw(|x, y, coverage| {
+                if x < width && y < height {
+                    let alpha = (coverage * 255.0).round().clamp(0.0, 255.0) as u8;
+                    image.put_pixel(x, y, Rgba([255, 255, 255, alpha]));
+                }
+            });
+            glyph.image = Some(image);
+            glyph.x_offset = bounds.min.x as i32;
+            glyph.y_offset = bounds.min.y as i32;
+        }
+    }
+    glyph
+}
+
+fn glyph_sprite(glyph: &RasterGlyph, image: &RgbaImage) -> SourceSprite {
+    SourceSprite {
+        name: (glyph.ch as u32).to_string(),
+        image: DynamicImage::ImageRgba8(image.clone()),
+        offset_x: 0,
+        offset_y: 0,
+        animation: None,
+        companions: BTreeMap::new(),
+        group: None,
+        keep_together: None,
+        fixed_position: None,
+        padding: None,
+        lossless: false,
+    }
+}
+
+// Rasterize `options.charset` from a TTF / OTF file into one atlas page and
+// describe it as a BMFont
+pub fn generate(
+    font_path: &Path,
+    options: &BitmapFontOptions,
+) -> Result<BitmapFontOutput, AppError> {
+    if !(options.size > 0.0 && options.size <= MAX_FONT_SIZE) {
+        return Err(AppError::invalid(
+            "size",
+            format!("Font size must be between 0 and {}", MAX_FONT_SIZE),
+        ));
+    }
+    let data =
+        std::fs::read(font_path).map_err(|e| AppError::from(e).context(font_path.display()))?;
+    let font = FontVec::try_from_vec(data)
+        .map_err(|e| AppError::decode(e).context(font_path.display()))?;
+
+    // Keep the order the characters were given in
+    let mut seen = BTreeSet::new();
+    let chars: Vec<char> = options
+        .charset
+        .chars()
+        .filter(|c| seen.insert(*c))
+        .collect();
+    let (chars, missing): (Vec<char>, Vec<char>) =
+        chars.into_iter().partition(|&c| font.glyph_id(c).0 != 0);
+    if chars.is_empty() {
+        return Err(AppError::invalid(
+            "charset",
+            "The font has none of the requested characters",
+        ));
+    }
+
+    let scale = font
+        .pt_to_px_scale(options.size)
+        .unwrap_or(options.size.into());
+    let scaled = font.as_scaled(scale);
+    let base = scaled.ascent().round();
+    let line_height = (scaled.ascent() - scaled.descent() + scaled.line_gap()).ceil() as i32;
+
+    let glyphs: Vec<RasterGlyph> = chars
+        .iter()
+        .map(|&c| rasterize(&font, scale, base, c, options.distance_field.as_ref()))
+        .collect();
+    let visible: Vec<(usize, &RgbaImage)> = glyphs
+        .iter()
+        .enumerate()
+        .filter_map(|(i, g)| g.image.as_ref().map(|image| (i, image)))
+        .collect();
+    let sprites: Vec<SourceSprite> = visible
+        .iter()
+        .map(|&(i, image)| glyph_sprite(&glyphs[i], image))
+        .collect();
+
+    // A font of only blank glyphs still gets a (1x1) page
+    let layout = if sprites.is_empty() {
+        UnscaledLayout {
+            width: 1,
+            height: 1,
+            positions: Vec::new(),
+        }
+    } else {
+        pack_unscaled(&sprites, options.padding, SortStrategy::Height)?
+    };
+    let mut page = RgbaImage::new(layout.width, layout.height);
+    let mut placed = vec![(0, 0); glyphs.len()];
+    for (&(i, image), &(x, y)) in visible.iter().zip(&layout.positions) {
+        imageops::replace(&mut page, image, x as i64, y as i64);
+        placed[i] = (x, y);
+    }
+
+    let face = match &options.face {
+        Some(face) => face.clone(),
+        None => font_path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    };
+    // Distance field glyphs carry the field's margin as padding
+    let margin = options.distance_field.map_or(0, |f| f.margin());
+    let mut fnt = String::new();
+    let _ = writeln!(
+        fnt,
+        "info face=\"{}\" size={} bold=0 italic=0 charset=\"\" unicode=1 stretchH=100 smooth=1 \
+         aa=1 padding={m},{m},{m},{m} spacing={},{}",
+        face.replace('"', "'"),
+        options.size.round(),
+        options.padding,
+        options.padding,
+        m = margin
+    );
+    let _ = writeln!(
+        fnt,
+        "common lineHeight={} base={} scaleW={} scaleH={} pages=1 packed=0",
+        line_height,
+        base as i32,
+        page.width(),
+        page.height()
+    );
+    let _ = writeln!(fnt, "page id=0 file=\"{}\"", options.image_name);
+    let _ = writeln!(fnt, "chars count={}", glyphs.len());
+    for (glyph, &(x, y)) in glyphs.iter().zip(&placed) {
+        let (w, h) = glyph.image.as_ref().map_or((0, 0), |i| i.dimensions());
+        let _ = writeln!(
+            fnt,
+            "char id={} x={} y={} width={} height={} xoffset={} yoffset={} xadvance={} page=0 \
+             chnl=15",
+            glyph.ch as u32, x, y, w, h, glyph.x_offset, glyph.y_offset, glyph.x_advance
+        );
+    }
+
+    let mut kernings = Vec::new();
+    if glyphs.len() <= MAX_KERNING_CHARS {
+        for first in &glyphs {
+            for second in &glyphs {
+                let amount = scaled.kern(first.id, second.id).round() as i32;
+                if amount != 0 {
+                    kernings.push((first.ch as u32, second.ch as u32, amount));
+                }
+            }
+        }
+    }
+    if !kernings.is_empty() {
+        let _ = writeln!(fnt, "kernings count={}", kernings.len());
+        for (first, second, amount) in kernings {
+            let _ = writeln!(
+                fnt,
+                "kerning first={} second={} amount={}",
+                first, second, amount
+            );
+        }
+    }
+
+    Ok(BitmapFontOutput {
+        image_base64: encode_png_base64(&DynamicImage::ImageRgba8(page))?,
+        fnt,
+        width: layout.width,
+        height: layout.height,
+        glyph_count: glyphs.len(),
+        missing: missing.into_iter().collect(),
+    })
+}
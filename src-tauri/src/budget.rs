@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::error::AppError;
+
+// Limits an export must stay within, e.g. for CI to gate on asset bloat.
+// Unset limits are not checked.
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Budget {
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+    pub max_pages: Option<usize>,
+    // Encoded image size of any one page
+    pub max_page_bytes: Option<usize>,
+    // Every exported file together
+    pub max_total_bytes: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetViolation {
+    // The Budget field that was exceeded, e.g. "maxWidth"
+    pub limit: &'static str,
+    // Page image name, None for limits on the whole export
+    pub page: Option<String>,
+    pub allowed: u64,
+    pub actual: u64,
+}
+
+impl fmt::Display for BudgetViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(page) = &self.page {
+            write!(f, "{}: ", page)?;
+        }
+        write!(
+            f,
+            "{} is {} (allowed {})",
+            self.limit, self.actual, self.allowed
+        )
+    }
+}
+
+pub struct PageStats {
+    pub image: String,
+    pub width: u32,
+    pub height: u32,
+    pub image_bytes: usize,
+    // Image plus JSON and every file written with it
+    pub total_bytes: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetReport {
+    pub passed: bool,
+    pub pages: usize,
+    pub total_bytes: usize,
+    pub violations: Vec<BudgetViolation>,
+}
+
+impl BudgetReport {
+    // The report, or an error listing every violation when it failed
+    pub fn into_result(self) -> Result<BudgetReport, AppError> {
+        if self.passed {
+            Ok(self)
+        } else {
+            Err(AppError::budget(self.violations))
+        }
+    }
+}
+
+pub fn check(budget: &Budget, pages: &[PageStats]) -> BudgetReport {
+    let mut violations = Vec::new();
+    let mut limit = |limit: &'static str, page: Option<&str>, allowed: Option<u64>, actual: u64| {
+        if let Some(allowed) = allowed.filter(|&allowed| actual > allowed) {
+            violations.push(BudgetViolation {
+                limit,
+                page: page.map(str::to_string),
+                allowed,
+                actual,
+            });
+        }
+    };
+
+    for page in pages {
+        let name = Some(page.image.as_str());
+        limit(
+            "maxWidth",
+            name,
+            budget.max_width.map(u64::from),
+            page.width as u64,
+        );
+        limit(
+            "maxHeight",
+            name,
+            budget.max_height.map(u64::from),
+            page.height as u64,
+        );
+        limit(
+            "maxPageBytes",
+            name,
+            budget.max_page_bytes.map(|b| b as u64),
+            page.image_bytes as u64,
+        );
+    }
+    let total_bytes: usize = pages.iter().map(|p| p.total_bytes).sum();
+    limit(
+        "maxPages",
+        None,
+        budget.max_pages.map(|p| p as u64),
+        pages.len() as u64,
+    );
+    limit(
+        "maxTotalBytes",
+        None,
+        budget.max_total_bytes.map(|b| b as u64),
+        total_bytes as u64,
+    );
+
+    BudgetReport {
+        passed: violations.is_empty(),
+        pages: pages.len(),
+        total_bytes,
+        violations,
+    }
+}
@@ -0,0 +1,79 @@
+use image::{GrayImage, Luma, RgbaImage};
+use serde::Deserialize;
+
+use crate::error::AppError;
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceChannel {
+    // Rec. 601 luma of the source
+    #[default]
+    Luma,
+    R,
+    G,
+    B,
+    A,
+}
+
+pub struct ChannelSource {
+    pub image: RgbaImage,
+    pub channel: SourceChannel,
+    pub invert: bool,
+}
+
+fn read_channel(pixel: &image::Rgba<u8>, channel: SourceChannel) -> u8 {
+    match channel {
+        SourceChannel::Luma => {
+            ((pixel[0] as u32 * 299 + pixel[1] as u32 * 587 + pixel[2] as u32 * 114) / 1000) as u8
+        }
+        SourceChannel::R => pixel[0],
+        SourceChannel::G => pixel[1],
+        SourceChannel::B => pixel[2],
+        SourceChannel::A => pixel[3],
+    }
+}
+
+// Combine up to four masks into the R, G, B and A channels of one texture.
+// Missing channels are filled with `fill[i]`; all sources must share a size.
+pub fn pack_channels(
+    sources: [Option<ChannelSource>; 4],
+    fill: [u8; 4],
+) -> Result<RgbaImage, AppError> {
+    let mut size: Option<(u32, u32)> = None;
+    for source in sources.iter().flatten() {
+        let dims = source.image.dimensions();
+        match size {
+            Some(expected) if expected != dims => {
+                return Err(AppError::invalid(
+                    "channels",
+                    format!(
+                        "Channel sources differ in size: {}x{} vs {}x{}",
+                        expected.0, expected.1, dims.0, dims.1
+                    ),
+                ));
+            }
+            _ => size = Some(dims),
+        }
+    }
+    let (width, height) =
+        size.ok_or_else(|| AppError::invalid("channels", "No channel sources given"))?;
+
+    let mut out = RgbaImage::from_pixel(width, height, image::Rgba(fill));
+    for (c, source) in sources.iter().enumerate() {
+        let Some(source) = source else { continue };
+        for (dst, src) in out.pixels_mut().zip(source.image.pixels()) {
+            let value = read_channel(src, source.channel);
+            dst[c] = if source.invert { 255 - value } else { value };
+        }
+    }
+    Ok(out)
+}
+
+// Split each channel into its own grayscale image (R, G, B, A order)
+pub fn unpack_channels(img: &RgbaImage) -> [GrayImage; 4] {
+    [0, 1, 2, 3].map(|c| {
+        GrayImage::from_fn(img.width(), img.height(), |x, y| {
+            Luma([img.get_pixel(x, y)[c]])
+        })
+    })
+}
@@ -0,0 +1,303 @@
+use std::path::PathBuf;
+
+use crate::atlas_packer::{load_sprite_dir, pack_atlas, AtlasOptions, DirImport};
+use crate::budget::{self, Budget, PageStats};
+use crate::encode::{OutputFormat, PngStrip};
+use crate::error::AppError;
+use crate::incremental;
+use crate::precompress::Precompression;
+use crate::sidecars::{precompressed_json, sidecars};
+use crate::transform::ResizeFilter;
+
+const USAGE: &str = "\
+Usage: texturepack pack --input <dir> --out <atlas.png> [options]
+
+Options:
+  --input <dir>        Folder of sprite images
+  --recursive          Include sub-folders; nested frames are named by
+                       their relative path (enemies/slime/walk_0)
+  --separator <s>      Joins folder and file names in frame names (default /)
+  --out <file>         Atlas image path; the JSON is written next to it.
+                       The extension picks the encoding (png, webp, avif, jpg)
+  --format <name>      Atlas data format (phaser)
+  --padding <px>       Padding around each sprite (default 2)
+  --premultiply        Premultiply color by alpha
+  --bleed <px>         Bleed edge colors into transparent space
+  --scale-filter <f>   Resampling if the atlas must be scaled down
+                       (nearest, triangle, catmull-rom, gaussian, lanczos3)
+  --optimize           Run a lossless oxipng pass over PNG output
+  --interlace          Write Adam7-interlaced PNG output
+  --strip <mode>       Remove ancillary PNG chunks: safe keeps color
+                       chunks, all removes them too
+  --uvs                Add normalized frame UVs to the JSON and write
+                       a binary .uv sidecar
+  --binary             Also write the metadata in binary form (.bin)
+  --gzip, --brotli     Also write atlas.json.gz / atlas.json.br
+  --max-size <WxH>     Fail without writing if the atlas is larger
+  --max-bytes <n>      Fail without writing if all files together are larger
+  --threads <n>        Worker threads (default: one per core)";
+
+struct PackArgs {
+    input: PathBuf,
+    out: PathBuf,
+    padding: u32,
+    import: DirImport,
+    options: AtlasOptions,
+    threads: Option<usize>,
+}
+
+fn parse_pack_args(args: &[String]) -> Result<PackArgs, AppError> {
+    let mut input = None;
+    let mut out = None;
+    let mut padding = 2;
+    let mut import = DirImport::default();
+    let mut options = AtlasOptions::default();
+    let mut interlace = false;
+    let mut strip = PngStrip::None;
+    let mut threads = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| AppError::invalid(arg, format!("Missing value for {}", arg)))
+        };
+        match arg.as_str() {
+            "--input" => input = Some(PathBuf::from(value()?)),
+            "--out" => out = Some(PathBuf::from(value()?)),
+            "--format" => match value()?.as_str() {
+                "phaser" => {}
+                other => {
+                    return Err(AppError::invalid(
+                        "--format",
+                        format!("Unsupported atlas format: {}", other),
+                    ))
+                }
+            },
+            "--padding" => {
+                padding = value()?
+                    .parse()
+                    .map_err(|_| AppError::invalid("--padding", "Invalid --padding"))?
+            }
+            "--recursive" => import.recursive = true,
+            "--separator" => import.separator = value()?,
+            "--premultiply" => options.premultiply_alpha = true,
+            "--bleed" => {
+                options.color_bleed = value()?
+                    .parse()
+                    .map_err(|_| AppError::invalid("--bleed", "Invalid --bleed"))?
+            }
+            "--optimize" => options.lossless_optimize = true,
+            "--interlace" => interlace = true,
+            "--strip" => {
+                strip = match value()?.as_str() {
+                    "safe" => PngStrip::Safe,
+                    "all" => PngStrip::All,
+                    other => {
+                        return Err(AppError::invalid(
+                            "--strip",
+                            format!("Unknown strip mode: {}", other),
+                        ))
+                    }
+                }
+            }
+            "--uvs" => options.uvs = true,
+            "--binary" => options.binary_metadata = true,
+            "--gzip" => options.precompress_json.push(Precompression::Gzip),
+            "--brotli" => options.precompress_json.push(Precompression::Brotli),
+            "--max-size" => {
+                let value = value()?;
+                let (w, h) = value
+                    .split_once('x')
+                    .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                    .ok_or_else(|| AppError::invalid("--max-size", "Expected WIDTHxHEIGHT"))?;
+                let budget = options.budget.get_or_insert_with(Budget::default);
+                budget.max_width = Some(w);
+                budget.max_height = Some(h);
+            }
+            "--max-bytes" => {
+                options
+                    .budget
+                    .get_or_insert_with(Budget::default)
+                    .max_total_bytes = Some(
+                    value()?
+                        .parse()
+                        .map_err(|_| AppError::invalid("--max-bytes", "Invalid --max-bytes"))?,
+                )
+            }
+            "--threads" => {
+                threads = Some(
+                    value()?
+                        .parse()
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or_else(|| AppError::invalid("--threads", "Invalid --threads"))?,
+                )
+            }
+            "--scale-filter" => {
+                options.scale_filter = match value()?.as_str() {
+                    "nearest" => ResizeFilter::Nearest,
+                    "triangle" => ResizeFilter::Triangle,
+                    "catmull-rom" => ResizeFilter::CatmullRom,
+                    "gaussian" => ResizeFilter::Gaussian,
+                    "lanczos3" => ResizeFilter::Lanczos3,
+                    other => {
+                        return Err(AppError::invalid(
+                            "--scale-filter",
+                            format!("Unknown scale filter: {}", other),
+                        ))
+                    }
+                }
+            }
+            other => {
+                return Err(AppError::invalid(
+                    other,
+                    format!("Unknown option: {}", other),
+                ))
+            }
+        }
+    }
+
+    let input = input.ok_or_else(|| AppError::invalid("--input", "--input is required"))?;
+    let out = out.ok_or_else(|| AppError::invalid("--out", "--out is required"))?;
+    options.format = match out.extension().and_then(|e| e.to_str()) {
+        Some("png") => OutputFormat::Png {
+            color_type: None,
+            interlace,
+            strip,
+        },
+        Some("webp") => OutputFormat::Webp {
+            quality: 80,
+            lossless: true,
+        },
+        Some("avif") => OutputFormat::Avif {
+            quality: 80,
+            speed: 6,
+        },
+        Some("jpg") | Some("jpeg") => OutputFormat::Jpeg {
+            quality: 90,
+            background: None,
+        },
+        _ => {
+            return Err(AppError::invalid(
+                "--out",
+                "--out must end in .png, .webp, .avif or .jpg",
+            ))
+        }
+    };
+
+    Ok(PackArgs {
+        input,
+        out,
+        padding,
+        import,
+        options,
+        threads,
+    })
+}
+
+fn pack(args: &[String]) -> Result<(), AppError> {
+    let args = parse_pack_args(args)?;
+    if let Some(threads) = args.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .map_err(|e| AppError::invalid("--threads", e))?;
+    }
+    let sprites = load_sprite_dir(&args.input, &args.import)?;
+
+    let image_name = args
+        .out
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let output = pack_atlas(&sprites, args.padding, &args.options, &image_name)?;
+    let json_path = args.out.with_extension("json");
+
+    // Files written next to the atlas image and JSON
+    let mut extras = precompressed_json(&image_name, &output.json, &args.options.precompress_json)?;
+    extras.extend(sidecars(
+        &image_name,
+        output.companions,
+        output.uv_bytes,
+        output.binary_bytes,
+        output.tsx,
+    ));
+
+    if let Some(budget) = &args.options.budget {
+        let (width, height) = incremental::atlas_size(&output.json)?;
+        let page = PageStats {
+            image: image_name,
+            width,
+            height,
+            image_bytes: output.image_bytes.len(),
+            total_bytes: output.image_bytes.len()
+                + output.json.len()
+                + extras.iter().map(|(_, data)| data.len()).sum::<usize>(),
+        };
+        budget::check(budget, &[page]).into_result()?;
+    }
+
+    if let Some(parent) = args.out.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&args.out, &output.image_bytes)?;
+    std::fs::write(&json_path, &output.json)?;
+    for (name, data) in extras {
+        std::fs::write(args.out.with_file_name(name), data)?;
+    }
+
+    println!(
+        "Packed {} sprites into {} ({} bytes) and {}",
+        sprites.len(),
+        args.out.display(),
+        output.size_bytes,
+        json_path.display()
+    );
+    Ok(())
+}
+
+// Release builds use the Windows GUI subsystem and start without a console;
+// attach to the one the CLI was run from so its output shows up there
+#[cfg(windows)]
+fn attach_console() {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    // Fails harmlessly when there is no parent console or one is attached
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
+
+// Runs a CLI subcommand and returns its exit code, or None when the
+// arguments aren't a CLI invocation and the GUI should start instead
+pub fn run(args: &[String]) -> Option<i32> {
+    let command = args.first().map(String::as_str)?;
+    if !matches!(command, "pack" | "help" | "--help" | "-h") {
+        return None;
+    }
+    attach_console();
+
+    let result = match command {
+        "pack" => pack(&args[1..]),
+        _ => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+    };
+
+    Some(match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            1
+        }
+    })
+}
@@ -0,0 +1,56 @@
+use arboard::Clipboard;
+use image::RgbaImage;
+use std::borrow::Cow;
+use std::sync::Mutex;
+
+use crate::error::AppError;
+
+// Managed state holding the system clipboard. On Linux the copied image is
+// served by this handle, so it lives as long as the app rather than one call.
+#[derive(Default)]
+pub struct ClipboardState {
+    clipboard: Mutex<Option<Clipboard>>,
+}
+
+fn clipboard_error(e: arboard::Error) -> AppError {
+    match e {
+        arboard::Error::ContentNotAvailable => {
+            AppError::invalid("clipboard", "The clipboard does not contain an image")
+        }
+        e => AppError::io(format!("Clipboard: {}", e)),
+    }
+}
+
+impl ClipboardState {
+    fn with_clipboard<T>(
+        &self,
+        f: impl FnOnce(&mut Clipboard) -> Result<T, arboard::Error>,
+    ) -> Result<T, AppError> {
+        let mut clipboard = self.clipboard.lock().unwrap();
+        if clipboard.is_none() {
+            *clipboard = Some(Clipboard::new().map_err(clipboard_error)?);
+        }
+        f(clipboard.as_mut().unwrap()).map_err(clipboard_error)
+    }
+
+    // The platform bitmap (DIB on Windows, TIFF / PNG on macOS, PNG on Linux)
+    // decoded to RGBA
+    pub fn read_image(&self) -> Result<RgbaImage, AppError> {
+        let data = self.with_clipboard(|c| c.get_image())?;
+        RgbaImage::from_raw(
+            data.width as u32,
+            data.height as u32,
+            data.bytes.into_owned(),
+        )
+        .ok_or_else(|| AppError::decode("Clipboard image has an unexpected size"))
+    }
+
+    pub fn write_image(&self, img: &RgbaImage) -> Result<(), AppError> {
+        let data = arboard::ImageData {
+            width: img.width() as usize,
+            height: img.height() as usize,
+            bytes: Cow::Borrowed(img.as_raw()),
+        };
+        self.with_clipboard(|c| c.set_image(data))
+    }
+}
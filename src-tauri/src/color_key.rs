@@ -0,0 +1,317 @@
+use crate::analysis::cluster_colors;
+use crate::error::AppError;
+use crate::image_io::neighbors;
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+// Tolerance and feather are on a 0-100 scale, mapped onto the max RGB distance (~442)
+const DISTANCE_SCALE: f64 = 4.42;
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ColorToRemove {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub tolerance: u8,
+    // Width of the soft band past `tolerance` where alpha ramps back up to opaque
+    #[serde(default)]
+    pub feather: u8,
+}
+
+impl ColorToRemove {
+    fn distance(&self, pixel: &[u8]) -> f64 {
+        let dr = (pixel[0] as i32 - self.r as i32).abs();
+        let dg = (pixel[1] as i32 - self.g as i32).abs();
+        let db = (pixel[2] as i32 - self.b as i32).abs();
+        ((dr * dr + dg * dg + db * db) as f64).sqrt()
+    }
+
+    // 0.0 = fully removed, 1.0 = untouched
+    fn alpha_factor(&self, pixel: &[u8]) -> f64 {
+        let distance = self.distance(pixel);
+        let hard = self.tolerance as f64 * DISTANCE_SCALE;
+        let soft = self.feather as f64 * DISTANCE_SCALE;
+
+        if distance <= hard {
+            0.0
+        } else if distance < hard + soft {
+            (distance - hard) / soft
+        } else {
+            1.0
+        }
+    }
+
+    // Pull the key's dominant channels down to the level of the others, e.g.
+    // g = min(g, max(r, b)) for a green key, so edges lose their colored fringe
+    fn despill(&self, pixel: &mut [u8]) {
+        let key = [self.r, self.g, self.b];
+        let mean = (self.r as u32 + self.g as u32 + self.b as u32) / 3;
+        let high: Vec<usize> = (0..3).filter(|&c| key[c] as u32 > mean).collect();
+        let low: Vec<usize> = (0..3).filter(|&c| key[c] as u32 <= mean).collect();
+        if high.is_empty() || low.is_empty() {
+            return; // Gray keys have no hue to spill
+        }
+
+        let high_min = high.iter().map(|&c| pixel[c]).min().unwrap_or(0);
+        let low_max = low.iter().map(|&c| pixel[c]).max().unwrap_or(0);
+        if high_min > low_max {
+            let spill = high_min - low_max;
+            for &c in &high {
+                pixel[c] -= spill;
+            }
+        }
+    }
+}
+
+pub fn remove_colors(img: &mut RgbaImage, colors: &[ColorToRemove], despill: bool) {
+    let (width, height) = img.dimensions();
+    // Per pixel: index of the key that affected it most, and whether it was fully removed
+    let mut nearest_key: Vec<Option<usize>> = vec![None; (width * height) as usize];
+    let mut removed = vec![false; (width * height) as usize];
+
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        let idx = (y * width + x) as usize;
+        let mut factor = 1.0f64;
+
+        for (i, color) in colors.iter().enumerate() {
+            let f = color.alpha_factor(&pixel.0);
+            if f < factor {
+                factor = f;
+                nearest_key[idx] = Some(i);
+            }
+        }
+
+        if factor <= 0.0 {
+            pixel[3] = 0; // Set alpha to transparent
+            removed[idx] = true;
+        } else if factor < 1.0 {
+            pixel[3] = (pixel[3] as f64 * factor).round() as u8;
+        }
+    }
+
+    if !despill || colors.is_empty() {
+        return;
+    }
+
+    // Despill the feathered band plus any kept pixel touching a removed one
+    for y in 0..height {
+        for x in 0..width {
+            let idx = (y * width + x) as usize;
+            if removed[idx] {
+                continue;
+            }
+
+            let key = nearest_key[idx].or_else(|| {
+                neighbors(x, y, width, height)
+                    .map(|(nx, ny)| (ny * width + nx) as usize)
+                    .find(|&n| removed[n])
+                    .and_then(|n| nearest_key[n])
+            });
+
+            if let Some(key) = key {
+                colors[key].despill(&mut img.get_pixel_mut(x, y).0);
+            }
+        }
+    }
+}
+
+// Magic wand: clear pixels matching the seed color. With `contiguous` only the
+// region 4-connected to the seed is removed, so enclosed holes in a sprite that
+// happen to share the background color survive.
+pub fn remove_background_floodfill(
+    img: &mut RgbaImage,
+    x: u32,
+    y: u32,
+    tolerance: u8,
+    contiguous: bool,
+) -> Result<(), AppError> {
+    let (width, height) = img.dimensions();
+    if x >= width || y >= height {
+        return Err(AppError::invalid(
+            "x",
+            format!("Seed point ({}, {}) is outside the image", x, y),
+        ));
+    }
+
+    let seed = img.get_pixel(x, y);
+    let key = ColorToRemove {
+        r: seed[0],
+        g: seed[1],
+        b: seed[2],
+        tolerance,
+        feather: 0,
+    };
+    // Already-cleared pixels don't block the fill
+    let matches = |pixel: &[u8]| pixel[3] == 0 || key.alpha_factor(pixel) <= 0.0;
+
+    if !contiguous {
+        for pixel in img.pixels_mut() {
+            if matches(&pixel.0) {
+                pixel[3] = 0;
+            }
+        }
+        return Ok(());
+    }
+
+    let mut visited = vec![false; (width * height) as usize];
+    let mut queue = VecDeque::new();
+    visited[(y * width + x) as usize] = true;
+    queue.push_back((x, y));
+
+    while let Some((cx, cy)) = queue.pop_front() {
+        img.get_pixel_mut(cx, cy)[3] = 0;
+
+        let candidates = [
+            (cx.wrapping_sub(1), cy),
+            (cx + 1, cy),
+            (cx, cy.wrapping_sub(1)),
+            (cx, cy + 1),
+        ];
+        for (nx, ny) in candidates {
+            if nx >= width || ny >= height {
+                continue;
+            }
+            let idx = (ny * width + nx) as usize;
+            if !visited[idx] && matches(&img.get_pixel(nx, ny).0) {
+                visited[idx] = true;
+                queue.push_back((nx, ny));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BackgroundSample {
+    // The outermost row and column of pixels on every side
+    #[default]
+    Border,
+    // A square patch in each corner, for sprites that touch the edges
+    Corners,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct BackgroundOptions {
+    pub sample: BackgroundSample,
+    pub max_colors: usize,
+    // Ignore clusters covering less than this share of the sampled pixels
+    pub min_coverage: f32,
+}
+
+impl Default for BackgroundOptions {
+    fn default() -> Self {
+        BackgroundOptions {
+            sample: BackgroundSample::Border,
+            max_colors: 3,
+            min_coverage: 0.05,
+        }
+    }
+}
+
+// Same shape as ColorToRemove so it can be passed straight to remove_colors
+#[derive(Serialize)]
+pub struct BackgroundSuggestion {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub tolerance: u8,
+    // Share of the sampled pixels this color accounts for
+    pub coverage: f32,
+}
+
+fn sample_points(width: u32, height: u32, sample: BackgroundSample) -> Vec<(u32, u32)> {
+    match sample {
+        BackgroundSample::Border => {
+            let mut points: Vec<(u32, u32)> =
+                (0..width).flat_map(|x| [(x, 0), (x, height - 1)]).collect();
+            points.extend((1..height.saturating_sub(1)).flat_map(|y| [(0, y), (width - 1, y)]));
+            points
+        }
+        BackgroundSample::Corners => {
+            let size = (width.min(height) / 16).max(1);
+            let mut points = Vec::new();
+            for (cx, cy) in [
+                (0, 0),
+                (width - size, 0),
+                (0, height - size),
+                (width - size, height - size),
+            ] {
+                for y in cy..cy + size {
+                    points.extend((cx..cx + size).map(|x| (x, y)));
+                }
+            }
+            points
+        }
+    }
+}
+
+// Likely background colors: cluster the visible edge pixels and keep the big
+// clusters. Each tolerance covers 95% of its cluster's samples, so gradients
+// and JPEG noise in the backdrop are removed along with the base color.
+pub fn suggest_background(
+    img: &RgbaImage,
+    options: &BackgroundOptions,
+) -> Vec<BackgroundSuggestion> {
+    let (width, height) = img.dimensions();
+    if width == 0 || height == 0 {
+        return Vec::new();
+    }
+    let samples: Vec<[u8; 3]> = sample_points(width, height, options.sample)
+        .into_iter()
+        .map(|(x, y)| img.get_pixel(x, y))
+        .filter(|p| p[3] > 0)
+        .map(|p| [p[0], p[1], p[2]])
+        .collect();
+
+    // Every cluster gets its samples, so sprite pixels touching the edge don't
+    // widen a background color's tolerance
+    let clusters = cluster_colors(samples.iter().copied(), usize::MAX, 24.0);
+    let keys: Vec<ColorToRemove> = clusters
+        .iter()
+        .map(|c| ColorToRemove {
+            r: c.r,
+            g: c.g,
+            b: c.b,
+            tolerance: 0,
+            feather: 0,
+        })
+        .collect();
+
+    let mut distances: Vec<Vec<f64>> = vec![Vec::new(); keys.len()];
+    for sample in &samples {
+        let nearest = keys
+            .iter()
+            .map(|key| key.distance(sample))
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((i, d)) = nearest {
+            distances[i].push(d);
+        }
+    }
+
+    clusters
+        .iter()
+        .zip(distances)
+        .filter(|(c, _)| c.ratio >= options.min_coverage)
+        .take(options.max_colors)
+        .map(|(c, mut d)| {
+            d.sort_by(f64::total_cmp);
+            let spread = d
+                .get((d.len() * 95 / 100).min(d.len().saturating_sub(1)))
+                .copied();
+            // One step of margin past the spread, on the 0-100 tolerance scale
+            let tolerance = (spread.unwrap_or(0.0) / DISTANCE_SCALE).ceil() as u8 + 1;
+            BackgroundSuggestion {
+                r: c.r,
+                g: c.g,
+                b: c.b,
+                tolerance: tolerance.min(100),
+                coverage: c.ratio,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,252 @@
+use image::{ColorType, DynamicImage, Rgba32FImage, RgbaImage};
+use rayon::prelude::*;
+
+// Linear sRGB from PCS XYZ (D50), Bradford-adapted
+const XYZ_D50_TO_SRGB: [[f32; 3]; 3] = [
+    [3.133856, -1.616867, -0.490615],
+    [-0.978768, 1.916142, 0.033454],
+    [0.071945, -0.228991, 1.405243],
+];
+
+const IDENTITY: [[f32; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+enum Curve {
+    Gamma(f32),
+    // Samples evenly spaced over 0-1, interpolated linearly
+    Table(Vec<f32>),
+    // ICC parametric curve: function type and parameters g, a, b, c, d, e, f
+    Parametric(u16, [f32; 7]),
+}
+
+impl Curve {
+    fn eval(&self, x: f32) -> f32 {
+        let x = x.clamp(0.0, 1.0);
+        let y = match self {
+            Curve::Gamma(g) => x.powf(*g),
+            Curve::Table(t) => {
+                let pos = x * (t.len() - 1) as f32;
+                let i = (pos as usize).min(t.len() - 2);
+                t[i] + (t[i + 1] - t[i]) * (pos - i as f32)
+            }
+            Curve::Parametric(kind, [g, a, b, c, d, e, f]) => {
+                let power = |x: f32| (a * x + b).max(0.0).powf(*g);
+                match kind {
+                    0 => x.powf(*g),
+                    1 if x >= -b / a => power(x),
+                    1 => 0.0,
+                    2 if x >= -b / a => power(x) + c,
+                    2 => *c,
+                    3 if x >= *d => power(x),
+                    3 => c * x,
+                    _ if x >= *d => power(x) + e,
+                    _ => c * x + f,
+                }
+            }
+        };
+        y.clamp(0.0, 1.0)
+    }
+}
+
+pub fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(v: f32) -> f32 {
+    let v = v.clamp(0.0, 1.0);
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// A matrix / TRC display profile (RGB or gray), reduced to per-channel
+// decoding curves followed by a matrix into linear sRGB
+pub struct ColorProfile {
+    curves: [Curve; 3],
+    to_srgb: [[f32; 3]; 3],
+}
+
+fn be16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn be32(data: &[u8], at: usize) -> Option<usize> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?) as usize)
+}
+
+fn s15fixed16(data: &[u8], at: usize) -> Option<f32> {
+    Some(i32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?) as f32 / 65536.0)
+}
+
+fn find_tag<'a>(icc: &'a [u8], signature: &[u8; 4]) -> Option<&'a [u8]> {
+    let count = be32(icc, 128)?;
+    (0..count).find_map(|i| {
+        let at = 132 + i * 12;
+        if icc.get(at..at + 4)? != signature {
+            return None;
+        }
+        let (offset, size) = (be32(icc, at + 4)?, be32(icc, at + 8)?);
+        icc.get(offset..offset.checked_add(size)?)
+    })
+}
+
+fn parse_curve(tag: &[u8]) -> Option<Curve> {
+    match tag.get(0..4)? {
+        b"curv" => match be32(tag, 8)? {
+            0 => Some(Curve::Gamma(1.0)),
+            1 => Some(Curve::Gamma(be16(tag, 12)? as f32 / 256.0)),
+            n => {
+                let table = (0..n)
+                    .map(|i| Some(be16(tag, 12 + i * 2)? as f32 / 65535.0))
+                    .collect::<Option<Vec<f32>>>()?;
+                Some(Curve::Table(table))
+            }
+        },
+        b"para" => {
+            let kind = be16(tag, 8)?;
+            let count = [1, 3, 4, 5, 7].get(kind as usize)?;
+            let mut params = [0.0; 7];
+            for (i, p) in params.iter_mut().take(*count).enumerate() {
+                *p = s15fixed16(tag, 12 + i * 4)?;
+            }
+            Some(Curve::Parametric(kind, params))
+        }
+        _ => None,
+    }
+}
+
+fn xyz(icc: &[u8], signature: &[u8; 4]) -> Option<[f32; 3]> {
+    let tag = find_tag(icc, signature)?;
+    if tag.get(0..4)? != b"XYZ " {
+        return None;
+    }
+    Some([
+        s15fixed16(tag, 8)?,
+        s15fixed16(tag, 12)?,
+        s15fixed16(tag, 16)?,
+    ])
+}
+
+fn multiply(a: &[[f32; 3]; 3], b: &[[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    let mut out = [[0.0; 3]; 3];
+    for (r, row) in out.iter_mut().enumerate() {
+        for (c, v) in row.iter_mut().enumerate() {
+            *v = (0..3).map(|k| a[r][k] * b[k][c]).sum();
+        }
+    }
+    out
+}
+
+impl ColorProfile {
+    // None for profiles this can't apply (LUT-based, CMYK, Lab, ...); those
+    // images are loaded as before
+    pub fn from_icc(icc: &[u8]) -> Option<ColorProfile> {
+        match icc.get(16..20)? {
+            b"RGB " => {
+                let curve = |sig| find_tag(icc, sig).and_then(parse_curve);
+                let (r, g, b) = (xyz(icc, b"rXYZ")?, xyz(icc, b"gXYZ")?, xyz(icc, b"bXYZ")?);
+                let to_xyz = [[r[0], g[0], b[0]], [r[1], g[1], b[1]], [r[2], g[2], b[2]]];
+                Some(ColorProfile {
+                    curves: [curve(b"rTRC")?, curve(b"gTRC")?, curve(b"bTRC")?],
+                    to_srgb: multiply(&XYZ_D50_TO_SRGB, &to_xyz),
+                })
+            }
+            // Neutral stays neutral, so only the tone curve matters
+            b"GRAY" => {
+                let curve = || find_tag(icc, b"kTRC").and_then(parse_curve);
+                Some(ColorProfile {
+                    curves: [curve()?, curve()?, curve()?],
+                    to_srgb: IDENTITY,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    // sRGB primaries with a plain power curve, for PNGs that only carry gAMA
+    pub fn from_gamma(file_gamma: f32) -> ColorProfile {
+        let decode = 1.0 / file_gamma;
+        ColorProfile {
+            curves: [
+                Curve::Gamma(decode),
+                Curve::Gamma(decode),
+                Curve::Gamma(decode),
+            ],
+            to_srgb: IDENTITY,
+        }
+    }
+
+    // Close enough to sRGB that converting would only add rounding noise
+    pub fn is_srgb(&self) -> bool {
+        let matrix_ok = self
+            .to_srgb
+            .iter()
+            .flatten()
+            .zip(IDENTITY.iter().flatten())
+            .all(|(a, b)| (a - b).abs() < 0.01);
+        let curves_ok = self.curves.iter().all(|curve| {
+            (0..=20).all(|i| {
+                let x = i as f32 / 20.0;
+                (curve.eval(x) - srgb_to_linear(x)).abs() < 0.01
+            })
+        });
+        matrix_ok && curves_ok
+    }
+
+    fn linear_to_output(&self, linear: [f32; 3]) -> [f32; 3] {
+        self.to_srgb.map(|row| {
+            let v: f32 = row.iter().zip(linear).map(|(m, v)| m * v).sum();
+            linear_to_srgb(v)
+        })
+    }
+}
+
+fn to_u8(v: f32) -> u8 {
+    (v * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+// Re-encode the image's colors as sRGB; alpha is kept as is. 8-bit sources
+// come back as 8-bit, anything deeper as sRGB-encoded floats so the caller
+// decides how to reduce the bit depth.
+pub fn convert_to_srgb(img: &DynamicImage, profile: &ColorProfile) -> DynamicImage {
+    let (width, height) = (img.width(), img.height());
+
+    match img.color() {
+        ColorType::L8 | ColorType::La8 | ColorType::Rgb8 | ColorType::Rgba8 => {
+            // 8-bit inputs only have 256 levels per channel, so decode via tables
+            let src = img.to_rgba8();
+            let mut out = RgbaImage::new(width, height);
+            let tables: [Vec<f32>; 3] = [0, 1, 2].map(|c| {
+                (0..256)
+                    .map(|v| profile.curves[c].eval(v as f32 / 255.0))
+                    .collect()
+            });
+            out.par_chunks_mut(4)
+                .zip(src.par_chunks(4))
+                .for_each(|(o, s)| {
+                    let [r, g, b] = profile
+                        .linear_to_output([0, 1, 2].map(|c| tables[c][s[c] as usize]))
+                        .map(to_u8);
+                    o.copy_from_slice(&[r, g, b, s[3]]);
+                });
+            DynamicImage::ImageRgba8(out)
+        }
+        _ => {
+            let src = img.to_rgba32f();
+            let mut out = Rgba32FImage::new(width, height);
+            out.par_chunks_mut(4)
+                .zip(src.par_chunks(4))
+                .for_each(|(o, s)| {
+                    let [r, g, b] =
+                        profile.linear_to_output([0, 1, 2].map(|c| profile.curves[c].eval(s[c])));
+                    o.copy_from_slice(&[r, g, b, s[3].clamp(0.0, 1.0)]);
+                });
+            DynamicImage::ImageRgba32F(out)
+        }
+    }
+}
@@ -0,0 +1,286 @@
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, GenericImageView, RgbaImage};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::encode::{self, OutputFormat, PngColorType};
+use crate::error::AppError;
+use crate::filters::{self, Denoise};
+use crate::image_io::{to_data_url, ImageSource};
+use crate::metrics;
+use crate::quantize::{Dithering, QuantizeSettings};
+use crate::transform::ResizeFilter;
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct CompressSettings {
+    // imagequant quality for PNG output
+    pub quality: u8,
+    // Percent of the original size
+    #[serde(default = "default_scale")]
+    pub scale: u8,
+    #[serde(default)]
+    pub filter: ResizeFilter,
+    #[serde(default)]
+    pub format: OutputFormat,
+    #[serde(default)]
+    pub dithering: Dithering,
+    #[serde(default = "default_max_colors")]
+    pub max_colors: u32,
+    // Smooth sensor / scan noise first; noise wastes palette entries
+    #[serde(default)]
+    pub denoise: Option<Denoise>,
+}
+
+fn default_scale() -> u8 {
+    100
+}
+
+fn default_max_colors() -> u32 {
+    256
+}
+
+pub struct Compressed {
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub fn compress(img: DynamicImage, settings: &CompressSettings) -> Result<Compressed, AppError> {
+    let (orig_width, orig_height) = img.dimensions();
+
+    // Apply scale
+    let new_width = (orig_width as f32 * settings.scale as f32 / 100.0).round() as u32;
+    let new_height = (orig_height as f32 * settings.scale as f32 / 100.0).round() as u32;
+
+    let resized = if settings.scale < 100 {
+        img.resize_exact(new_width, new_height, settings.filter.into())
+    } else {
+        img
+    };
+
+    let mut rgba = resized.to_rgba8();
+    if let Some(method) = settings.denoise {
+        filters::denoise(&mut rgba, method);
+    }
+    let (width, height) = rgba.dimensions();
+
+    let data = match settings.format {
+        // PNG defaults to palette quantization; `quality` drives imagequant
+        OutputFormat::Png { color_type, .. } => {
+            let quantize = QuantizeSettings {
+                quality: settings.quality,
+                max_colors: settings.max_colors,
+                dithering: settings.dithering,
+            };
+            let data = encode::encode_png(
+                &rgba,
+                color_type.unwrap_or(PngColorType::Indexed8),
+                &quantize,
+            )?;
+            encode::finish_png(data, &settings.format, None)?
+        }
+        _ => encode::encode_image(&rgba, &settings.format)?,
+    };
+
+    Ok(Compressed {
+        data,
+        width,
+        height,
+    })
+}
+
+#[derive(Serialize, Clone)]
+pub struct BatchItem {
+    pub index: usize,
+    pub name: String,
+    // Data URL, or the written file path when an output directory is given
+    pub output: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub original_size: usize,
+    pub compressed_size: usize,
+    // Marked lossless only: written without quantization or lossy encoding
+    pub lossless: bool,
+    pub error: Option<AppError>,
+}
+
+#[derive(Serialize)]
+pub struct BatchReport {
+    pub items: Vec<BatchItem>,
+    pub total_original_size: usize,
+    pub total_compressed_size: usize,
+    pub failed: usize,
+}
+
+#[derive(Serialize, Clone)]
+pub struct BatchProgress {
+    pub completed: usize,
+    pub total: usize,
+    pub item: BatchItem,
+}
+
+fn compress_one(
+    index: usize,
+    source: &ImageSource,
+    settings: &CompressSettings,
+    lossless: bool,
+    output_dir: Option<&Path>,
+) -> Result<BatchItem, AppError> {
+    let (img, original_size) = source.load()?;
+    // Scaling still applies so the batch stays consistent
+    let settings = &if lossless {
+        CompressSettings {
+            format: settings.format.lossless(),
+            denoise: None,
+            ..*settings
+        }
+    } else {
+        *settings
+    };
+    let compressed = compress(img, settings)?;
+
+    let name = source.name();
+    let output = match output_dir {
+        Some(dir) => {
+            let path = dir.join(format!("{}.{}", name, settings.format.extension()));
+            std::fs::write(&path, &compressed.data)?;
+            path.to_string_lossy().into_owned()
+        }
+        None => to_data_url(&compressed.data, settings.format.mime_type()),
+    };
+
+    Ok(BatchItem {
+        index,
+        name,
+        output: Some(output),
+        width: compressed.width,
+        height: compressed.height,
+        original_size,
+        compressed_size: compressed.data.len(),
+        lossless,
+        error: None,
+    })
+}
+
+// Compress every source in parallel. Failures are reported per item rather
+// than aborting the batch; `on_progress` runs as each image finishes. Sources
+// whose index is in `lossless` skip quantization and lossy encoding.
+pub fn compress_batch(
+    sources: &[ImageSource],
+    settings: &CompressSettings,
+    lossless: &HashSet<usize>,
+    output_dir: Option<&Path>,
+    on_progress: impl Fn(BatchProgress) + Sync,
+) -> BatchReport {
+    let completed = AtomicUsize::new(0);
+
+    let items: Vec<BatchItem> = sources
+        .par_iter()
+        .enumerate()
+        .map(|(index, source)| {
+            let lossless = lossless.contains(&index);
+            let item = compress_one(index, source, settings, lossless, output_dir);
+            let item = item.unwrap_or_else(|e| BatchItem {
+                index,
+                name: source.name(),
+                output: None,
+                width: 0,
+                height: 0,
+                original_size: 0,
+                compressed_size: 0,
+                lossless,
+                error: Some(e),
+            });
+
+            on_progress(BatchProgress {
+                completed: completed.fetch_add(1, Ordering::SeqCst) + 1,
+                total: sources.len(),
+                item: item.clone(),
+            });
+            item
+        })
+        .collect();
+
+    let succeeded = items.iter().filter(|i| i.error.is_none());
+    BatchReport {
+        total_original_size: succeeded.clone().map(|i| i.original_size).sum(),
+        total_compressed_size: succeeded.map(|i| i.compressed_size).sum(),
+        failed: items.iter().filter(|i| i.error.is_some()).count(),
+        items,
+    }
+}
+
+#[derive(Serialize)]
+pub struct PreviewVariant {
+    pub index: usize,
+    // Data URL of the encoded variant
+    pub output: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub compressed_size: usize,
+    // Against the original at full size, so scaling losses count too. None
+    // when the encoded format can't be decoded again (AVIF).
+    pub psnr: Option<f64>,
+    pub ssim: Option<f64>,
+    pub error: Option<AppError>,
+}
+
+fn preview_variant(
+    img: &DynamicImage,
+    reference: &RgbaImage,
+    settings: &CompressSettings,
+) -> Result<(Compressed, Option<(f64, f64)>), AppError> {
+    let compressed = compress(img.clone(), settings)?;
+    let metrics = image::load_from_memory(&compressed.data)
+        .ok()
+        .map(|decoded| {
+            let (width, height) = reference.dimensions();
+            let decoded = if decoded.dimensions() == (width, height) {
+                decoded.to_rgba8()
+            } else {
+                imageops::resize(&decoded.to_rgba8(), width, height, FilterType::CatmullRom)
+            };
+            (
+                metrics::psnr(reference, &decoded),
+                metrics::ssim(reference, &decoded),
+            )
+        });
+    Ok((compressed, metrics))
+}
+
+// Encode one image with every settings variant in parallel and measure each
+// against the original, for a size / quality comparison grid
+pub fn preview_matrix(img: &DynamicImage, variants: &[CompressSettings]) -> Vec<PreviewVariant> {
+    let reference = img.to_rgba8();
+    variants
+        .par_iter()
+        .enumerate()
+        .map(
+            |(index, settings)| match preview_variant(img, &reference, settings) {
+                Ok((compressed, metrics)) => PreviewVariant {
+                    index,
+                    output: Some(to_data_url(&compressed.data, settings.format.mime_type())),
+                    width: compressed.width,
+                    height: compressed.height,
+                    compressed_size: compressed.data.len(),
+                    psnr: metrics.map(|m| m.0),
+                    ssim: metrics.map(|m| m.1),
+                    error: None,
+                },
+                Err(e) => PreviewVariant {
+                    index,
+                    output: None,
+                    width: 0,
+                    height: 0,
+                    compressed_size: 0,
+                    psnr: None,
+                    ssim: None,
+                    error: Some(e),
+                },
+            },
+        )
+        .collect()
+}
@@ -0,0 +1,73 @@
+use ddsfile::{AlphaMode, D3D10ResourceDimension, Dds, DxgiFormat, NewDxgiParams};
+use image::RgbaImage;
+use serde::Deserialize;
+
+use crate::bcn::{self, BcFormat};
+use crate::error::AppError;
+use crate::mipmap::{self, MipmapOptions};
+
+#[derive(Deserialize)]
+pub struct DdsOptions {
+    pub format: BcFormat,
+    #[serde(default)]
+    pub mipmaps: bool,
+    #[serde(default = "default_srgb")]
+    pub srgb: bool,
+}
+
+fn default_srgb() -> bool {
+    true
+}
+
+fn dxgi_format(format: BcFormat, srgb: bool) -> DxgiFormat {
+    match (format, srgb) {
+        (BcFormat::Bc1, false) => DxgiFormat::BC1_UNorm,
+        (BcFormat::Bc1, true) => DxgiFormat::BC1_UNorm_sRGB,
+        (BcFormat::Bc3, false) => DxgiFormat::BC3_UNorm,
+        (BcFormat::Bc3, true) => DxgiFormat::BC3_UNorm_sRGB,
+        (BcFormat::Bc7, false) => DxgiFormat::BC7_UNorm,
+        (BcFormat::Bc7, true) => DxgiFormat::BC7_UNorm_sRGB,
+    }
+}
+
+pub fn encode_dds(img: &RgbaImage, options: &DdsOptions) -> Result<Vec<u8>, AppError> {
+    let levels = if options.mipmaps {
+        mipmap::generate_mipmaps(img, &MipmapOptions::default())
+    } else {
+        vec![img.clone()]
+    };
+    encode_dds_levels(&levels, options.format, options.srgb)
+}
+
+// Write an already generated mip chain (level 0 first)
+pub fn encode_dds_levels(
+    levels: &[RgbaImage],
+    format: BcFormat,
+    srgb: bool,
+) -> Result<Vec<u8>, AppError> {
+    let base = levels.first().ok_or_else(|| AppError::encode("No mip levels to encode"))?;
+
+    let mut dds = Dds::new_dxgi(NewDxgiParams {
+        height: base.height(),
+        width: base.width(),
+        depth: None,
+        format: dxgi_format(format, srgb),
+        mipmap_levels: Some(levels.len() as u32),
+        array_layers: None,
+        caps2: None,
+        is_cubemap: false,
+        resource_dimension: D3D10ResourceDimension::Texture2D,
+        alpha_mode: AlphaMode::Straight,
+    })
+    .map_err(AppError::encode)?;
+
+    // Levels are stored back to back, largest first
+    dds.data = levels
+        .iter()
+        .flat_map(|level| bcn::compress(level, format))
+        .collect();
+
+    let mut out = Vec::new();
+    dds.write(&mut out).map_err(AppError::encode)?;
+    Ok(out)
+}
@@ -0,0 +1,84 @@
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::image_cache::ImageCache;
+use crate::naming::fill_pattern;
+use crate::pipeline::EditOperation;
+
+#[derive(Deserialize)]
+pub struct SpriteRef {
+    pub handle: u32,
+    pub name: String,
+}
+
+#[derive(Serialize)]
+pub struct DuplicatedSprite {
+    pub source: u32,
+    pub handle: u32,
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub const DEFAULT_NAME_PATTERN: &str = "{name}_copy";
+
+// Copies of cached sprites as new handles with `operations` applied (e.g. a
+// horizontal flip for a mirrored walk cycle, or a recolor for a team color).
+// Names come from `name_pattern`: {name} is the source name, {index} its
+// 1-based position in `sprites`.
+pub fn duplicate(
+    cache: &ImageCache,
+    sprites: &[SpriteRef],
+    operations: &[EditOperation],
+    name_pattern: &str,
+) -> Result<Vec<DuplicatedSprite>, AppError> {
+    for operation in operations {
+        operation.validate()?;
+    }
+    let names = sprites
+        .iter()
+        .enumerate()
+        .map(|(i, sprite)| {
+            let name = fill_pattern(name_pattern, "name_pattern", |key| match key {
+                "name" => Some(sprite.name.clone()),
+                "index" => Some((i + 1).to_string()),
+                _ => None,
+            })?;
+            if name.is_empty() {
+                return Err(AppError::invalid(
+                    "name_pattern",
+                    "Name pattern gives an empty name",
+                ));
+            }
+            Ok(name)
+        })
+        .collect::<Result<Vec<_>, AppError>>()?;
+
+    // Fetch every source first so a missing handle fails before anything
+    // is added to the cache
+    let images = sprites
+        .iter()
+        .map(|sprite| cache.get(sprite.handle))
+        .collect::<Result<Vec<_>, AppError>>()?;
+    let images: Vec<_> = images
+        .into_par_iter()
+        .map(|img| operations.iter().try_fold(img, |img, op| op.apply(&img)))
+        .collect::<Result<_, AppError>>()?;
+
+    Ok(sprites
+        .iter()
+        .zip(names)
+        .zip(images)
+        .map(|((sprite, name), img)| {
+            let (width, height) = img.dimensions();
+            DuplicatedSprite {
+                source: sprite.handle,
+                handle: cache.insert(img),
+                name,
+                width,
+                height,
+            }
+        })
+        .collect())
+}
@@ -0,0 +1,172 @@
+use crate::image_io::Color;
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+// Pixels at or above this alpha count as part of the sprite silhouette
+const SILHOUETTE_ALPHA: u8 = 128;
+
+#[derive(Serialize)]
+pub struct EffectOutput {
+    pub base64: String,
+    pub width: u32,
+    pub height: u32,
+    // Where the original top-left pixel ended up on the new canvas
+    #[serde(rename = "offsetX")]
+    pub offset_x: i32,
+    #[serde(rename = "offsetY")]
+    pub offset_y: i32,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutlineMode {
+    Inside,
+    Outside,
+    Center,
+}
+
+// Source-over compositing of `top` onto `bottom` (straight alpha)
+pub fn blend_over(top: Rgba<u8>, bottom: Rgba<u8>) -> Rgba<u8> {
+    let ta = top[3] as f32 / 255.0;
+    let ba = bottom[3] as f32 / 255.0;
+    let out_a = ta + ba * (1.0 - ta);
+    if out_a <= 0.0 {
+        return Rgba([0, 0, 0, 0]);
+    }
+
+    let mut out = [0u8; 4];
+    for c in 0..3 {
+        let v = (top[c] as f32 * ta + bottom[c] as f32 * ba * (1.0 - ta)) / out_a;
+        out[c] = v.round().clamp(0.0, 255.0) as u8;
+    }
+    out[3] = (out_a * 255.0).round() as u8;
+    Rgba(out)
+}
+
+// Copy `img` onto a transparent canvas grown by `margin` on every side
+fn expand(img: &RgbaImage, margin: u32) -> RgbaImage {
+    let mut canvas = RgbaImage::new(img.width() + margin * 2, img.height() + margin * 2);
+    image::imageops::replace(&mut canvas, img, margin as i64, margin as i64);
+    canvas
+}
+
+fn disc_offsets(radius: u32) -> Vec<(i64, i64)> {
+    let r = radius as i64;
+    let mut offsets = Vec::new();
+    for dy in -r..=r {
+        for dx in -r..=r {
+            if (dx != 0 || dy != 0) && dx * dx + dy * dy <= r * r {
+                offsets.push((dx, dy));
+            }
+        }
+    }
+    offsets
+}
+
+// True if any pixel within `offsets` of (x, y) has the given silhouette state.
+// Out-of-bounds pixels count as outside the silhouette.
+fn any_within(
+    mask: &[bool],
+    width: u32,
+    height: u32,
+    x: u32,
+    y: u32,
+    offsets: &[(i64, i64)],
+    want: bool,
+) -> bool {
+    offsets.iter().any(|&(dx, dy)| {
+        let nx = x as i64 + dx;
+        let ny = y as i64 + dy;
+        if nx < 0 || ny < 0 || nx >= width as i64 || ny >= height as i64 {
+            return !want;
+        }
+        mask[(ny as u32 * width + nx as u32) as usize] == want
+    })
+}
+
+pub fn add_outline(
+    img: &RgbaImage,
+    color: Color,
+    thickness: u32,
+    mode: OutlineMode,
+) -> (RgbaImage, u32) {
+    let (outer, inner) = match mode {
+        OutlineMode::Outside => (thickness, 0),
+        OutlineMode::Inside => (0, thickness),
+        OutlineMode::Center => (thickness.div_ceil(2), thickness / 2),
+    };
+
+    let source = expand(img, outer);
+    let (width, height) = source.dimensions();
+    let mask: Vec<bool> = source.pixels().map(|p| p[3] >= SILHOUETTE_ALPHA).collect();
+    let outer_offsets = disc_offsets(outer);
+    let inner_offsets = disc_offsets(inner);
+    let stroke = color.to_rgba();
+
+    let mut output = source.clone();
+    for (x, y, pixel) in output.enumerate_pixels_mut() {
+        let inside = mask[(y * width + x) as usize];
+        if !inside && outer > 0 && any_within(&mask, width, height, x, y, &outer_offsets, true) {
+            // Outline sits behind the sprite's soft edge
+            *pixel = blend_over(*pixel, stroke);
+        } else if inside
+            && inner > 0
+            && any_within(&mask, width, height, x, y, &inner_offsets, false)
+        {
+            let mut top = stroke;
+            top[3] = top[3].min(pixel[3]);
+            *pixel = blend_over(top, *pixel);
+        }
+    }
+
+    (output, outer)
+}
+
+#[derive(Deserialize)]
+pub struct ShadowConfig {
+    pub offset_x: i32,
+    pub offset_y: i32,
+    pub blur_radius: u32,
+    pub color: Color,
+    pub opacity: u8, // 0-100
+}
+
+// Bake a blurred copy of the silhouette under the sprite. Returns the new
+// canvas and where the original top-left pixel landed on it.
+pub fn add_drop_shadow(img: &RgbaImage, config: &ShadowConfig) -> (RgbaImage, u32, u32) {
+    let blur = config.blur_radius as i64;
+    let (dx, dy) = (config.offset_x as i64, config.offset_y as i64);
+
+    let left = (blur - dx).max(0) as u32;
+    let right = (blur + dx).max(0) as u32;
+    let top = (blur - dy).max(0) as u32;
+    let bottom = (blur + dy).max(0) as u32;
+
+    let width = img.width() + left + right;
+    let height = img.height() + top + bottom;
+    let opacity = config.opacity.min(100) as f32 / 100.0;
+    let color = config.color;
+
+    // Keep the shadow color in every pixel so blurring only spreads alpha
+    let mut shadow = RgbaImage::from_pixel(width, height, Rgba([color.r, color.g, color.b, 0]));
+    for (x, y, pixel) in img.enumerate_pixels() {
+        let sx = x as i64 + left as i64 + dx;
+        let sy = y as i64 + top as i64 + dy;
+        if sx >= 0 && sy >= 0 && sx < width as i64 && sy < height as i64 {
+            let alpha = pixel[3] as f32 * color.a as f32 / 255.0 * opacity;
+            shadow.get_pixel_mut(sx as u32, sy as u32)[3] = alpha.round() as u8;
+        }
+    }
+
+    if config.blur_radius > 0 {
+        // Treat the radius as ~2 standard deviations
+        shadow = image::imageops::blur(&shadow, config.blur_radius as f32 / 2.0);
+    }
+
+    for (x, y, pixel) in img.enumerate_pixels() {
+        let target = shadow.get_pixel_mut(x + left, y + top);
+        *target = blend_over(*pixel, *target);
+    }
+
+    (shadow, left, top)
+}
@@ -0,0 +1,292 @@
+use crate::error::AppError;
+use crate::image_io::{encode_png_bytes, Color};
+use crate::quantize::{self, Dithering, QuantizeSettings};
+use image::codecs::{avif::AvifEncoder, jpeg::JpegEncoder};
+use image::{DynamicImage, ExtendedColorType, ImageEncoder, Rgb, RgbImage, RgbaImage};
+use serde::Deserialize;
+use std::num::NonZeroU8;
+
+// Output encoding for atlases and compressed images
+#[derive(Deserialize, Clone, Copy)]
+#[serde(tag = "format", rename_all = "lowercase")]
+pub enum OutputFormat {
+    Png {
+        // Unset keeps each caller's historical behavior
+        #[serde(default)]
+        color_type: Option<PngColorType>,
+        // Adam7: a coarse image shows while loading, at a larger file size
+        #[serde(default)]
+        interlace: bool,
+        #[serde(default)]
+        strip: PngStrip,
+    },
+    Webp {
+        #[serde(default = "default_quality")]
+        quality: u8,
+        #[serde(default)]
+        lossless: bool,
+    },
+    Avif {
+        #[serde(default = "default_quality")]
+        quality: u8,
+        // 1 (slowest, smallest) to 10 (fastest)
+        #[serde(default = "default_avif_speed")]
+        speed: u8,
+    },
+    Jpeg {
+        #[serde(default = "default_quality")]
+        quality: u8,
+        // Transparency is composited onto this color (white by default)
+        #[serde(default)]
+        background: Option<Color>,
+    },
+}
+
+impl OutputFormat {
+    // The same format without lossy steps; JPEG and AVIF have no lossless
+    // mode here, so they become PNG
+    pub fn lossless(&self) -> OutputFormat {
+        match *self {
+            OutputFormat::Webp { quality, .. } => OutputFormat::Webp {
+                quality,
+                lossless: true,
+            },
+            OutputFormat::Png {
+                interlace, strip, ..
+            } => OutputFormat::Png {
+                color_type: Some(PngColorType::Auto),
+                interlace,
+                strip,
+            },
+            _ => OutputFormat::Png {
+                color_type: Some(PngColorType::Auto),
+                interlace: false,
+                strip: PngStrip::None,
+            },
+        }
+    }
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        OutputFormat::Png {
+            color_type: None,
+            interlace: false,
+            strip: PngStrip::None,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PngColorType {
+    // Smallest lossless color type and bit depth for the pixels
+    Auto,
+    Rgba8,
+    Rgb8,
+    // Quantized to a palette of 256 / 16 / 4 colors
+    Indexed8,
+    Indexed4,
+    Indexed2,
+    Grayscale,
+}
+
+// Ancillary chunks removed from PNG output
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PngStrip {
+    #[default]
+    None,
+    // Everything that doesn't change how the image displays (text, EXIF,
+    // timestamps); color chunks stay
+    Safe,
+    // Every ancillary chunk, color chunks included
+    All,
+}
+
+fn default_quality() -> u8 {
+    80
+}
+
+fn default_avif_speed() -> u8 {
+    6
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Png { .. } => "png",
+            OutputFormat::Webp { .. } => "webp",
+            OutputFormat::Avif { .. } => "avif",
+            OutputFormat::Jpeg { .. } => "jpg",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            OutputFormat::Png { .. } => "image/png",
+            OutputFormat::Webp { .. } => "image/webp",
+            OutputFormat::Avif { .. } => "image/avif",
+            OutputFormat::Jpeg { .. } => "image/jpeg",
+        }
+    }
+}
+
+pub fn encode_image(img: &RgbaImage, format: &OutputFormat) -> Result<Vec<u8>, AppError> {
+    match *format {
+        OutputFormat::Png { color_type, .. } => {
+            let settings = QuantizeSettings {
+                quality: 100,
+                max_colors: 256,
+                dithering: Dithering::default(),
+            };
+            encode_png(img, color_type.unwrap_or(PngColorType::Rgba8), &settings)
+        }
+        OutputFormat::Webp { quality, lossless } => {
+            let encoder = webp::Encoder::from_rgba(img.as_raw(), img.width(), img.height());
+            let data = encoder
+                .encode_simple(lossless, quality.min(100) as f32)
+                .map_err(|e| AppError::encode(format!("WebP encoding failed: {:?}", e)))?;
+            Ok(data.to_vec())
+        }
+        OutputFormat::Avif { quality, speed } => {
+            let mut buf = Vec::new();
+            AvifEncoder::new_with_speed_quality(&mut buf, speed.clamp(1, 10), quality.min(100))
+                .write_image(
+                    img.as_raw(),
+                    img.width(),
+                    img.height(),
+                    ExtendedColorType::Rgba8,
+                )
+                .map_err(AppError::encode)?;
+            Ok(buf)
+        }
+        OutputFormat::Jpeg {
+            quality,
+            background,
+        } => {
+            let matte = flatten(img, background.unwrap_or(WHITE));
+            let mut buf = Vec::new();
+            JpegEncoder::new_with_quality(&mut buf, quality.clamp(1, 100))
+                .write_image(
+                    matte.as_raw(),
+                    matte.width(),
+                    matte.height(),
+                    ExtendedColorType::Rgb8,
+                )
+                .map_err(AppError::encode)?;
+            Ok(buf)
+        }
+    }
+}
+
+// `settings` only apply to the indexed color types
+pub fn encode_png(
+    img: &RgbaImage,
+    color_type: PngColorType,
+    settings: &QuantizeSettings,
+) -> Result<Vec<u8>, AppError> {
+    let (width, height) = img.dimensions();
+    let rgba = DynamicImage::ImageRgba8(img.clone());
+
+    match color_type {
+        PngColorType::Auto => {
+            // lodepng's auto-convert picks palette / grey / RGB / RGBA and the
+            // lowest bit depth that represents every pixel exactly
+            let encoder = lodepng::Encoder::new();
+            encoder
+                .encode(img.as_raw(), width as usize, height as usize)
+                .map_err(AppError::encode)
+        }
+        PngColorType::Rgba8 => encode_png_bytes(&rgba),
+        PngColorType::Rgb8 => encode_png_bytes(&rgba.to_rgb8().into()),
+        PngColorType::Grayscale => {
+            if img.pixels().all(|p| p[3] == 255) {
+                encode_png_bytes(&rgba.to_luma8().into())
+            } else {
+                encode_png_bytes(&rgba.to_luma_alpha8().into())
+            }
+        }
+        PngColorType::Indexed8 | PngColorType::Indexed4 | PngColorType::Indexed2 => {
+            let bit_depth = match color_type {
+                PngColorType::Indexed4 => 4,
+                PngColorType::Indexed2 => 2,
+                _ => 8,
+            };
+            let settings = QuantizeSettings {
+                max_colors: settings.max_colors.min(1 << bit_depth),
+                ..*settings
+            };
+            let (palette, indices) = quantize::quantize_image(img, &settings)?;
+            quantize::encode_indexed_png(&palette, &indices, width, height, bit_depth)
+        }
+    }
+}
+
+const WHITE: Color = Color {
+    r: 255,
+    g: 255,
+    b: 255,
+    a: 255,
+};
+
+// Composite onto an opaque background for formats without alpha
+pub fn flatten(img: &RgbaImage, background: Color) -> RgbImage {
+    RgbImage::from_fn(img.width(), img.height(), |x, y| {
+        let p = img.get_pixel(x, y);
+        let a = p[3] as u32;
+        let bg = [background.r, background.g, background.b];
+        Rgb([0, 1, 2].map(|c| ((p[c] as u32 * a + bg[c] as u32 * (255 - a) + 127) / 255) as u8))
+    })
+}
+
+// Lossless oxipng pass: re-filter and re-deflate without touching pixels,
+// with `strip` applied. `interlace` turns Adam7 on or off; None keeps the
+// input's interlacing.
+pub fn optimize_png(
+    data: &[u8],
+    level: u8,
+    zopfli: bool,
+    interlace: Option<bool>,
+    strip: PngStrip,
+) -> Result<Vec<u8>, AppError> {
+    let mut opts = oxipng::Options::from_preset(level.min(6));
+    if zopfli {
+        opts.deflate = oxipng::Deflaters::Zopfli {
+            iterations: NonZeroU8::new(15).unwrap(),
+        };
+    }
+    opts.interlace = interlace.map(|interlace| {
+        if interlace {
+            oxipng::Interlacing::Adam7
+        } else {
+            oxipng::Interlacing::None
+        }
+    });
+    opts.strip = match strip {
+        PngStrip::None => oxipng::StripChunks::None,
+        PngStrip::Safe => oxipng::StripChunks::Safe,
+        PngStrip::All => oxipng::StripChunks::All,
+    };
+    oxipng::optimize_from_memory(data, &opts).map_err(AppError::encode)
+}
+
+// Applies the format's interlacing and chunk stripping to encoded PNG data,
+// with an oxipng optimization at `optimize` when given. Other formats and
+// PNGs that need neither pass through.
+pub fn finish_png(
+    data: Vec<u8>,
+    format: &OutputFormat,
+    optimize: Option<u8>,
+) -> Result<Vec<u8>, AppError> {
+    let OutputFormat::Png {
+        interlace, strip, ..
+    } = *format
+    else {
+        return Ok(data);
+    };
+    if optimize.is_none() && !interlace && strip == PngStrip::None {
+        return Ok(data);
+    }
+    optimize_png(&data, optimize.unwrap_or(0), false, Some(interlace), strip)
+}
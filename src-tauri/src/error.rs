@@ -0,0 +1,156 @@
+use serde::Serialize;
+use std::fmt;
+
+use crate::budget::BudgetViolation;
+
+// Error returned by every command. Serialized as `{ kind, message, ... }` so
+// the frontend can pick a localized message and recovery action by `kind`;
+// `message` is the English detail.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum AppError {
+    // Reading or writing files and directories
+    Io {
+        message: String,
+    },
+    // Input bytes that could not be read as an image or document
+    Decode {
+        message: String,
+    },
+    // Producing an output image or file format failed
+    Encode {
+        message: String,
+    },
+    // Sprites don't fit; `needed_size` is the estimated square atlas side
+    PackingFailed {
+        message: String,
+        needed_size: u32,
+    },
+    // A parameter was out of range or inconsistent; `field` names it
+    InvalidInput {
+        field: String,
+        message: String,
+    },
+    // An export went over its configured size budget
+    BudgetExceeded {
+        message: String,
+        violations: Vec<BudgetViolation>,
+    },
+}
+
+impl AppError {
+    pub fn io(message: impl fmt::Display) -> Self {
+        AppError::Io {
+            message: message.to_string(),
+        }
+    }
+
+    pub fn decode(message: impl fmt::Display) -> Self {
+        AppError::Decode {
+            message: message.to_string(),
+        }
+    }
+
+    pub fn encode(message: impl fmt::Display) -> Self {
+        AppError::Encode {
+            message: message.to_string(),
+        }
+    }
+
+    pub fn packing(message: impl fmt::Display, needed_size: u32) -> Self {
+        AppError::PackingFailed {
+            message: message.to_string(),
+            needed_size,
+        }
+    }
+
+    pub fn invalid(field: &str, message: impl fmt::Display) -> Self {
+        AppError::InvalidInput {
+            field: field.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    pub fn budget(violations: Vec<BudgetViolation>) -> Self {
+        let details: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+        AppError::BudgetExceeded {
+            message: format!("Export exceeds its budget: {}", details.join("; ")),
+            violations,
+        }
+    }
+
+    // Same kind, message prefixed with where it happened (a file, a sprite)
+    pub fn context(self, prefix: impl fmt::Display) -> Self {
+        let prefixed = |message: String| format!("{}: {}", prefix, message);
+        match self {
+            AppError::Io { message } => AppError::Io {
+                message: prefixed(message),
+            },
+            AppError::Decode { message } => AppError::Decode {
+                message: prefixed(message),
+            },
+            AppError::Encode { message } => AppError::Encode {
+                message: prefixed(message),
+            },
+            AppError::PackingFailed {
+                message,
+                needed_size,
+            } => AppError::PackingFailed {
+                message: prefixed(message),
+                needed_size,
+            },
+            AppError::InvalidInput { field, message } => AppError::InvalidInput {
+                field,
+                message: prefixed(message),
+            },
+            AppError::BudgetExceeded {
+                message,
+                violations,
+            } => AppError::BudgetExceeded {
+                message: prefixed(message),
+                violations,
+            },
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            AppError::Io { message }
+            | AppError::Decode { message }
+            | AppError::Encode { message }
+            | AppError::PackingFailed { message, .. }
+            | AppError::InvalidInput { message, .. }
+            | AppError::BudgetExceeded { message, .. } => message,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        AppError::io(e)
+    }
+}
+
+impl From<base64::DecodeError> for AppError {
+    fn from(e: base64::DecodeError) -> Self {
+        AppError::decode(e)
+    }
+}
+
+impl From<image::ImageError> for AppError {
+    fn from(e: image::ImageError) -> Self {
+        match e {
+            image::ImageError::Encoding(_) => AppError::encode(e),
+            image::ImageError::IoError(_) => AppError::io(e),
+            _ => AppError::decode(e),
+        }
+    }
+}
@@ -0,0 +1,351 @@
+use image::RgbaImage;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Clone, Copy)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ImageFilter {
+    Grayscale,
+    // Blend toward the sepia tone by `amount` (0-1)
+    Sepia {
+        #[serde(default = "default_amount")]
+        amount: f32,
+    },
+    Invert,
+    // Levels per channel (2-255)
+    Posterize {
+        levels: u8,
+    },
+    // Pure black / white split at luma `level`
+    Threshold {
+        level: u8,
+    },
+    // Gaussian blur with standard deviation `sigma` in pixels
+    Blur {
+        sigma: f32,
+    },
+    // Mean of the (2 * radius + 1)^2 neighbourhood
+    BoxBlur {
+        radius: u32,
+    },
+    // Unsharp mask: add back `amount` x the detail removed by a Gaussian blur,
+    // ignoring differences at or below `threshold`
+    Sharpen {
+        #[serde(default = "default_amount")]
+        sigma: f32,
+        #[serde(default = "default_amount")]
+        amount: f32,
+        #[serde(default)]
+        threshold: u8,
+    },
+    Denoise(Denoise),
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy)]
+#[serde(tag = "method", rename_all = "lowercase")]
+pub enum Denoise {
+    // Per-channel median of the (2 * radius + 1)^2 window; removes speckle
+    Median {
+        #[serde(default = "default_denoise_radius")]
+        radius: u32,
+    },
+    // Edge-preserving average weighted by distance and color similarity
+    Bilateral {
+        #[serde(default = "default_denoise_radius")]
+        radius: u32,
+        #[serde(default = "default_sigma_color")]
+        sigma_color: f32,
+        #[serde(default = "default_sigma_space")]
+        sigma_space: f32,
+    },
+}
+
+impl ImageFilter {
+    // The same filter for an image `factor` times the size: radii and sigmas
+    // are in pixels, so they grow with it to look the same
+    pub fn scaled(self, factor: f32) -> ImageFilter {
+        let radius = |r: u32| (r as f32 * factor).round() as u32;
+        match self {
+            ImageFilter::Blur { sigma } => ImageFilter::Blur {
+                sigma: sigma * factor,
+            },
+            ImageFilter::BoxBlur { radius: r } => ImageFilter::BoxBlur { radius: radius(r) },
+            ImageFilter::Sharpen {
+                sigma,
+                amount,
+                threshold,
+            } => ImageFilter::Sharpen {
+                sigma: sigma * factor,
+                amount,
+                threshold,
+            },
+            ImageFilter::Denoise(Denoise::Median { radius: r }) => {
+                ImageFilter::Denoise(Denoise::Median { radius: radius(r) })
+            }
+            ImageFilter::Denoise(Denoise::Bilateral {
+                radius: r,
+                sigma_color,
+                sigma_space,
+            }) => ImageFilter::Denoise(Denoise::Bilateral {
+                radius: radius(r),
+                sigma_color,
+                sigma_space: sigma_space * factor,
+            }),
+            other => other,
+        }
+    }
+}
+
+fn default_denoise_radius() -> u32 {
+    1
+}
+
+fn default_sigma_color() -> f32 {
+    25.0
+}
+
+fn default_sigma_space() -> f32 {
+    2.0
+}
+
+fn default_amount() -> f32 {
+    1.0
+}
+
+fn luma(p: &image::Rgba<u8>) -> u8 {
+    ((p[0] as u32 * 299 + p[1] as u32 * 587 + p[2] as u32 * 114) / 1000) as u8
+}
+
+fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    let sigma = sigma.max(0.01);
+    let radius = (sigma * 3.0).ceil() as i32;
+    let weights: Vec<f32> = (-radius..=radius)
+        .map(|i| (-(i * i) as f32 / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let sum: f32 = weights.iter().sum();
+    weights.into_iter().map(|w| w / sum).collect()
+}
+
+// Separable convolution of the premultiplied image (so transparent pixels
+// don't bleed dark fringes), edges clamped. Returns premultiplied floats.
+fn convolve(img: &RgbaImage, kernel: &[f32]) -> Vec<[f32; 4]> {
+    if img.width() == 0 || img.height() == 0 {
+        return Vec::new();
+    }
+    let (width, height) = (img.width() as i64, img.height() as i64);
+    let radius = (kernel.len() / 2) as i64;
+    let at = |x: i64, y: i64| (y * width + x) as usize;
+
+    let src: Vec<[f32; 4]> = img
+        .pixels()
+        .map(|p| {
+            let a = p[3] as f32 / 255.0;
+            [
+                p[0] as f32 * a,
+                p[1] as f32 * a,
+                p[2] as f32 * a,
+                p[3] as f32,
+            ]
+        })
+        .collect();
+
+    // Rows are independent within a pass, so split them across threads
+    let pass = |input: &[[f32; 4]], horizontal: bool| {
+        let mut out = vec![[0f32; 4]; input.len()];
+        out.par_chunks_mut(width as usize)
+            .enumerate()
+            .for_each(|(y, row)| {
+                let y = y as i64;
+                for (x, dst) in row.iter_mut().enumerate() {
+                    let x = x as i64;
+                    let mut acc = [0f32; 4];
+                    for (k, w) in kernel.iter().enumerate() {
+                        let d = k as i64 - radius;
+                        let (sx, sy) = if horizontal {
+                            ((x + d).clamp(0, width - 1), y)
+                        } else {
+                            (x, (y + d).clamp(0, height - 1))
+                        };
+                        let p = input[at(sx, sy)];
+                        for c in 0..4 {
+                            acc[c] += p[c] * w;
+                        }
+                    }
+                    *dst = acc;
+                }
+            });
+        out
+    };
+
+    pass(&pass(&src, true), false)
+}
+
+fn write_premultiplied(img: &mut RgbaImage, pixels: &[[f32; 4]]) {
+    for (p, v) in img.pixels_mut().zip(pixels) {
+        let a = v[3];
+        for c in 0..3 {
+            let straight = if a > 0.0 { v[c] * 255.0 / a } else { 0.0 };
+            p[c] = straight.round().clamp(0.0, 255.0) as u8;
+        }
+        p[3] = a.round().clamp(0.0, 255.0) as u8;
+    }
+}
+
+// Only visible neighbours contribute, so transparent areas don't pull color
+// into sprite edges. Alpha is left untouched.
+pub fn denoise(img: &mut RgbaImage, denoise: Denoise) {
+    let (width, height) = img.dimensions();
+    if width == 0 || height == 0 {
+        return;
+    }
+    let radius = match denoise {
+        Denoise::Median { radius } | Denoise::Bilateral { radius, .. } => radius as i64,
+    };
+    let src = img.clone();
+
+    let rows: Vec<Vec<[u8; 3]>> = (0..height as i64)
+        .into_par_iter()
+        .map(|y| {
+            (0..width as i64)
+                .map(|x| {
+                    let center = src.get_pixel(x as u32, y as u32);
+                    let mut window = Vec::new();
+                    for dy in -radius..=radius {
+                        for dx in -radius..=radius {
+                            let (nx, ny) = (x + dx, y + dy);
+                            if nx < 0 || ny < 0 || nx >= width as i64 || ny >= height as i64 {
+                                continue;
+                            }
+                            let p = src.get_pixel(nx as u32, ny as u32);
+                            if p[3] > 0 {
+                                window.push((dx, dy, [p[0], p[1], p[2]]));
+                            }
+                        }
+                    }
+                    if center[3] == 0 || window.is_empty() {
+                        return [center[0], center[1], center[2]];
+                    }
+
+                    match denoise {
+                        Denoise::Median { .. } => [0, 1, 2].map(|c| {
+                            let mut values: Vec<u8> = window.iter().map(|w| w.2[c]).collect();
+                            values.sort_unstable();
+                            values[values.len() / 2]
+                        }),
+                        Denoise::Bilateral {
+                            sigma_color,
+                            sigma_space,
+                            ..
+                        } => {
+                            let space = 2.0 * sigma_space.max(0.01).powi(2);
+                            let range = 2.0 * sigma_color.max(0.01).powi(2);
+                            let mut acc = [0f32; 3];
+                            let mut total = 0f32;
+                            for (dx, dy, color) in &window {
+                                let dist_sq = (dx * dx + dy * dy) as f32;
+                                let color_sq: f32 = (0..3)
+                                    .map(|c| (color[c] as f32 - center[c] as f32).powi(2))
+                                    .sum();
+                                let w = (-dist_sq / space - color_sq / range).exp();
+                                for c in 0..3 {
+                                    acc[c] += color[c] as f32 * w;
+                                }
+                                total += w;
+                            }
+                            acc.map(|v| (v / total).round().clamp(0.0, 255.0) as u8)
+                        }
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    for (y, row) in rows.iter().enumerate() {
+        for (x, color) in row.iter().enumerate() {
+            let p = img.get_pixel_mut(x as u32, y as u32);
+            p[0] = color[0];
+            p[1] = color[1];
+            p[2] = color[2];
+        }
+    }
+}
+
+// Color filters leave alpha untouched; blurs spread it along with color
+pub fn apply_filter(img: &mut RgbaImage, filter: ImageFilter) {
+    match filter {
+        ImageFilter::Grayscale => {
+            for p in img.pixels_mut() {
+                let l = luma(p);
+                p[0] = l;
+                p[1] = l;
+                p[2] = l;
+            }
+        }
+        ImageFilter::Sepia { amount } => {
+            let amount = amount.clamp(0.0, 1.0);
+            for p in img.pixels_mut() {
+                let (r, g, b) = (p[0] as f32, p[1] as f32, p[2] as f32);
+                let sepia = [
+                    0.393 * r + 0.769 * g + 0.189 * b,
+                    0.349 * r + 0.686 * g + 0.168 * b,
+                    0.272 * r + 0.534 * g + 0.131 * b,
+                ];
+                for c in 0..3 {
+                    let v = p[c] as f32 + (sepia[c] - p[c] as f32) * amount;
+                    p[c] = v.round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+        ImageFilter::Invert => {
+            for p in img.pixels_mut() {
+                for c in 0..3 {
+                    p[c] = 255 - p[c];
+                }
+            }
+        }
+        ImageFilter::Posterize { levels } => {
+            let steps = levels.max(2) as f32 - 1.0;
+            for p in img.pixels_mut() {
+                for c in 0..3 {
+                    p[c] = ((p[c] as f32 / 255.0 * steps).round() / steps * 255.0).round() as u8;
+                }
+            }
+        }
+        ImageFilter::Threshold { level } => {
+            for p in img.pixels_mut() {
+                let v = if luma(p) >= level { 255 } else { 0 };
+                p[0] = v;
+                p[1] = v;
+                p[2] = v;
+            }
+        }
+        ImageFilter::Denoise(method) => denoise(img, method),
+        ImageFilter::Blur { sigma } => {
+            let blurred = convolve(img, &gaussian_kernel(sigma));
+            write_premultiplied(img, &blurred);
+        }
+        ImageFilter::BoxBlur { radius } => {
+            let size = 2 * radius as usize + 1;
+            let blurred = convolve(img, &vec![1.0 / size as f32; size]);
+            write_premultiplied(img, &blurred);
+        }
+        ImageFilter::Sharpen {
+            sigma,
+            amount,
+            threshold,
+        } => {
+            let blurred = convolve(img, &gaussian_kernel(sigma));
+            for (p, b) in img.pixels_mut().zip(&blurred) {
+                if b[3] <= 0.0 {
+                    continue;
+                }
+                for c in 0..3 {
+                    let detail = p[c] as f32 - b[c] * 255.0 / b[3];
+                    if detail.abs() > threshold as f32 {
+                        p[c] = (p[c] as f32 + detail * amount).round().clamp(0.0, 255.0) as u8;
+                    }
+                }
+            }
+        }
+    }
+}
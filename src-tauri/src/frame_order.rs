@@ -0,0 +1,60 @@
+use serde::Deserialize;
+use std::collections::HashSet;
+
+use crate::error::AppError;
+use crate::natural::natural_cmp;
+
+#[derive(Deserialize)]
+pub struct FrameKey {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum FrameSort {
+    // Natural order, frame2 before frame10
+    Name,
+    // Pixel area, ties by name
+    Size,
+}
+
+// Frame names in the requested order, to be stored as `frameOrder`
+pub fn sort_frames(frames: &[FrameKey], by: FrameSort, descending: bool) -> Vec<String> {
+    let mut sorted: Vec<&FrameKey> = frames.iter().collect();
+    sorted.sort_by(|a, b| {
+        let ordering = match by {
+            FrameSort::Name => natural_cmp(&a.name, &b.name),
+            FrameSort::Size => (a.width as u64 * a.height as u64)
+                .cmp(&(b.width as u64 * b.height as u64))
+                .then_with(|| natural_cmp(&a.name, &b.name)),
+        };
+        if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+    sorted.into_iter().map(|f| f.name.clone()).collect()
+}
+
+// `order` with the frames in `names` taken out and reinserted, in their
+// current relative order, at position `to` of what remains
+pub fn move_frames(order: &[String], names: &[String], to: usize) -> Result<Vec<String>, AppError> {
+    let moving: HashSet<&str> = names.iter().map(String::as_str).collect();
+    if let Some(name) = names.iter().find(|n| !order.contains(n)) {
+        return Err(AppError::invalid(
+            "names",
+            format!("\"{}\" is not in the frame order", name),
+        ));
+    }
+
+    let (moved, mut rest): (Vec<String>, Vec<String>) = order
+        .iter()
+        .cloned()
+        .partition(|name| moving.contains(name.as_str()));
+    let to = to.min(rest.len());
+    rest.splice(to..to, moved);
+    Ok(rest)
+}
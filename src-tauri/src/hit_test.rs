@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::error::AppError;
+
+#[derive(Deserialize, Serialize, Clone, Copy)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameInfo {
+    pub frame: Rect,
+    pub rotated: bool,
+    pub trimmed: bool,
+    pub sprite_source_size: Rect,
+    pub source_size: serde_json::Value,
+    pub pivot: serde_json::Value,
+    pub offset: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub padding: Option<u32>,
+    // Polygon mode: outline in atlas pixels and its triangulation
+    #[serde(rename = "verticesUV", default, skip_serializing)]
+    vertices_uv: Option<Vec<[f32; 2]>>,
+    #[serde(default, skip_serializing)]
+    triangles: Option<Vec<[u32; 3]>>,
+}
+
+#[derive(Deserialize)]
+struct Animation {
+    frames: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Atlas {
+    frames: BTreeMap<String, FrameInfo>,
+    #[serde(default)]
+    animations: BTreeMap<String, Animation>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct FrameHit {
+    // Atlas page the frame is on
+    pub image: String,
+    pub name: String,
+    // Animations listing the frame
+    pub animations: Vec<String>,
+    #[serde(flatten)]
+    pub info: FrameInfo,
+}
+
+// Frames of one atlas page, parsed once per build for click-to-select
+pub struct AtlasFrames {
+    image: String,
+    frames: Vec<(String, FrameInfo)>,
+    animations: Vec<(String, Vec<String>)>,
+}
+
+impl AtlasFrames {
+    pub fn parse(image: &str, json: &str) -> Result<AtlasFrames, AppError> {
+        let atlas: Atlas = serde_json::from_str(json).map_err(AppError::decode)?;
+        Ok(AtlasFrames {
+            image: image.to_string(),
+            frames: atlas.frames.into_iter().collect(),
+            animations: atlas
+                .animations
+                .into_iter()
+                .map(|(name, a)| (name, a.frames))
+                .collect(),
+        })
+    }
+
+    pub fn image(&self) -> &str {
+        &self.image
+    }
+
+    // The frame under atlas pixel (x, y). Polygon frames only count inside
+    // their mesh, so clicks on trimmed-away corners fall through.
+    pub fn hit(&self, x: f32, y: f32) -> Option<FrameHit> {
+        let (name, info) = self.frames.iter().find(|(_, info)| contains(info, x, y))?;
+        Some(FrameHit {
+            image: self.image.clone(),
+            name: name.clone(),
+            animations: self
+                .animations
+                .iter()
+                .filter(|(_, frames)| frames.contains(name))
+                .map(|(animation, _)| animation.clone())
+                .collect(),
+            info: info.clone(),
+        })
+    }
+}
+
+fn contains(info: &FrameInfo, x: f32, y: f32) -> bool {
+    let r = info.frame;
+    let in_rect =
+        x >= r.x as f32 && y >= r.y as f32 && x < (r.x + r.w) as f32 && y < (r.y + r.h) as f32;
+    match (&info.vertices_uv, &info.triangles) {
+        (Some(vertices), Some(triangles)) if in_rect => triangles.iter().any(|t| {
+            let [a, b, c] = t.map(|i| vertices.get(i as usize).copied());
+            matches!((a, b, c), (Some(a), Some(b), Some(c)) if in_triangle([x, y], a, b, c))
+        }),
+        _ => in_rect,
+    }
+}
+
+fn in_triangle(p: [f32; 2], a: [f32; 2], b: [f32; 2], c: [f32; 2]) -> bool {
+    let side =
+        |u: [f32; 2], v: [f32; 2]| (v[0] - u[0]) * (p[1] - u[1]) - (v[1] - u[1]) * (p[0] - u[0]);
+    let (d1, d2, d3) = (side(a, b), side(b, c), side(c, a));
+    let negative = d1 < 0.0 || d2 < 0.0 || d3 < 0.0;
+    let positive = d1 > 0.0 || d2 > 0.0 || d3 > 0.0;
+    !(negative && positive)
+}
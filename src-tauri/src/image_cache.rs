@@ -0,0 +1,242 @@
+use image::{imageops, Rgba, RgbaImage};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::error::AppError;
+use crate::image_io::encode_png_base64;
+
+// Managed state holding decoded images by handle, so repeated edits on a
+// large image don't pay for base64 transfer and decoding every time
+#[derive(Default)]
+pub struct ImageCache {
+    inner: Mutex<CacheInner>,
+}
+
+// Snapshots kept per handle; each one is a full copy of the image
+const HISTORY_LIMIT: usize = 20;
+
+#[derive(Default)]
+struct CacheInner {
+    next_id: u32,
+    images: HashMap<u32, CachedEntry>,
+}
+
+struct CachedEntry {
+    image: RgbaImage,
+    // Image before each edit, most recent last
+    undo: Vec<Snapshot>,
+    // Image after each undone edit, next to redo last
+    redo: Vec<Snapshot>,
+    // Encoded previews by max dimension, dropped whenever the image changes
+    thumbnails: HashMap<u32, Thumbnail>,
+    // Bumped on every change, so a preview rendered outside the lock isn't
+    // stored against a newer image
+    revision: u64,
+}
+
+impl CachedEntry {
+    fn set_image(&mut self, img: RgbaImage) -> RgbaImage {
+        self.thumbnails.clear();
+        self.revision += 1;
+        std::mem::replace(&mut self.image, img)
+    }
+}
+
+struct Snapshot {
+    operation: String,
+    image: RgbaImage,
+}
+
+#[derive(Serialize)]
+pub struct History {
+    // Operations that can be undone, oldest first
+    pub undo: Vec<String>,
+    // Operations that can be redone, next first
+    pub redo: Vec<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct Thumbnail {
+    pub width: u32,
+    pub height: u32,
+    pub base64: String,
+}
+
+fn missing(id: u32) -> AppError {
+    AppError::invalid("handle", format!("No cached image with handle {}", id))
+}
+
+impl ImageCache {
+    pub fn insert(&self, img: RgbaImage) -> u32 {
+        let mut inner = self.inner.lock().unwrap();
+        inner.next_id += 1;
+        let id = inner.next_id;
+        inner.images.insert(
+            id,
+            CachedEntry {
+                image: img,
+                undo: Vec::new(),
+                redo: Vec::new(),
+                thumbnails: HashMap::new(),
+                revision: 0,
+            },
+        );
+        id
+    }
+
+    pub fn get(&self, id: u32) -> Result<RgbaImage, AppError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .images
+            .get(&id)
+            .map(|entry| entry.image.clone())
+            .ok_or_else(|| missing(id))
+    }
+
+    pub fn pixel(&self, id: u32, x: u32, y: u32) -> Result<Rgba<u8>, AppError> {
+        let inner = self.inner.lock().unwrap();
+        let image = &inner.images.get(&id).ok_or_else(|| missing(id))?.image;
+        if x >= image.width() || y >= image.height() {
+            return Err(AppError::invalid("x", "Pixel lies outside the image"));
+        }
+        Ok(*image.get_pixel(x, y))
+    }
+
+    // Copy of the part of the image inside the given rectangle, clamped to
+    // its bounds, without cloning the whole image
+    pub fn region(
+        &self,
+        id: u32,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(u32, u32, RgbaImage), AppError> {
+        let inner = self.inner.lock().unwrap();
+        let image = &inner.images.get(&id).ok_or_else(|| missing(id))?.image;
+        let x = x.min(image.width());
+        let y = y.min(image.height());
+        let width = width.min(image.width() - x);
+        let height = height.min(image.height() - y);
+        if width == 0 || height == 0 {
+            return Err(AppError::invalid("x", "Region lies outside the image"));
+        }
+        Ok((
+            x,
+            y,
+            imageops::crop_imm(image, x, y, width, height).to_image(),
+        ))
+    }
+
+    // Store the result of `operation`, keeping the previous image for undo.
+    // A new edit discards anything that was undone.
+    pub fn commit(&self, id: u32, operation: &str, img: RgbaImage) -> Result<(), AppError> {
+        let mut inner = self.inner.lock().unwrap();
+        let entry = inner.images.get_mut(&id).ok_or_else(|| missing(id))?;
+        let previous = entry.set_image(img);
+        entry.undo.push(Snapshot {
+            operation: operation.to_string(),
+            image: previous,
+        });
+        if entry.undo.len() > HISTORY_LIMIT {
+            entry.undo.remove(0);
+        }
+        entry.redo.clear();
+        Ok(())
+    }
+
+    // Step back one edit; returns the restored image
+    pub fn undo(&self, id: u32) -> Result<RgbaImage, AppError> {
+        let mut inner = self.inner.lock().unwrap();
+        let entry = inner.images.get_mut(&id).ok_or_else(|| missing(id))?;
+        let step = entry
+            .undo
+            .pop()
+            .ok_or_else(|| AppError::invalid("handle", "Nothing to undo"))?;
+        let current = entry.set_image(step.image);
+        entry.redo.push(Snapshot {
+            operation: step.operation,
+            image: current,
+        });
+        Ok(entry.image.clone())
+    }
+
+    pub fn redo(&self, id: u32) -> Result<RgbaImage, AppError> {
+        let mut inner = self.inner.lock().unwrap();
+        let entry = inner.images.get_mut(&id).ok_or_else(|| missing(id))?;
+        let step = entry
+            .redo
+            .pop()
+            .ok_or_else(|| AppError::invalid("handle", "Nothing to redo"))?;
+        let previous = entry.set_image(step.image);
+        entry.undo.push(Snapshot {
+            operation: step.operation,
+            image: previous,
+        });
+        Ok(entry.image.clone())
+    }
+
+    // Downscaled so neither side exceeds `max_dimension`; smaller images are
+    // returned at their own size. Repeated requests reuse the encoded preview.
+    pub fn thumbnail(&self, id: u32, max_dimension: u32) -> Result<Thumbnail, AppError> {
+        if max_dimension == 0 {
+            return Err(AppError::invalid(
+                "max_dimension",
+                "Thumbnail size must be at least 1",
+            ));
+        }
+        let (image, revision) = {
+            let inner = self.inner.lock().unwrap();
+            let entry = inner.images.get(&id).ok_or_else(|| missing(id))?;
+            if let Some(thumbnail) = entry.thumbnails.get(&max_dimension) {
+                return Ok(thumbnail.clone());
+            }
+            (entry.image.clone(), entry.revision)
+        };
+
+        // Resized and encoded without the lock so other commands on the
+        // cache aren't held up
+        let (width, height) = image.dimensions();
+        let scale = (max_dimension as f32 / width.max(height) as f32).min(1.0);
+        let small = if scale < 1.0 {
+            let w = ((width as f32 * scale).round() as u32).max(1);
+            let h = ((height as f32 * scale).round() as u32).max(1);
+            imageops::thumbnail(&image, w, h)
+        } else {
+            image
+        };
+        let thumbnail = Thumbnail {
+            width: small.width(),
+            height: small.height(),
+            base64: encode_png_base64(&small.into())?,
+        };
+
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(entry) = inner.images.get_mut(&id) {
+            if entry.revision == revision {
+                entry.thumbnails.insert(max_dimension, thumbnail.clone());
+            }
+        }
+        Ok(thumbnail)
+    }
+
+    pub fn history(&self, id: u32) -> Result<History, AppError> {
+        let inner = self.inner.lock().unwrap();
+        let entry = inner.images.get(&id).ok_or_else(|| missing(id))?;
+        Ok(History {
+            undo: entry.undo.iter().map(|s| s.operation.clone()).collect(),
+            redo: entry
+                .redo
+                .iter()
+                .rev()
+                .map(|s| s.operation.clone())
+                .collect(),
+        })
+    }
+
+    pub fn remove(&self, id: u32) {
+        self.inner.lock().unwrap().images.remove(&id);
+    }
+}
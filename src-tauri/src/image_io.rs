@@ -0,0 +1,191 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, Rgba};
+use serde::Deserialize;
+use std::io::Cursor;
+use std::path::Path;
+
+use crate::bit_depth::{self, DepthOptions};
+use crate::color_profile::{convert_to_srgb, ColorProfile};
+use crate::error::AppError;
+use crate::metadata::png_gamma;
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    #[serde(default = "opaque")]
+    pub a: u8,
+}
+
+fn opaque() -> u8 {
+    255
+}
+
+impl Color {
+    pub fn to_rgba(self) -> Rgba<u8> {
+        Rgba([self.r, self.g, self.b, self.a])
+    }
+}
+
+// Hue in degrees (0-360), saturation and lightness 0-1
+pub fn rgb_to_hsl(r: u8, g: u8, b: u8) -> (f32, f32, f32) {
+    let (r, g, b) = (r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let lightness = (max + min) / 2.0;
+    let delta = max - min;
+    if delta == 0.0 {
+        return (0.0, 0.0, lightness);
+    }
+
+    let saturation = delta / (1.0 - (2.0 * lightness - 1.0).abs());
+    let hue = if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    (hue, saturation, lightness)
+}
+
+pub fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> (u8, u8, u8) {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let sector = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    let channel = |v: f32| ((v + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+    (channel(r), channel(g), channel(b))
+}
+
+// An input image given either as a file on disk or as base64 data
+#[derive(Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ImageSource {
+    Path { path: String },
+    Base64 { data: String },
+}
+
+impl ImageSource {
+    // Display name for reports and output file names
+    pub fn name(&self) -> String {
+        match self {
+            ImageSource::Path { path } => std::path::Path::new(path)
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.clone()),
+            ImageSource::Base64 { .. } => "image".to_string(),
+        }
+    }
+
+    // The encoded file as given
+    pub fn read_bytes(&self) -> Result<Vec<u8>, AppError> {
+        Ok(match self {
+            ImageSource::Path { path } => std::fs::read(path)?,
+            ImageSource::Base64 { data } => STANDARD.decode(strip_data_url(data))?,
+        })
+    }
+
+    // Decoded image and the size of its encoded bytes
+    pub fn load(&self) -> Result<(DynamicImage, usize), AppError> {
+        let bytes = self.read_bytes()?;
+        let img = decode_image_bytes(&bytes)?;
+        Ok((img, bytes.len()))
+    }
+}
+
+// Strip a `data:<mime>;base64,` prefix if present
+pub fn strip_data_url(base64_input: &str) -> &str {
+    match base64_input.split_once(";base64,") {
+        Some((prefix, data)) if prefix.starts_with("data:") => data,
+        _ => base64_input,
+    }
+}
+
+pub fn to_data_url(bytes: &[u8], mime_type: &str) -> String {
+    format!("data:{};base64,{}", mime_type, STANDARD.encode(bytes))
+}
+
+// Decode an encoded image, converting an embedded ICC profile (or a bare PNG
+// gamma) to sRGB so colors match what image editors show
+pub fn decode_image_bytes(bytes: &[u8]) -> Result<DynamicImage, AppError> {
+    decode_image_with(bytes, &DepthOptions::default())
+}
+
+// As `decode_image_bytes`, with explicit control over how 16-bit and HDR
+// sources are reduced to 8 bits
+pub fn decode_image_with(bytes: &[u8], options: &DepthOptions) -> Result<DynamicImage, AppError> {
+    let mut decoder = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()?
+        .into_decoder()?;
+    let icc = decoder.icc_profile().ok().flatten();
+    let img = DynamicImage::from_decoder(decoder)?;
+
+    // The common 1/2.2 is treated as sRGB, as browsers do
+    let profile = match icc {
+        Some(icc) => ColorProfile::from_icc(&icc),
+        None => png_gamma(bytes)
+            .filter(|g| (g - 1.0 / 2.2).abs() > 0.005)
+            .map(ColorProfile::from_gamma),
+    };
+    let img = if bit_depth::is_float(&img) {
+        DynamicImage::ImageRgba32F(bit_depth::tone_map(&img, options))
+    } else {
+        match profile {
+            Some(profile) if !profile.is_srgb() => convert_to_srgb(&img, &profile),
+            _ => img,
+        }
+    };
+    Ok(bit_depth::reduce_to_8bit(img, options.dither))
+}
+
+pub fn open_image(path: &Path) -> Result<DynamicImage, AppError> {
+    open_image_with(path, &DepthOptions::default())
+}
+
+pub fn open_image_with(path: &Path, options: &DepthOptions) -> Result<DynamicImage, AppError> {
+    let bytes = std::fs::read(path).map_err(|e| AppError::from(e).context(path.display()))?;
+    decode_image_with(&bytes, options).map_err(|e| e.context(path.display()))
+}
+
+// Decode a base64 image (with or without the data URL prefix)
+pub fn decode_base64_image(base64_input: &str) -> Result<DynamicImage, AppError> {
+    let bytes = STANDARD.decode(strip_data_url(base64_input))?;
+    decode_image_bytes(&bytes)
+}
+
+// Pixel values exactly as stored, for data textures (normal maps, masks,
+// packed channels) that must not be color converted
+pub fn decode_base64_raw(base64_input: &str) -> Result<DynamicImage, AppError> {
+    let bytes = STANDARD.decode(strip_data_url(base64_input))?;
+    Ok(image::load_from_memory(&bytes)?)
+}
+
+pub fn encode_png_bytes(img: &DynamicImage) -> Result<Vec<u8>, AppError> {
+    let mut buf = Cursor::new(Vec::new());
+    img.write_to(&mut buf, ImageFormat::Png).map_err(AppError::encode)?;
+    Ok(buf.into_inner())
+}
+
+pub fn encode_png_base64(img: &DynamicImage) -> Result<String, AppError> {
+    Ok(to_data_url(&encode_png_bytes(img)?, "image/png"))
+}
+
+// 8-connected neighbors of (x, y) that lie inside the image
+pub fn neighbors(x: u32, y: u32, width: u32, height: u32) -> impl Iterator<Item = (u32, u32)> {
+    (-1i64..=1)
+        .flat_map(|dy| (-1i64..=1).map(move |dx| (dx, dy)))
+        .filter(|&(dx, dy)| dx != 0 || dy != 0)
+        .map(move |(dx, dy)| (x as i64 + dx, y as i64 + dy))
+        .filter(move |&(nx, ny)| nx >= 0 && ny >= 0 && nx < width as i64 && ny < height as i64)
+        .map(|(nx, ny)| (nx as u32, ny as u32))
+}
@@ -0,0 +1,260 @@
+use image::{imageops, DynamicImage, GenericImageView, RgbaImage};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+
+use crate::alpha::unpremultiply;
+use crate::atlas_packer::{AnimationTag, SourceSprite};
+use crate::error::AppError;
+use crate::maxrects::Region;
+
+#[derive(Deserialize)]
+struct Rect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize, Default)]
+struct Offset {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Deserialize)]
+struct Frame {
+    frame: Rect,
+    #[serde(default)]
+    offset: Option<Offset>,
+    #[serde(default)]
+    padding: Option<u32>,
+    // Written by other packers; ours never rotate
+    #[serde(default)]
+    rotated: bool,
+    #[serde(rename = "spriteSourceSize", default)]
+    sprite_source_size: Option<Rect>,
+    #[serde(rename = "sourceSize", default)]
+    source_size: Option<Size>,
+}
+
+#[derive(Deserialize)]
+struct Animation {
+    frames: Vec<String>,
+    #[serde(rename = "frameRate")]
+    frame_rate: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct Meta {
+    size: Size,
+}
+
+#[derive(Deserialize)]
+struct Size {
+    w: u32,
+    h: u32,
+}
+
+// The subset of our Phaser JSON needed to rebuild a layout
+#[derive(Deserialize)]
+struct PreviousAtlas {
+    frames: BTreeMap<String, Frame>,
+    #[serde(default)]
+    animations: BTreeMap<String, Animation>,
+    meta: Meta,
+}
+
+fn parse_atlas(json: &str) -> Result<PreviousAtlas, AppError> {
+    serde_json::from_str(json).map_err(|e| AppError::decode(format!("Invalid atlas JSON: {}", e)))
+}
+
+// Atlas width and height from the JSON's meta.size
+pub fn atlas_size(json: &str) -> Result<(u32, u32), AppError> {
+    let size = parse_atlas(json)?.meta.size;
+    Ok((size.w, size.h))
+}
+
+// Frame rectangles of an atlas JSON by name
+pub fn frame_regions(json: &str) -> Result<Vec<(String, Region)>, AppError> {
+    Ok(parse_atlas(json)?
+        .frames
+        .into_iter()
+        .map(|(name, f)| {
+            let Rect { x, y, w, h } = f.frame;
+            (
+                name,
+                Region {
+                    x,
+                    y,
+                    width: w,
+                    height: h,
+                },
+            )
+        })
+        .collect())
+}
+
+pub struct ExtractedFrame {
+    // The pixels stored in the atlas
+    pub trimmed: RgbaImage,
+    // Placed back on a canvas of the original size
+    pub untrimmed: RgbaImage,
+    // Where `trimmed` sits within `untrimmed`
+    pub trim_x: u32,
+    pub trim_y: u32,
+}
+
+// Largest side of an untrimmed frame, so a corrupt sourceSize can't
+// allocate GBs
+const MAX_SOURCE_SIDE: u32 = 16384;
+
+// Cut frame `name` back out of an atlas image. Rotated frames (stored 90°
+// clockwise) are turned upright; `premultiplied` undoes PMA.
+pub fn extract_frame(
+    atlas: &RgbaImage,
+    json: &str,
+    name: &str,
+    premultiplied: bool,
+) -> Result<ExtractedFrame, AppError> {
+    let mut previous = parse_atlas(json)?;
+    let frame = previous
+        .frames
+        .remove(name)
+        .ok_or_else(|| AppError::invalid("frame_name", format!("No frame named \"{}\"", name)))?;
+    let Rect { x, y, w, h } = frame.frame;
+    let (stored_w, stored_h) = if frame.rotated { (h, w) } else { (w, h) };
+    if x.saturating_add(stored_w) > atlas.width() || y.saturating_add(stored_h) > atlas.height() {
+        return Err(AppError::invalid(
+            "atlas_json",
+            format!("Frame \"{}\" lies outside the atlas image", name),
+        ));
+    }
+
+    let stored = imageops::crop_imm(atlas, x, y, stored_w, stored_h).to_image();
+    let mut trimmed = if frame.rotated {
+        imageops::rotate270(&stored)
+    } else {
+        stored
+    };
+    if premultiplied {
+        unpremultiply(&mut trimmed);
+    }
+
+    let (trim_x, trim_y) = frame.sprite_source_size.map_or((0, 0), |r| (r.x, r.y));
+    let (source_w, source_h) = frame.source_size.map_or((w, h), |s| (s.w, s.h));
+    let fits = |trim: u32, size: u32, source: u32| {
+        trim.checked_add(size).is_some_and(|end| end <= source) && source <= MAX_SOURCE_SIDE
+    };
+    if !fits(trim_x, w, source_w) || !fits(trim_y, h, source_h) {
+        return Err(AppError::invalid(
+            "atlas_json",
+            format!("Frame \"{}\" doesn't fit its sourceSize", name),
+        ));
+    }
+    let mut untrimmed = RgbaImage::new(source_w, source_h);
+    imageops::replace(&mut untrimmed, &trimmed, trim_x as i64, trim_y as i64);
+    Ok(ExtractedFrame {
+        trimmed,
+        untrimmed,
+        trim_x,
+        trim_y,
+    })
+}
+
+pub struct MergedLayout {
+    pub sprites: Vec<SourceSprite>,
+    // Sprites that got a new position (added, or resized in place)
+    pub placed: Vec<String>,
+    pub previous_size: (u32, u32),
+}
+
+// Rebuild the previous atlas' sprites pinned at their old coordinates, then
+// apply the update: removed sprites free their space, same-size replacements
+// keep their slot, and new or resized sprites are left unpinned for the
+// packer to fit into free space. `premultiplied` undoes PMA on the old image.
+pub fn merge_update(
+    previous_image: DynamicImage,
+    previous_json: &str,
+    premultiplied: bool,
+    upserts: Vec<SourceSprite>,
+    removed: &[String],
+) -> Result<MergedLayout, AppError> {
+    let previous = parse_atlas(previous_json)?;
+    let mut atlas = previous_image.to_rgba8();
+    if premultiplied {
+        unpremultiply(&mut atlas);
+    }
+    let atlas = DynamicImage::ImageRgba8(atlas);
+
+    let mut animations: BTreeMap<&str, AnimationTag> = BTreeMap::new();
+    for (name, animation) in &previous.animations {
+        for (index, frame) in animation.frames.iter().enumerate() {
+            animations.insert(
+                frame,
+                AnimationTag {
+                    name: name.clone(),
+                    frame_index: Some(index as u32),
+                    frame_rate: animation.frame_rate,
+                },
+            );
+        }
+    }
+
+    let removed: HashSet<&str> = removed.iter().map(String::as_str).collect();
+    let mut upserts: BTreeMap<String, SourceSprite> =
+        upserts.into_iter().map(|s| (s.name.clone(), s)).collect();
+
+    let mut sprites = Vec::new();
+    let mut placed = Vec::new();
+    for (name, frame) in &previous.frames {
+        if removed.contains(name.as_str()) {
+            continue;
+        }
+        let Rect { x, y, w, h } = frame.frame;
+
+        let sprite = match upserts.remove(name) {
+            Some(mut replacement) => {
+                if replacement.image.dimensions() == (w, h) {
+                    replacement.fixed_position = Some((x, y));
+                } else {
+                    placed.push(name.clone());
+                }
+                replacement
+            }
+            None => {
+                if x + w > atlas.width() || y + h > atlas.height() {
+                    return Err(AppError::invalid(
+                        "previous_json",
+                        format!("Frame \"{}\" lies outside the atlas image", name),
+                    ));
+                }
+                let offset = frame.offset.as_ref();
+                SourceSprite {
+                    name: name.clone(),
+                    image: atlas.crop_imm(x, y, w, h),
+                    offset_x: offset.map_or(0, |o| o.x),
+                    offset_y: offset.map_or(0, |o| o.y),
+                    animation: animations.get(name.as_str()).cloned(),
+                    companions: BTreeMap::new(),
+                    group: None,
+                    keep_together: None,
+                    fixed_position: Some((x, y)),
+                    padding: frame.padding,
+                    lossless: false,
+                }
+            }
+        };
+        sprites.push(sprite);
+    }
+
+    for (name, sprite) in upserts {
+        placed.push(name);
+        sprites.push(sprite);
+    }
+
+    Ok(MergedLayout {
+        sprites,
+        placed,
+        previous_size: (previous.meta.size.w, previous.meta.size.h),
+    })
+}
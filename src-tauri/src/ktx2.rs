@@ -0,0 +1,541 @@
+use basis_universal::{BasisTextureFormat, ColorSpace, Compressor, CompressorParams};
+use image::RgbaImage;
+use serde::Deserialize;
+
+use crate::error::AppError;
+
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+// Data Format Descriptor constants (Khronos Data Format spec)
+const KHR_DF_MODEL_RGBSDA: u8 = 1;
+const KHR_DF_MODEL_ETC1S: u8 = 163;
+const KHR_DF_MODEL_UASTC: u8 = 166;
+const KHR_DF_PRIMARIES_BT709: u8 = 1;
+const KHR_DF_TRANSFER_LINEAR: u8 = 1;
+const KHR_DF_TRANSFER_SRGB: u8 = 2;
+const KHR_DF_CHANNEL_RGBSDA_ALPHA: u8 = 15;
+const KHR_DF_CHANNEL_ETC1S_RGB: u8 = 0;
+const KHR_DF_CHANNEL_ETC1S_AAA: u8 = 15;
+const KHR_DF_CHANNEL_UASTC_RGB: u8 = 0;
+const KHR_DF_CHANNEL_UASTC_RGBA: u8 = 3;
+
+const VK_FORMAT_R8G8B8A8_UNORM: u32 = 37;
+const VK_FORMAT_R8G8B8A8_SRGB: u32 = 43;
+
+const SUPERCOMPRESSION_NONE: u32 = 0;
+const SUPERCOMPRESSION_BASIS_LZ: u32 = 1;
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(tag = "codec", rename_all = "lowercase")]
+pub enum BasisCodec {
+    // Small files, lower quality (BasisLZ supercompressed)
+    Etc1s {
+        #[serde(default = "default_etc1s_quality")]
+        quality: u32,
+    },
+    // Near-BC7 quality, larger files
+    Uastc {
+        #[serde(default = "default_uastc_quality")]
+        quality: u32,
+    },
+}
+
+fn default_etc1s_quality() -> u32 {
+    basis_universal::ETC1S_QUALITY_DEFAULT
+}
+
+fn default_uastc_quality() -> u32 {
+    basis_universal::UASTC_QUALITY_DEFAULT
+}
+
+#[derive(Deserialize)]
+pub struct Ktx2Options {
+    #[serde(flatten)]
+    pub codec: BasisCodec,
+    #[serde(default)]
+    pub mipmaps: bool,
+    #[serde(default = "default_srgb")]
+    pub srgb: bool,
+}
+
+fn default_srgb() -> bool {
+    true
+}
+
+// A KTX2 file ready to be serialized. Level 0 is the base level.
+pub struct Ktx2Texture {
+    pub vk_format: u32,
+    pub type_size: u32,
+    pub width: u32,
+    pub height: u32,
+    // 0 for a plain 2D texture, otherwise the number of array layers
+    pub layer_count: u32,
+    pub supercompression: u32,
+    pub dfd: Vec<u8>,
+    pub supercompression_global_data: Vec<u8>,
+    pub levels: Vec<Vec<u8>>,
+    // Per-level size before supercompression (0 for BasisLZ)
+    pub uncompressed_lengths: Vec<u64>,
+    pub level_alignment: usize,
+}
+
+fn align(value: usize, alignment: usize) -> usize {
+    value.div_ceil(alignment) * alignment
+}
+
+fn key_value_data() -> Vec<u8> {
+    let mut entry = Vec::new();
+    entry.extend_from_slice(b"KTXwriter\0texturepack\0");
+
+    let mut kvd = Vec::new();
+    kvd.extend_from_slice(&(entry.len() as u32).to_le_bytes());
+    kvd.extend_from_slice(&entry);
+    kvd.resize(align(kvd.len(), 4), 0);
+    kvd
+}
+
+impl Ktx2Texture {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let level_count = self.levels.len();
+        let kvd = key_value_data();
+
+        let index_end = 12 + 36 + 32 + level_count * 24;
+        let dfd_offset = index_end;
+        let kvd_offset = dfd_offset + self.dfd.len();
+        let mut cursor = kvd_offset + kvd.len();
+        let (sgd_offset, sgd_len) = if self.supercompression_global_data.is_empty() {
+            (0, 0)
+        } else {
+            cursor = align(cursor, 8);
+            let offset = cursor;
+            cursor += self.supercompression_global_data.len();
+            (offset, self.supercompression_global_data.len())
+        };
+
+        // Mip levels are stored smallest first
+        let mut level_offsets = vec![0usize; level_count];
+        for level in (0..level_count).rev() {
+            cursor = align(cursor, self.level_alignment);
+            level_offsets[level] = cursor;
+            cursor += self.levels[level].len();
+        }
+
+        let mut out = Vec::with_capacity(cursor);
+        out.extend_from_slice(&KTX2_IDENTIFIER);
+        for value in [
+            self.vk_format,
+            self.type_size,
+            self.width,
+            self.height,
+            0, // pixelDepth
+            self.layer_count,
+            1, // faceCount
+            level_count as u32,
+            self.supercompression,
+        ] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+
+        out.extend_from_slice(&(dfd_offset as u32).to_le_bytes());
+        out.extend_from_slice(&(self.dfd.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(kvd_offset as u32).to_le_bytes());
+        out.extend_from_slice(&(kvd.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(sgd_offset as u64).to_le_bytes());
+        out.extend_from_slice(&(sgd_len as u64).to_le_bytes());
+
+        for ((offset, data), uncompressed) in level_offsets
+            .iter()
+            .zip(&self.levels)
+            .zip(&self.uncompressed_lengths)
+        {
+            out.extend_from_slice(&(*offset as u64).to_le_bytes());
+            out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            out.extend_from_slice(&uncompressed.to_le_bytes());
+        }
+
+        out.extend_from_slice(&self.dfd);
+        out.extend_from_slice(&kvd);
+        if sgd_len > 0 {
+            out.resize(sgd_offset, 0);
+            out.extend_from_slice(&self.supercompression_global_data);
+        }
+        for level in (0..level_count).rev() {
+            out.resize(level_offsets[level], 0);
+            out.extend_from_slice(&self.levels[level]);
+        }
+
+        out
+    }
+}
+
+pub struct DfdSample {
+    pub bit_offset: u16,
+    pub bit_length: u8,
+    pub channel_type: u8,
+    pub sample_lower: u32,
+    pub sample_upper: u32,
+}
+
+// Basic descriptor block with a leading total-size word
+pub fn build_dfd(
+    color_model: u8,
+    srgb: bool,
+    block_dimensions: [u8; 4],
+    bytes_plane0: u8,
+    samples: &[DfdSample],
+) -> Vec<u8> {
+    let block_size = 24 + 16 * samples.len();
+    let mut dfd = Vec::with_capacity(4 + block_size);
+
+    dfd.extend_from_slice(&((4 + block_size) as u32).to_le_bytes());
+    dfd.extend_from_slice(&0u32.to_le_bytes()); // vendorId = Khronos, descriptorType = basic
+    dfd.extend_from_slice(&2u16.to_le_bytes()); // versionNumber
+    dfd.extend_from_slice(&(block_size as u16).to_le_bytes());
+    dfd.push(color_model);
+    dfd.push(KHR_DF_PRIMARIES_BT709);
+    dfd.push(if srgb {
+        KHR_DF_TRANSFER_SRGB
+    } else {
+        KHR_DF_TRANSFER_LINEAR
+    });
+    dfd.push(0); // flags: straight alpha
+    dfd.extend_from_slice(&block_dimensions);
+    dfd.push(bytes_plane0);
+    dfd.extend_from_slice(&[0; 7]);
+
+    for sample in samples {
+        dfd.extend_from_slice(&sample.bit_offset.to_le_bytes());
+        dfd.push(sample.bit_length - 1);
+        dfd.push(sample.channel_type);
+        dfd.extend_from_slice(&[0; 4]); // samplePosition
+        dfd.extend_from_slice(&sample.sample_lower.to_le_bytes());
+        dfd.extend_from_slice(&sample.sample_upper.to_le_bytes());
+    }
+
+    dfd
+}
+
+// Minimal reader for the .basis container produced by the Basis encoder;
+// all multi-byte fields are little-endian "packed" integers
+fn read_packed(data: &[u8], offset: usize, len: usize) -> Result<u64, AppError> {
+    let bytes = data
+        .get(offset..offset + len)
+        .ok_or_else(|| AppError::encode("Truncated .basis file"))?;
+    Ok(bytes
+        .iter()
+        .rev()
+        .fold(0u64, |acc, &b| (acc << 8) | b as u64))
+}
+
+struct BasisSlice {
+    image_index: u32,
+    level_index: u32,
+    is_alpha: bool,
+    data: Vec<u8>,
+}
+
+struct BasisFile {
+    etc1s: bool,
+    has_alpha_slices: bool,
+    total_endpoints: u16,
+    total_selectors: u16,
+    endpoints: Vec<u8>,
+    selectors: Vec<u8>,
+    tables: Vec<u8>,
+    extended: Vec<u8>,
+    slices: Vec<BasisSlice>,
+}
+
+const BASIS_HEADER_SIZE: usize = 77;
+const BASIS_SLICE_DESC_SIZE: usize = 23;
+const BASIS_FLAG_HAS_ALPHA_SLICES: u64 = 4;
+const BASIS_TEX_FORMAT_UASTC: u64 = 1;
+
+fn parse_basis(data: &[u8]) -> Result<BasisFile, AppError> {
+    if data.len() < BASIS_HEADER_SIZE || &data[0..2] != b"sB" {
+        return Err(AppError::encode("Basis encoder produced an invalid file"));
+    }
+
+    let section = |ofs_at: usize, ofs_len: usize, size_at: usize, size_len: usize| {
+        let ofs = read_packed(data, ofs_at, ofs_len)? as usize;
+        let size = read_packed(data, size_at, size_len)? as usize;
+        data.get(ofs..ofs + size)
+            .map(|s| s.to_vec())
+            .ok_or_else(|| AppError::encode("Truncated .basis file"))
+    };
+
+    let total_slices = read_packed(data, 14, 3)? as usize;
+    let tex_format = read_packed(data, 20, 1)?;
+    let flags = read_packed(data, 21, 2)?;
+    let slice_desc_ofs = read_packed(data, 65, 4)? as usize;
+
+    let mut slices = Vec::with_capacity(total_slices);
+    for i in 0..total_slices {
+        let base = slice_desc_ofs + i * BASIS_SLICE_DESC_SIZE;
+        let file_ofs = read_packed(data, base + 13, 4)? as usize;
+        let file_size = read_packed(data, base + 17, 4)? as usize;
+        slices.push(BasisSlice {
+            image_index: read_packed(data, base, 3)? as u32,
+            level_index: read_packed(data, base + 3, 1)? as u32,
+            is_alpha: read_packed(data, base + 4, 1)? & 1 != 0,
+            data: data
+                .get(file_ofs..file_ofs + file_size)
+                .ok_or_else(|| AppError::encode("Truncated .basis file"))?
+                .to_vec(),
+        });
+    }
+
+    Ok(BasisFile {
+        etc1s: tex_format != BASIS_TEX_FORMAT_UASTC,
+        has_alpha_slices: flags & BASIS_FLAG_HAS_ALPHA_SLICES != 0,
+        total_endpoints: read_packed(data, 39, 2)? as u16,
+        total_selectors: read_packed(data, 48, 2)? as u16,
+        endpoints: section(41, 4, 45, 3)?,
+        selectors: section(50, 4, 54, 3)?,
+        tables: section(57, 4, 61, 4)?,
+        extended: section(69, 4, 73, 4)?,
+        slices,
+    })
+}
+
+fn encode_basis(layers: &[RgbaImage], options: &Ktx2Options) -> Result<Vec<u8>, AppError> {
+    let mut params = CompressorParams::new();
+    match options.codec {
+        BasisCodec::Etc1s { quality } => {
+            params.set_basis_format(BasisTextureFormat::ETC1S);
+            params.set_etc1s_quality_level(quality.clamp(
+                basis_universal::ETC1S_QUALITY_MIN,
+                basis_universal::ETC1S_QUALITY_MAX,
+            ));
+        }
+        BasisCodec::Uastc { quality } => {
+            params.set_basis_format(BasisTextureFormat::UASTC4x4);
+            params.set_uastc_quality_level(quality.clamp(
+                basis_universal::UASTC_QUALITY_MIN,
+                basis_universal::UASTC_QUALITY_MAX,
+            ));
+        }
+    }
+    params.set_color_space(if options.srgb {
+        ColorSpace::Srgb
+    } else {
+        ColorSpace::Linear
+    });
+    params.set_generate_mipmaps(options.mipmaps);
+    params.set_print_status_to_stdout(false);
+
+    for (i, layer) in layers.iter().enumerate() {
+        params
+            .source_image_mut(i as u32)
+            .init(layer.as_raw(), layer.width(), layer.height(), 4);
+    }
+
+    // The current rayon pool's size, so a configured worker limit holds
+    let threads = rayon::current_num_threads() as u32;
+    let mut compressor = Compressor::new(threads);
+    // SAFETY: `params` outlives the compressor calls and holds valid source images
+    unsafe {
+        if !compressor.init(&params) {
+            return Err(AppError::encode("Failed to initialize the Basis encoder"));
+        }
+        compressor
+            .process()
+            .map_err(|e| AppError::encode(format!("Basis encoding failed: {:?}", e)))?;
+    }
+
+    Ok(compressor.basis_file().to_vec())
+}
+
+// Encode same-sized images (one per array layer) as a Basis Universal KTX2
+pub fn encode_ktx2(
+    layers: &[RgbaImage],
+    options: &Ktx2Options,
+    array: bool,
+) -> Result<Vec<u8>, AppError> {
+    let first = layers
+        .first()
+        .ok_or_else(|| AppError::invalid("layers", "No images to encode"))?;
+    if layers.iter().any(|l| l.dimensions() != first.dimensions()) {
+        return Err(AppError::invalid("layers", "All KTX2 layers must have the same size"));
+    }
+
+    let basis = parse_basis(&encode_basis(layers, options)?)?;
+    let level_count = basis
+        .slices
+        .iter()
+        .map(|s| s.level_index + 1)
+        .max()
+        .unwrap_or(1) as usize;
+    let layer_count = layers.len() as u32;
+
+    let texture = if basis.etc1s {
+        // BasisLZ: level data is the ETC1S slices, codebooks go in the global data
+        let mut levels = vec![Vec::new(); level_count];
+        let mut image_descs = Vec::new();
+
+        for (level, level_data) in levels.iter_mut().enumerate() {
+            for layer in 0..layer_count {
+                let find = |alpha: bool| {
+                    basis.slices.iter().find(|s| {
+                        s.image_index == layer
+                            && s.level_index as usize == level
+                            && s.is_alpha == alpha
+                    })
+                };
+                let rgb =
+                    find(false).ok_or_else(|| AppError::encode("Missing slice in .basis output"))?;
+                let rgb_range = (level_data.len(), rgb.data.len());
+                level_data.extend_from_slice(&rgb.data);
+
+                let alpha_range = match find(true) {
+                    Some(alpha) if basis.has_alpha_slices => {
+                        let range = (level_data.len(), alpha.data.len());
+                        level_data.extend_from_slice(&alpha.data);
+                        range
+                    }
+                    _ => (0, 0),
+                };
+
+                for value in [
+                    0u32, // imageFlags (no P-frames)
+                    rgb_range.0 as u32,
+                    rgb_range.1 as u32,
+                    alpha_range.0 as u32,
+                    alpha_range.1 as u32,
+                ] {
+                    image_descs.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+        }
+
+        let mut sgd = Vec::new();
+        sgd.extend_from_slice(&basis.total_endpoints.to_le_bytes());
+        sgd.extend_from_slice(&basis.total_selectors.to_le_bytes());
+        for len in [
+            basis.endpoints.len(),
+            basis.selectors.len(),
+            basis.tables.len(),
+            basis.extended.len(),
+        ] {
+            sgd.extend_from_slice(&(len as u32).to_le_bytes());
+        }
+        sgd.extend_from_slice(&image_descs);
+        sgd.extend_from_slice(&basis.endpoints);
+        sgd.extend_from_slice(&basis.selectors);
+        sgd.extend_from_slice(&basis.tables);
+        sgd.extend_from_slice(&basis.extended);
+
+        let mut samples = vec![DfdSample {
+            bit_offset: 0,
+            bit_length: 64,
+            channel_type: KHR_DF_CHANNEL_ETC1S_RGB,
+            sample_lower: 0,
+            sample_upper: u32::MAX,
+        }];
+        if basis.has_alpha_slices {
+            samples.push(DfdSample {
+                bit_offset: 64,
+                bit_length: 64,
+                channel_type: KHR_DF_CHANNEL_ETC1S_AAA,
+                sample_lower: 0,
+                sample_upper: u32::MAX,
+            });
+        }
+
+        Ktx2Texture {
+            vk_format: 0,
+            type_size: 1,
+            width: first.width(),
+            height: first.height(),
+            layer_count: if array { layer_count } else { 0 },
+            supercompression: SUPERCOMPRESSION_BASIS_LZ,
+            dfd: build_dfd(KHR_DF_MODEL_ETC1S, options.srgb, [3, 3, 0, 0], 0, &samples),
+            supercompression_global_data: sgd,
+            uncompressed_lengths: vec![0; level_count],
+            levels,
+            level_alignment: 1,
+        }
+    } else {
+        // UASTC: slices are raw 16-byte blocks stored without supercompression
+        let mut levels = vec![Vec::new(); level_count];
+        for (level, level_data) in levels.iter_mut().enumerate() {
+            for layer in 0..layer_count {
+                let slice = basis
+                    .slices
+                    .iter()
+                    .find(|s| s.image_index == layer && s.level_index as usize == level)
+                    .ok_or_else(|| AppError::encode("Missing slice in .basis output"))?;
+                level_data.extend_from_slice(&slice.data);
+            }
+        }
+
+        let has_alpha = layers.iter().any(|l| l.pixels().any(|p| p[3] < 255));
+        let samples = [DfdSample {
+            bit_offset: 0,
+            bit_length: 128,
+            channel_type: if has_alpha {
+                KHR_DF_CHANNEL_UASTC_RGBA
+            } else {
+                KHR_DF_CHANNEL_UASTC_RGB
+            },
+            sample_lower: 0,
+            sample_upper: u32::MAX,
+        }];
+
+        Ktx2Texture {
+            vk_format: 0,
+            type_size: 1,
+            width: first.width(),
+            height: first.height(),
+            layer_count: if array { layer_count } else { 0 },
+            supercompression: SUPERCOMPRESSION_NONE,
+            dfd: build_dfd(KHR_DF_MODEL_UASTC, options.srgb, [3, 3, 0, 0], 16, &samples),
+            supercompression_global_data: Vec::new(),
+            uncompressed_lengths: levels.iter().map(|l| l.len() as u64).collect(),
+            levels,
+            level_alignment: 16,
+        }
+    };
+
+    Ok(texture.to_bytes())
+}
+
+// Uncompressed RGBA8 KTX2 from an already generated mip chain (level 0 first)
+pub fn encode_rgba8_ktx2(levels: &[RgbaImage], srgb: bool) -> Result<Vec<u8>, AppError> {
+    let base = levels.first().ok_or_else(|| AppError::encode("No mip levels to encode"))?;
+
+    let samples: Vec<DfdSample> = [0, 1, 2, KHR_DF_CHANNEL_RGBSDA_ALPHA]
+        .iter()
+        .enumerate()
+        .map(|(i, &channel_type)| DfdSample {
+            bit_offset: i as u16 * 8,
+            bit_length: 8,
+            channel_type,
+            sample_lower: 0,
+            sample_upper: 255,
+        })
+        .collect();
+
+    let levels: Vec<Vec<u8>> = levels.iter().map(|l| l.as_raw().clone()).collect();
+    let texture = Ktx2Texture {
+        vk_format: if srgb {
+            VK_FORMAT_R8G8B8A8_SRGB
+        } else {
+            VK_FORMAT_R8G8B8A8_UNORM
+        },
+        type_size: 1,
+        width: base.width(),
+        height: base.height(),
+        layer_count: 0,
+        supercompression: SUPERCOMPRESSION_NONE,
+        dfd: build_dfd(KHR_DF_MODEL_RGBSDA, srgb, [0, 0, 0, 0], 4, &samples),
+        supercompression_global_data: Vec::new(),
+        uncompressed_lengths: levels.iter().map(|l| l.len() as u64).collect(),
+        levels,
+        level_alignment: 4,
+    };
+
+    Ok(texture.to_bytes())
+}
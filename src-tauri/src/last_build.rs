@@ -0,0 +1,504 @@
+use rayon::prelude::*;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::atlas_packer::{
+    pack_atlas, pack_atlas_groups, pack_atlas_variants, AtlasOptions, AtlasOutput, AtlasVariant,
+    SourceSprite,
+};
+use crate::budget::{self, Budget, BudgetReport, PageStats};
+use crate::encode::OutputFormat;
+use crate::error::AppError;
+use crate::hit_test::{AtlasFrames, FrameHit};
+use crate::incremental;
+use crate::ktx2::Ktx2Options;
+use crate::load_time::{self, FormatLoadTime, Network};
+use crate::sidecars::{precompressed_json, sidecars};
+use crate::texture_array::{self, TextureArray};
+use crate::zip::ZipWriter;
+
+// One atlas image + JSON, plus files written next to it (companions, .tsx)
+struct Artifact {
+    // Resolution variant scale, None for regular builds
+    scale: Option<f32>,
+    image_name: String,
+    image_bytes: Vec<u8>,
+    json: String,
+    extras: Vec<(String, Vec<u8>)>,
+}
+
+fn atlas_artifact(image_name: String, output: AtlasOutput) -> Artifact {
+    let extras = sidecars(
+        &image_name,
+        output.companions,
+        output.uv_bytes,
+        output.binary_bytes,
+        output.tsx,
+    );
+    Artifact {
+        scale: None,
+        image_name,
+        image_bytes: output.image_bytes,
+        json: output.json,
+        extras,
+    }
+}
+
+enum BuildKind {
+    Single,
+    Groups,
+    Variants {
+        variants: Vec<AtlasVariant>,
+        share_layout: bool,
+    },
+}
+
+// Everything needed to regenerate the last successful pack
+struct LastBuild {
+    sprites: Vec<SourceSprite>,
+    padding: u32,
+    options: AtlasOptions,
+    kind: BuildKind,
+}
+
+impl LastBuild {
+    fn pack(&self, options: &AtlasOptions) -> Result<Vec<Artifact>, AppError> {
+        Ok(match &self.kind {
+            BuildKind::Single => {
+                let image_name = format!("atlas.{}", options.format.extension());
+                let output = pack_atlas(&self.sprites, self.padding, options, &image_name)?;
+                vec![atlas_artifact(image_name, output)]
+            }
+            BuildKind::Groups => pack_atlas_groups(&self.sprites, self.padding, options)?
+                .into_iter()
+                .map(|g| atlas_artifact(g.image_name, g.atlas))
+                .collect(),
+            BuildKind::Variants {
+                variants,
+                share_layout,
+            } => pack_atlas_variants(
+                &self.sprites,
+                self.padding,
+                options,
+                variants,
+                *share_layout,
+            )?
+            .into_iter()
+            .map(|v| {
+                let extras = sidecars(
+                    &v.image_name,
+                    v.companions,
+                    v.uv_bytes,
+                    v.binary_bytes,
+                    None,
+                );
+                Artifact {
+                    scale: Some(v.scale),
+                    image_name: v.image_name,
+                    image_bytes: v.image_bytes,
+                    json: v.json,
+                    extras,
+                }
+            })
+            .collect(),
+        })
+    }
+
+    // Everything an export writes: the packed artifacts plus pre-compressed
+    // JSON
+    fn exported(&self) -> Result<Vec<Artifact>, AppError> {
+        let mut artifacts = self.pack(&self.options)?;
+        for artifact in &mut artifacts {
+            artifact.extras.extend(precompressed_json(
+                &artifact.image_name,
+                &artifact.json,
+                &self.options.precompress_json,
+            )?);
+        }
+        Ok(artifacts)
+    }
+}
+
+fn page_stats(artifacts: &[Artifact]) -> Result<Vec<PageStats>, AppError> {
+    artifacts
+        .iter()
+        .map(|artifact| {
+            let (width, height) = incremental::atlas_size(&artifact.json)?;
+            Ok(PageStats {
+                image: artifact.image_name.clone(),
+                width,
+                height,
+                image_bytes: artifact.image_bytes.len(),
+                total_bytes: artifact.image_bytes.len()
+                    + artifact.json.len()
+                    + artifact
+                        .extras
+                        .iter()
+                        .map(|(_, data)| data.len())
+                        .sum::<usize>(),
+            })
+        })
+        .collect()
+}
+
+fn animation_of(sprite: &SourceSprite) -> Option<&String> {
+    sprite.animation.as_ref().map(|a| &a.name)
+}
+
+fn load_time(
+    build: &LastBuild,
+    index: usize,
+    format: &OutputFormat,
+    networks: &[Network],
+) -> Result<FormatLoadTime, AppError> {
+    let options = AtlasOptions {
+        format: *format,
+        ..build.options.clone()
+    };
+    let artifacts = build.pack(&options)?;
+    let (mut total_bytes, mut image_bytes, mut pixels) = (0, 0, 0);
+    for artifact in &artifacts {
+        image_bytes += artifact.image_bytes.len();
+        total_bytes += artifact.image_bytes.len()
+            + artifact.json.len()
+            + artifact
+                .extras
+                .iter()
+                .map(|(_, data)| data.len())
+                .sum::<usize>();
+        let (width, height) = incremental::atlas_size(&artifact.json)?;
+        pixels += width as u64 * height as u64;
+    }
+    Ok(load_time::estimate(
+        index,
+        format,
+        total_bytes,
+        image_bytes,
+        pixels,
+        networks,
+    ))
+}
+
+const MANIFEST_NAME: &str = "manifest.json";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ManifestAtlas {
+    image: String,
+    json: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scale: Option<f32>,
+    // Companion atlases and tilesets that belong to this atlas
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    extras: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BundleManifest {
+    generator: &'static str,
+    version: &'static str,
+    // "single", "groups" or "variants"
+    kind: &'static str,
+    sprite_count: usize,
+    atlases: Vec<ManifestAtlas>,
+}
+
+// Managed state. Sprites are kept decoded so a re-export skips both the IPC
+// transfer and base64 decoding.
+#[derive(Default)]
+pub struct BuildState {
+    last: Mutex<Option<LastBuild>>,
+    // Frames of every page the last build returned, for hit testing
+    frames: Mutex<Vec<AtlasFrames>>,
+}
+
+impl BuildState {
+    // Index the (image name, JSON) of each page just returned to the preview
+    pub fn record_frames<'a>(
+        &self,
+        pages: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Result<(), AppError> {
+        let frames = pages
+            .into_iter()
+            .map(|(image, json)| AtlasFrames::parse(image, json))
+            .collect::<Result<Vec<_>, _>>()?;
+        *self.frames.lock().unwrap() = frames;
+        Ok(())
+    }
+
+    // The frame at atlas pixel (x, y) on page `image` (the first page when
+    // None), or None over empty space
+    pub fn hit_test(
+        &self,
+        x: f32,
+        y: f32,
+        image: Option<&str>,
+    ) -> Result<Option<FrameHit>, AppError> {
+        let frames = self.frames.lock().unwrap();
+        let page = match image {
+            Some(image) => frames
+                .iter()
+                .find(|page| page.image() == image)
+                .ok_or_else(|| {
+                    AppError::invalid("image", format!("No atlas page named \"{}\"", image))
+                })?,
+            None => frames
+                .first()
+                .ok_or_else(|| AppError::invalid("build", "Nothing has been packed yet"))?,
+        };
+        Ok(page.hit(x, y))
+    }
+
+    pub fn record_single(&self, sprites: Vec<SourceSprite>, padding: u32, options: AtlasOptions) {
+        *self.last.lock().unwrap() = Some(LastBuild {
+            sprites,
+            padding,
+            options,
+            kind: BuildKind::Single,
+        });
+    }
+
+    pub fn record_groups(&self, sprites: Vec<SourceSprite>, padding: u32, options: AtlasOptions) {
+        *self.last.lock().unwrap() = Some(LastBuild {
+            sprites,
+            padding,
+            options,
+            kind: BuildKind::Groups,
+        });
+    }
+
+    pub fn record_variants(
+        &self,
+        sprites: Vec<SourceSprite>,
+        padding: u32,
+        options: AtlasOptions,
+        variants: Vec<AtlasVariant>,
+        share_layout: bool,
+    ) {
+        *self.last.lock().unwrap() = Some(LastBuild {
+            sprites,
+            padding,
+            options,
+            kind: BuildKind::Variants {
+                variants,
+                share_layout,
+            },
+        });
+    }
+
+    // Repacks the last build, along with how many sprites and which kind of
+    // build it was
+    // Fails when the build's budget is exceeded, before anything is written
+    fn artifacts(&self) -> Result<(Vec<Artifact>, usize, &'static str), AppError> {
+        let last = self.last.lock().unwrap();
+        let build = last
+            .as_ref()
+            .ok_or_else(|| AppError::invalid("build", "Nothing has been packed yet"))?;
+
+        let artifacts = build.exported()?;
+        if let Some(budget) = &build.options.budget {
+            budget::check(budget, &page_stats(&artifacts)?).into_result()?;
+        }
+        let kind = match build.kind {
+            BuildKind::Single => "single",
+            BuildKind::Groups => "groups",
+            BuildKind::Variants { .. } => "variants",
+        };
+        Ok((artifacts, build.sprites.len(), kind))
+    }
+
+    // Regenerates the atlas image(s) and JSON into `output_dir` and returns
+    // the written paths
+    pub fn re_export(&self, output_dir: &Path) -> Result<Vec<String>, AppError> {
+        let (artifacts, _, _) = self.artifacts()?;
+        std::fs::create_dir_all(output_dir)?;
+        let mut written = Vec::new();
+        for artifact in artifacts {
+            let image_path = output_dir.join(&artifact.image_name);
+            let json_path: PathBuf = image_path.with_extension("json");
+            std::fs::write(&image_path, artifact.image_bytes)?;
+            std::fs::write(&json_path, artifact.json)?;
+            written.push(image_path.to_string_lossy().into_owned());
+            written.push(json_path.to_string_lossy().into_owned());
+            for (name, data) in artifact.extras {
+                let path = output_dir.join(name);
+                std::fs::write(&path, data)?;
+                written.push(path.to_string_lossy().into_owned());
+            }
+        }
+
+        Ok(written)
+    }
+
+    // The last build's sprites named in `frames` or belonging to one of
+    // `animations`, in build order, with its padding and options
+    pub fn subset(
+        &self,
+        frames: &[String],
+        animations: &[String],
+    ) -> Result<(Vec<SourceSprite>, u32, AtlasOptions), AppError> {
+        let last = self.last.lock().unwrap();
+        let build = last
+            .as_ref()
+            .ok_or_else(|| AppError::invalid("build", "Nothing has been packed yet"))?;
+        if let Some(name) = frames
+            .iter()
+            .find(|name| !build.sprites.iter().any(|s| &s.name == *name))
+        {
+            return Err(AppError::invalid(
+                "frames",
+                format!("No frame named \"{}\"", name),
+            ));
+        }
+        if let Some(name) = animations
+            .iter()
+            .find(|name| !build.sprites.iter().any(|s| animation_of(s) == Some(*name)))
+        {
+            return Err(AppError::invalid(
+                "animations",
+                format!("No animation named \"{}\"", name),
+            ));
+        }
+
+        let sprites: Vec<SourceSprite> = build
+            .sprites
+            .iter()
+            .filter(|s| {
+                frames.contains(&s.name) || animation_of(s).is_some_and(|a| animations.contains(a))
+            })
+            .cloned()
+            .collect();
+        if sprites.is_empty() {
+            return Err(AppError::invalid("frames", "No frames selected"));
+        }
+        Ok((sprites, build.padding, build.options.clone()))
+    }
+
+    // Measures the last build's export against `budget` (or the build's own)
+    // without failing, so the frontend can show the report
+    pub fn check_budget(&self, budget: Option<&Budget>) -> Result<BudgetReport, AppError> {
+        let last = self.last.lock().unwrap();
+        let build = last
+            .as_ref()
+            .ok_or_else(|| AppError::invalid("build", "Nothing has been packed yet"))?;
+        let budget = budget
+            .or(build.options.budget.as_ref())
+            .ok_or_else(|| AppError::invalid("budget", "No budget configured"))?;
+        let artifacts = build.exported()?;
+        Ok(budget::check(budget, &page_stats(&artifacts)?))
+    }
+
+    // Repacks the last build once per format and estimates how long each
+    // result takes to download and decode on every network. A format that
+    // fails to pack reports its error instead.
+    pub fn load_time_report(
+        &self,
+        formats: &[OutputFormat],
+        networks: &[Network],
+    ) -> Result<Vec<FormatLoadTime>, AppError> {
+        let last = self.last.lock().unwrap();
+        let build = last
+            .as_ref()
+            .ok_or_else(|| AppError::invalid("build", "Nothing has been packed yet"))?;
+        Ok(formats
+            .par_iter()
+            .enumerate()
+            .map(|(index, format)| {
+                load_time(build, index, format, networks).unwrap_or_else(|e| FormatLoadTime {
+                    error: Some(e),
+                    ..load_time::estimate(index, format, 0, 0, 0, &[])
+                })
+            })
+            .collect())
+    }
+
+    // The last build's pages stacked into one KTX2 array texture named
+    // `image_name`, with a JSON giving each frame's layer. Pages are rendered
+    // losslessly first so the Basis encoder sees the full-quality pixels.
+    pub fn texture_array(
+        &self,
+        image_name: &str,
+        ktx2: &Ktx2Options,
+    ) -> Result<TextureArray, AppError> {
+        let last = self.last.lock().unwrap();
+        let build = last
+            .as_ref()
+            .ok_or_else(|| AppError::invalid("build", "Nothing has been packed yet"))?;
+        if let BuildKind::Variants { .. } = build.kind {
+            return Err(AppError::invalid(
+                "build",
+                "Resolution variants can't be stacked into one texture array",
+            ));
+        }
+        let options = AtlasOptions {
+            format: OutputFormat::default(),
+            quantize: None,
+            page_profiles: Vec::new(),
+            uvs: false,
+            binary_metadata: false,
+            ..build.options.clone()
+        };
+        let pages = build
+            .pack(&options)?
+            .into_iter()
+            .map(|artifact| {
+                let img = image::load_from_memory(&artifact.image_bytes)
+                    .map_err(AppError::decode)?
+                    .to_rgba8();
+                Ok((artifact.image_name, img, artifact.json))
+            })
+            .collect::<Result<Vec<_>, AppError>>()?;
+        texture_array::stack_pages(pages, image_name, ktx2)
+    }
+
+    // Regenerates every artifact into one ZIP archive at `path`, with a
+    // manifest.json listing them, and returns the archive's entry names
+    pub fn export_bundle(&self, path: &Path) -> Result<Vec<String>, AppError> {
+        let (artifacts, sprite_count, kind) = self.artifacts()?;
+        let mut zip = ZipWriter::default();
+        let mut atlases = Vec::new();
+        for artifact in artifacts {
+            let json_name = Path::new(&artifact.image_name)
+                .with_extension("json")
+                .to_string_lossy()
+                .into_owned();
+            zip.add(&artifact.image_name, &artifact.image_bytes)?;
+            zip.add(&json_name, artifact.json.as_bytes())?;
+            let mut extras = Vec::new();
+            for (name, data) in artifact.extras {
+                zip.add(&name, &data)?;
+                extras.push(name);
+            }
+            atlases.push(ManifestAtlas {
+                image: artifact.image_name,
+                json: json_name,
+                scale: artifact.scale,
+                extras,
+            });
+        }
+
+        let mut entries: Vec<String> = atlases
+            .iter()
+            .flat_map(|a| [&a.image, &a.json].into_iter().chain(&a.extras).cloned())
+            .collect();
+        let manifest = BundleManifest {
+            generator: "texturepack",
+            version: env!("CARGO_PKG_VERSION"),
+            kind,
+            sprite_count,
+            atlases,
+        };
+        let json = serde_json::to_string_pretty(&manifest).map_err(AppError::encode)?;
+        zip.add(MANIFEST_NAME, json.as_bytes())?;
+        entries.push(MANIFEST_NAME.to_string());
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, zip.finish()?)
+            .map_err(|e| AppError::from(e).context(path.display()))?;
+        Ok(entries)
+    }
+}
@@ -1,18 +1,126 @@
+mod alpha;
+mod analysis;
+mod animation;
 mod atlas_packer;
+mod bcn;
+mod binary_meta;
+mod bit_depth;
+mod bitmap_font;
+mod budget;
+mod channels;
+pub mod cli;
+mod clipboard;
+mod color_key;
+mod color_profile;
+mod compress;
+mod dds;
+mod duplicate;
+mod effects;
+mod encode;
+mod error;
+mod filters;
+mod frame_order;
+mod hit_test;
+mod image_cache;
+mod image_io;
+mod incremental;
+mod ktx2;
+mod last_build;
+mod load_time;
+mod maxrects;
+mod metadata;
+mod metrics;
+mod mipmap;
+mod naming;
+mod natural;
+mod notifications;
+mod overlay;
+mod page_profile;
+mod pipeline;
+mod polygon;
+mod precompress;
+mod presets;
+mod psd;
+mod quantize;
+mod sdf;
+mod session;
+mod sidecars;
+mod sprite_export;
+mod texture_array;
+mod tileset;
+mod transform;
+mod uv;
+mod watch;
+mod workers;
+mod zip;
 
-use atlas_packer::{pack_atlas, SpriteInput, AtlasOutput};
+use analysis::{
+    ColorAnalysis, ColorAnalysisOptions, DuplicateGroup, DuplicateOptions, GroupSuggestion,
+    GroupingOptions, QuantizationReport, SpriteInfo, SpriteOverdraw, DEFAULT_WARN_DELTA_E,
+};
+use animation::{CompareMode, PreviewFormat, PreviewOptions, StripLayout};
+use atlas_packer::{
+    attach_issues, decode_sprites, decode_sprites_lenient, load_sprite_dir, pack_atlas,
+    pack_atlas_groups, pack_atlas_variants, AtlasOptions, AtlasOutput, AtlasVariant,
+    AtlasVariantOutput, DirImport, GroupedAtlas, SpriteInput,
+};
 use base64::{engine::general_purpose::STANDARD, Engine};
-use image::{imageops::FilterType, GenericImageView, ImageFormat};
+use bcn::BcFormat;
+use bit_depth::DepthOptions;
+use bitmap_font::{BitmapFontOptions, BitmapFontOutput};
+use budget::{Budget, BudgetReport};
+use channels::{ChannelSource, SourceChannel};
+use clipboard::ClipboardState;
+use color_key::{BackgroundOptions, BackgroundSuggestion, ColorToRemove};
+use compress::{BatchReport, CompressSettings, PreviewVariant};
+use dds::DdsOptions;
+use duplicate::{DuplicatedSprite, SpriteRef, DEFAULT_NAME_PATTERN};
+use effects::{EffectOutput, OutlineMode, ShadowConfig};
+use encode::{OutputFormat, PngStrip};
+use error::AppError;
+use filters::{Denoise, ImageFilter};
+use frame_order::{FrameKey, FrameSort};
+use hit_test::FrameHit;
+use image::{imageops, GenericImageView, ImageFormat, RgbaImage};
+use image_cache::{History, ImageCache, Thumbnail};
+use image_io::{
+    decode_base64_image, decode_base64_raw, encode_png_base64, open_image_with, rgb_to_hsl,
+    strip_data_url, to_data_url, Color, ImageSource,
+};
+use ktx2::Ktx2Options;
+use last_build::BuildState;
+use load_time::{FormatLoadTime, Network};
+use metadata::ImageMetadata;
+use mipmap::MipmapOptions;
+use naming::{RenamePreview, RenameRule};
+use notifications::{JobKind, NotificationSettings, Notifier};
+use overlay::{apply_checkerboard, Checkerboard, Guides, OverlayStyle};
+use pipeline::{EditOperation, EditPipelines, ImportOptions, ImportedImage};
+use precompress::Precompression;
+use presets::{ExportPreset, PresetStore};
+use psd::{PsdDocument, PsdSprite};
+use quantize::{Dithering, PaletteColor, QuantizeSettings};
+use rayon::prelude::*;
+use sdf::SdfOptions;
+use session::{RecentProject, SessionSnapshot, SessionState};
+use sprite_export::ExportedSprite;
+use std::collections::HashSet;
 use std::io::Cursor;
-use tauri::command;
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tauri::{command, AppHandle, Emitter, Manager, State};
+use tileset::ImportedTileset;
+use transform::{Anchor, ImageTransform, Interpolation, PixelArtAlgorithm, ResizeFilter};
+use watch::{WatchConfig, WatchState};
+use workers::{WorkerPool, WorkerStatus};
 
 #[command]
-fn load_image(path: String) -> Result<ImageData, String> {
-    let img = image::open(&path).map_err(|e| e.to_string())?;
+fn load_image(path: String, depth: Option<DepthOptions>) -> Result<ImageData, AppError> {
+    let img = open_image_with(Path::new(&path), &depth.unwrap_or_default())?;
     let (width, height) = img.dimensions();
 
     let mut buf = Cursor::new(Vec::new());
-    img.write_to(&mut buf, ImageFormat::Png).map_err(|e| e.to_string())?;
+    img.write_to(&mut buf, ImageFormat::Png).map_err(AppError::encode)?;
     let base64_data = STANDARD.encode(buf.get_ref());
 
     Ok(ImageData {
@@ -22,6 +130,28 @@ fn load_image(path: String) -> Result<ImageData, String> {
     })
 }
 
+// Image on the system clipboard, e.g. a fresh screenshot
+#[command]
+fn paste_image_from_clipboard(
+    clipboard: State<'_, ClipboardState>,
+) -> Result<ImageData, AppError> {
+    let img = clipboard.read_image()?;
+    Ok(ImageData {
+        width: img.width(),
+        height: img.height(),
+        base64: encode_png_base64(&img.into())?,
+    })
+}
+
+#[command]
+fn copy_image_to_clipboard(
+    clipboard: State<'_, ClipboardState>,
+    base64_input: String,
+) -> Result<(), AppError> {
+    let img = decode_base64_image(&base64_input)?.to_rgba8();
+    clipboard.write_image(&img)
+}
+
 #[derive(serde::Serialize)]
 struct ImageData {
     width: u32,
@@ -29,41 +159,39 @@ struct ImageData {
     base64: String,
 }
 
-#[derive(serde::Deserialize)]
-struct ColorToRemove {
-    r: u8,
-    g: u8,
-    b: u8,
-    tolerance: u8,
+#[command]
+fn remove_colors(
+    base64_input: String,
+    colors: Vec<ColorToRemove>,
+    despill: Option<bool>,
+) -> Result<String, AppError> {
+    let mut img = decode_base64_image(&base64_input)?.to_rgba8();
+    color_key::remove_colors(&mut img, &colors, despill.unwrap_or(false));
+    encode_png_base64(&img.into())
 }
 
+// Likely background colors from the image edges, with tolerances ready for
+// remove_colors
 #[command]
-fn remove_colors(base64_input: String, colors: Vec<ColorToRemove>) -> Result<String, String> {
-    // Decode base64 (strip data URL prefix if present)
-    let base64_clean = base64_input
-        .strip_prefix("data:image/png;base64,")
-        .unwrap_or(&base64_input);
-
-    let bytes = STANDARD.decode(base64_clean).map_err(|e| e.to_string())?;
-    let mut img = image::load_from_memory(&bytes).map_err(|e| e.to_string())?.to_rgba8();
-
-    for (_, _, pixel) in img.enumerate_pixels_mut() {
-        for color in &colors {
-            let dr = (pixel[0] as i32 - color.r as i32).abs();
-            let dg = (pixel[1] as i32 - color.g as i32).abs();
-            let db = (pixel[2] as i32 - color.b as i32).abs();
-            let distance = ((dr * dr + dg * dg + db * db) as f64).sqrt();
-
-            if distance <= color.tolerance as f64 * 4.42 {  // Scale 0-100 to ~0-442 (max RGB distance)
-                pixel[3] = 0; // Set alpha to transparent
-                break;
-            }
-        }
-    }
+fn suggest_background_colors(
+    base64_input: String,
+    options: Option<BackgroundOptions>,
+) -> Result<Vec<BackgroundSuggestion>, AppError> {
+    let img = decode_base64_image(&base64_input)?.to_rgba8();
+    Ok(color_key::suggest_background(&img, &options.unwrap_or_default()))
+}
 
-    let mut buf = Cursor::new(Vec::new());
-    img.write_to(&mut buf, ImageFormat::Png).map_err(|e| e.to_string())?;
-    Ok(format!("data:image/png;base64,{}", STANDARD.encode(buf.get_ref())))
+#[command]
+fn remove_background_floodfill(
+    base64_input: String,
+    x: u32,
+    y: u32,
+    tolerance: u8,
+    contiguous: bool,
+) -> Result<String, AppError> {
+    let mut img = decode_base64_image(&base64_input)?.to_rgba8();
+    color_key::remove_background_floodfill(&mut img, x, y, tolerance, contiguous)?;
+    encode_png_base64(&img.into())
 }
 
 #[derive(serde::Deserialize)]
@@ -77,58 +205,494 @@ struct SplitConfig {
     vertical_lines: Vec<SplitLine>,    // x positions
 }
 
+// Cell rectangles (x, y, w, h) in row-major order; lines already include boundaries
+fn split_rects(config: &SplitConfig) -> Result<Vec<(u32, u32, u32, u32)>, AppError> {
+    let y_points: Vec<u32> = config.horizontal_lines.iter().map(|l| l.position).collect();
+    let x_points: Vec<u32> = config.vertical_lines.iter().map(|l| l.position).collect();
+
+    if y_points.len() < 2 || x_points.len() < 2 {
+        return Err(AppError::invalid(
+            "config",
+            "Split needs at least two lines in each direction",
+        ));
+    }
+
+    let mut rects = Vec::new();
+    for row in y_points.windows(2) {
+        for col in x_points.windows(2) {
+            if row[1] <= row[0] || col[1] <= col[0] {
+                return Err(AppError::invalid(
+                    "config",
+                    "Split lines must be in increasing order",
+                ));
+            }
+            rects.push((col[0], row[0], col[1] - col[0], row[1] - row[0]));
+        }
+    }
+    Ok(rects)
+}
+
 #[command]
-fn split_image(base64_input: String, config: SplitConfig) -> Result<Vec<String>, String> {
-    let base64_clean = base64_input
-        .strip_prefix("data:image/png;base64,")
-        .unwrap_or(&base64_input);
+fn split_image(base64_input: String, config: SplitConfig) -> Result<Vec<String>, AppError> {
+    let img = decode_base64_image(&base64_input)?;
 
-    let bytes = STANDARD.decode(base64_clean).map_err(|e| e.to_string())?;
-    let img = image::load_from_memory(&bytes).map_err(|e| e.to_string())?;
+    split_rects(&config)?
+        .into_iter()
+        .map(|(x, y, w, h)| encode_png_base64(&img.crop_imm(x, y, w, h)))
+        .collect()
+}
 
-    // Use lines directly from config (they already include boundaries)
-    let y_points: Vec<u32> = config.horizontal_lines.iter().map(|l| l.position).collect();
-    let x_points: Vec<u32> = config.vertical_lines.iter().map(|l| l.position).collect();
+#[derive(Clone, serde::Serialize)]
+struct SplitChunk {
+    start_index: usize,
+    total: usize,
+    cells: Vec<String>,
+}
 
-    let mut results = Vec::new();
+// Emits cells as "split-chunk" events instead of returning one huge array,
+// so very large sheets don't hit IPC payload limits. Returns the cell count.
+#[command]
+async fn split_image_stream(
+    app: AppHandle,
+    base64_input: String,
+    config: SplitConfig,
+    chunk_size: usize,
+) -> Result<usize, AppError> {
+    let img = decode_base64_image(&base64_input)?;
+    let rects = split_rects(&config)?;
+    let total = rects.len();
 
-    // Iterate row by row, then column by column (within boundaries)
-    for row in 0..y_points.len() - 1 {
-        for col in 0..x_points.len() - 1 {
-            let x = x_points[col];
-            let y = y_points[row];
-            let w = x_points[col + 1] - x;
-            let h = y_points[row + 1] - y;
+    for (chunk_index, chunk) in rects.chunks(chunk_size.max(1)).enumerate() {
+        let cells = chunk
+            .iter()
+            .map(|&(x, y, w, h)| encode_png_base64(&img.crop_imm(x, y, w, h)))
+            .collect::<Result<Vec<_>, _>>()?;
 
-            let cropped = img.crop_imm(x, y, w, h);
-            let mut buf = Cursor::new(Vec::new());
-            cropped.write_to(&mut buf, ImageFormat::Png).map_err(|e| e.to_string())?;
-            results.push(format!("data:image/png;base64,{}", STANDARD.encode(buf.get_ref())));
-        }
+        app.emit(
+            "split-chunk",
+            SplitChunk {
+                start_index: chunk_index * chunk_size.max(1),
+                total,
+                cells,
+            },
+        )
+        .map_err(AppError::io)?;
     }
 
-    Ok(results)
+    Ok(total)
 }
 
+// Writes cells straight to disk (a fresh temp directory unless one is given)
+// and returns their paths, keeping pixel data out of IPC entirely
 #[command]
-fn save_image(base64_input: String, path: String) -> Result<(), String> {
-    let base64_clean = base64_input
-        .strip_prefix("data:image/png;base64,")
-        .unwrap_or(&base64_input);
+async fn split_image_to_dir(
+    base64_input: String,
+    config: SplitConfig,
+    output_dir: Option<String>,
+) -> Result<Vec<String>, AppError> {
+    let img = decode_base64_image(&base64_input)?;
+    let rects = split_rects(&config)?;
 
-    let bytes = STANDARD.decode(base64_clean).map_err(|e| e.to_string())?;
-    std::fs::write(&path, &bytes).map_err(|e| e.to_string())?;
+    let dir = match output_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let stamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(AppError::io)?
+                .as_millis();
+            std::env::temp_dir().join(format!("texturepack-split-{}", stamp))
+        }
+    };
+    std::fs::create_dir_all(&dir)?;
+
+    let mut paths = Vec::with_capacity(rects.len());
+    for (i, (x, y, w, h)) in rects.into_iter().enumerate() {
+        let path = dir.join(format!("{}.png", i + 1));
+        img.crop_imm(x, y, w, h)
+            .save_with_format(&path, ImageFormat::Png)
+            .map_err(AppError::encode)?;
+        paths.push(path.to_string_lossy().into_owned());
+    }
+
+    Ok(paths)
+}
+
+#[command]
+fn save_image(base64_input: String, path: String) -> Result<(), AppError> {
+    let bytes = STANDARD.decode(strip_data_url(&base64_input))?;
+    std::fs::write(&path, &bytes)?;
     Ok(())
 }
 
+// Write cached sprites to `dir`, named by `name_pattern` (see sprite_export)
+#[command]
+async fn export_sprites(
+    cache: State<'_, ImageCache>,
+    workers: State<'_, WorkerPool>,
+    handles: Vec<u32>,
+    dir: String,
+    name_pattern: String,
+    format: Option<OutputFormat>,
+) -> Result<Vec<ExportedSprite>, AppError> {
+    let sprites = handles.iter().map(|&h| (h, cache.get(h))).collect();
+    workers.install(|| {
+        sprite_export::export_sprites(
+            sprites,
+            Path::new(&dir),
+            &name_pattern,
+            &format.unwrap_or_default(),
+        )
+    })
+}
+
+#[derive(serde::Serialize)]
+#[serde(untagged)]
+enum AtlasResult {
+    Single(Box<AtlasOutput>),
+    Grouped(Vec<GroupedAtlas>),
+}
+
+#[command]
+fn create_atlas(
+    app: AppHandle,
+    builds: State<'_, BuildState>,
+    workers: State<'_, WorkerPool>,
+    sprites: Vec<SpriteInput>,
+    padding: u32,
+    options: Option<AtlasOptions>,
+) -> Result<AtlasResult, AppError> {
+    let started = Instant::now();
+    let result = workers.install(|| {
+        let options = options.unwrap_or_default();
+        let (originals, issues) = if options.lenient {
+            decode_sprites_lenient(&sprites)?
+        } else {
+            (decode_sprites(&sprites)?, Vec::new())
+        };
+
+        // Tagged sprites get one atlas per group, multi-page output one per page;
+        // page profiles and lossless pages may split any build into pages
+        let split = options.multi_page
+            || !options.page_profiles.is_empty()
+            || (options.lossless_page && options.quantize.is_some());
+        if split || originals.iter().any(|s| s.group.is_some()) {
+            let mut groups = pack_atlas_groups(&originals, padding, &options)?;
+            attach_issues(&mut groups, issues);
+            let pages = groups.iter().map(|g| (g.image_name.as_str(), g.atlas.json.as_str()));
+            builds.record_frames(pages)?;
+            builds.record_groups(originals, padding, options);
+            return Ok(AtlasResult::Grouped(groups));
+        }
+
+        let image_name = format!("atlas.{}", options.format.extension());
+        let mut output = pack_atlas(&originals, padding, &options, &image_name)?;
+        output.issues = issues;
+
+        builds.record_frames([(image_name.as_str(), output.json.as_str())])?;
+        builds.record_single(originals, padding, options);
+        Ok(AtlasResult::Single(Box::new(output)))
+    });
+    notifications::job_finished(&app, JobKind::Pack, started, &result, |_| {
+        format!("Packed {} sprites", sprites.len())
+    });
+    result
+}
+
+#[command]
+#[allow(clippy::too_many_arguments)]
+fn create_atlas_variants(
+    app: AppHandle,
+    builds: State<'_, BuildState>,
+    workers: State<'_, WorkerPool>,
+    sprites: Vec<SpriteInput>,
+    padding: u32,
+    options: Option<AtlasOptions>,
+    variants: Vec<AtlasVariant>,
+    share_layout: bool,
+) -> Result<Vec<AtlasVariantOutput>, AppError> {
+    let started = Instant::now();
+    let result = workers.install(|| {
+        let options = options.unwrap_or_default();
+        let (originals, issues) = if options.lenient {
+            decode_sprites_lenient(&sprites)?
+        } else {
+            (decode_sprites(&sprites)?, Vec::new())
+        };
+        let mut outputs =
+            pack_atlas_variants(&originals, padding, &options, &variants, share_layout)?;
+        for output in &mut outputs {
+            output.issues = issues.clone();
+        }
+
+        builds.record_frames(outputs.iter().map(|v| (v.image_name.as_str(), v.json.as_str())))?;
+        builds.record_variants(originals, padding, options, variants, share_layout);
+        Ok(outputs)
+    });
+    notifications::job_finished(&app, JobKind::Pack, started, &result, |outputs| {
+        format!("Packed {} sprites at {} resolutions", sprites.len(), outputs.len())
+    });
+    result
+}
+
+// Glyph atlas plus BMFont (.fnt) description for a TTF / OTF font
+#[command]
+async fn generate_bitmap_font(
+    font_path: String,
+    options: Option<BitmapFontOptions>,
+) -> Result<BitmapFontOutput, AppError> {
+    bitmap_font::generate(Path::new(&font_path), &options.unwrap_or_default())
+}
+
+// Layer tree of a Photoshop file
+#[command]
+async fn inspect_psd(path: String) -> Result<PsdDocument, AppError> {
+    let data = std::fs::read(&path).map_err(|e| AppError::from(e).context(&path))?;
+    psd::inspect(&data).map_err(|e| e.context(&path))
+}
+
+#[derive(serde::Serialize)]
+struct FolderSprite {
+    name: String,
+    group: Option<String>,
+    width: u32,
+    height: u32,
+    base64: String,
+}
+
+// New frame names for the project's sprite list with collisions flagged; the
+// frontend applies them once there are none
+#[command]
+fn rename_frames(
+    names: Vec<String>,
+    rule: RenameRule,
+    selected: Option<Vec<usize>>,
+) -> Result<RenamePreview, AppError> {
+    naming::rename_frames(&names, &rule, selected.as_deref())
+}
+
+// Frame names sorted by name or size; the result is kept as the project's
+// `frameOrder` atlas option
+#[command]
+fn sort_frames(frames: Vec<FrameKey>, by: FrameSort, descending: Option<bool>) -> Vec<String> {
+    frame_order::sort_frames(&frames, by, descending.unwrap_or(false))
+}
+
+// Manual reordering: move the selected frames to position `to`
+#[command]
+fn move_frames(
+    order: Vec<String>,
+    names: Vec<String>,
+    to: usize,
+) -> Result<Vec<String>, AppError> {
+    frame_order::move_frames(&order, &names, to)
+}
+
+// Images of a folder tree as sprites, named (and optionally grouped) by their
+// relative paths
+#[command]
+async fn import_folder(
+    workers: State<'_, WorkerPool>,
+    path: String,
+    options: Option<DirImport>,
+) -> Result<Vec<FolderSprite>, AppError> {
+    let sprites = load_sprite_dir(Path::new(&path), &options.unwrap_or_default())?;
+    workers.install(|| {
+        sprites
+            .into_par_iter()
+            .map(|sprite| {
+                Ok(FolderSprite {
+                    width: sprite.image.width(),
+                    height: sprite.image.height(),
+                    base64: encode_png_base64(&sprite.image)?,
+                    name: sprite.name,
+                    group: sprite.group,
+                })
+            })
+            .collect()
+    })
+}
+
+// Selected layers / groups of a Photoshop file as sprites with their canvas
+// offsets; ids come from `inspect_psd`
+#[command]
+async fn import_psd_layers(path: String, ids: Vec<usize>) -> Result<Vec<PsdSprite>, AppError> {
+    if ids.is_empty() {
+        return Err(AppError::invalid("ids", "No layers selected"));
+    }
+    let data = std::fs::read(&path).map_err(|e| AppError::from(e).context(&path))?;
+    psd::rasterize(&data, &ids).map_err(|e| e.context(&path))
+}
+
+// Signed distance field (or MSDF) of a monochrome sprite's alpha, with
+// `range` pixels of margin on every side, ready to pack like any sprite
 #[command]
-fn create_atlas(sprites: Vec<SpriteInput>, padding: u32) -> Result<AtlasOutput, String> {
-    pack_atlas(sprites, padding)
+async fn generate_sdf(
+    base64_input: String,
+    options: Option<SdfOptions>,
+) -> Result<ImageData, AppError> {
+    let options = options.unwrap_or_default();
+    if !(options.range > 0.0 && options.range.is_finite()) {
+        return Err(AppError::invalid("range", "Range must be greater than 0"));
+    }
+    if !(options.scale > 0.0 && options.scale <= 1.0) {
+        return Err(AppError::invalid("scale", "Scale must be greater than 0 and at most 1"));
+    }
+    let img = decode_base64_image(&base64_input)?.to_rgba8();
+    let field = sdf::sprite_field(&img, &options);
+    Ok(ImageData {
+        width: field.width(),
+        height: field.height(),
+        base64: encode_png_base64(&field.into())?,
+    })
+}
+
+// Regenerate every artifact of the last successful pack into `output_dir`
+#[command]
+async fn re_export(
+    app: AppHandle,
+    builds: State<'_, BuildState>,
+    workers: State<'_, WorkerPool>,
+    output_dir: String,
+) -> Result<Vec<String>, AppError> {
+    let started = Instant::now();
+    let result = workers.install(|| builds.re_export(Path::new(&output_dir)));
+    notifications::job_finished(&app, JobKind::Export, started, &result, |paths| {
+        format!("Wrote {} files", paths.len())
+    });
+    result
+}
+
+// The last build's export measured against `budget` (the build's own when
+// None); re-export and bundle export fail on the same violations
+#[command]
+async fn check_budget(
+    builds: State<'_, BuildState>,
+    budget: Option<Budget>,
+) -> Result<BudgetReport, AppError> {
+    builds.check_budget(budget.as_ref())
+}
+
+// A mini-atlas of only some frames of the last build (e.g. one character's
+// animations), packed with the build's settings unless `options` is given.
+// The last build itself is left as it was.
+#[command]
+async fn repack_subset(
+    builds: State<'_, BuildState>,
+    workers: State<'_, WorkerPool>,
+    frames: Option<Vec<String>>,
+    animations: Option<Vec<String>>,
+    name: Option<String>,
+    padding: Option<u32>,
+    options: Option<AtlasOptions>,
+) -> Result<AtlasOutput, AppError> {
+    let (sprites, build_padding, build_options) =
+        builds.subset(&frames.unwrap_or_default(), &animations.unwrap_or_default())?;
+    let options = options.unwrap_or(build_options);
+    let name = name.unwrap_or_else(|| "atlas_subset".to_string());
+    let image_name = format!("{}.{}", name, options.format.extension());
+    let padding = padding.unwrap_or(build_padding);
+    workers.install(|| pack_atlas(&sprites, padding, &options, &image_name))
+}
+
+// Every page of the last build stacked into one KTX2 array texture, for
+// renderers that batch through array textures, written with its JSON to
+// `output_dir` as <name>.ktx2 and <name>.json
+#[command]
+async fn export_texture_array(
+    app: AppHandle,
+    builds: State<'_, BuildState>,
+    workers: State<'_, WorkerPool>,
+    output_dir: String,
+    name: Option<String>,
+    options: Ktx2Options,
+) -> Result<Vec<String>, AppError> {
+    let started = Instant::now();
+    let name = name.unwrap_or_else(|| "atlas_array".to_string());
+    if name.is_empty() || name.contains(['/', '\\']) || name.contains("..") {
+        return Err(AppError::invalid(
+            "name",
+            format!("\"{}\" is not a valid file name", name),
+        ));
+    }
+    let image_name = format!("{}.ktx2", name);
+    let result = workers.install(|| {
+        let array = builds.texture_array(&image_name, &options)?;
+
+        let output_dir = Path::new(&output_dir);
+        std::fs::create_dir_all(output_dir)?;
+        let image_path = output_dir.join(&image_name);
+        let json_path = image_path.with_extension("json");
+        std::fs::write(&image_path, array.ktx2)?;
+        std::fs::write(&json_path, array.json)?;
+        Ok(vec![
+            image_path.to_string_lossy().into_owned(),
+            json_path.to_string_lossy().into_owned(),
+        ])
+    });
+    notifications::job_finished(&app, JobKind::Export, started, &result, |_| {
+        format!("Wrote {}", image_name)
+    });
+    result
+}
+
+// Which frame of the last packed atlas lies under (x, y), in atlas pixels,
+// for click-to-select in the preview. `image` picks the page of multi-page
+// or variant output.
+#[command]
+fn hit_test_atlas(
+    builds: State<'_, BuildState>,
+    x: f32,
+    y: f32,
+    image: Option<String>,
+) -> Result<Option<FrameHit>, AppError> {
+    builds.hit_test(x, y, image.as_deref())
+}
+
+// The same artifacts plus a manifest, as one ZIP archive at `path`
+#[command]
+async fn export_bundle(
+    app: AppHandle,
+    builds: State<'_, BuildState>,
+    workers: State<'_, WorkerPool>,
+    path: String,
+) -> Result<Vec<String>, AppError> {
+    let started = Instant::now();
+    let result = workers.install(|| builds.export_bundle(Path::new(&path)));
+    notifications::job_finished(&app, JobKind::Export, started, &result, |_| {
+        format!("Saved {}", path)
+    });
+    result
+}
+
+// Estimated download + decode time of the last build in each of `formats`,
+// per network (3G / 4G / WiFi presets unless given)
+#[command]
+async fn load_time_report(
+    builds: State<'_, BuildState>,
+    workers: State<'_, WorkerPool>,
+    formats: Vec<OutputFormat>,
+    networks: Option<Vec<Network>>,
+) -> Result<Vec<FormatLoadTime>, AppError> {
+    if formats.is_empty() {
+        return Err(AppError::invalid("formats", "No formats to compare"));
+    }
+    let networks = networks.unwrap_or_else(load_time::default_networks);
+    workers.install(|| builds.load_time_report(&formats, &networks))
 }
 
+// `precompress` also writes "<path>.gz" / "<path>.br" next to the file
 #[command]
-fn save_file(content: String, path: String) -> Result<(), String> {
-    std::fs::write(&path, &content).map_err(|e| e.to_string())?;
+fn save_file(
+    content: String,
+    path: String,
+    precompress: Option<Vec<Precompression>>,
+) -> Result<(), AppError> {
+    std::fs::write(&path, &content)?;
+    for (name, data) in
+        precompress::precompressed(&path, content.as_bytes(), &precompress.unwrap_or_default())?
+    {
+        std::fs::write(&name, data).map_err(|e| AppError::from(e).context(&name))?;
+    }
     Ok(())
 }
 
@@ -141,96 +705,1469 @@ struct CompressResult {
 }
 
 #[command]
-fn compress_image(base64_input: String, quality: u8, scale: u8) -> Result<CompressResult, String> {
-    let base64_clean = base64_input
-        .strip_prefix("data:image/png;base64,")
-        .unwrap_or(&base64_input);
+#[allow(clippy::too_many_arguments)]
+fn compress_image(
+    base64_input: String,
+    quality: u8,
+    scale: u8,
+    filter: Option<ResizeFilter>,
+    format: Option<OutputFormat>,
+    dithering: Option<Dithering>,
+    max_colors: Option<u32>,
+    denoise: Option<Denoise>,
+) -> Result<CompressResult, AppError> {
+    let img = decode_base64_image(&base64_input)?;
+    let settings = CompressSettings {
+        quality,
+        scale,
+        filter: filter.unwrap_or_default(),
+        format: format.unwrap_or_default(),
+        dithering: dithering.unwrap_or_default(),
+        max_colors: max_colors.unwrap_or(256),
+        denoise,
+    };
+    let compressed = compress::compress(img, &settings)?;
+
+    Ok(CompressResult {
+        base64: to_data_url(&compressed.data, settings.format.mime_type()),
+        width: compressed.width,
+        height: compressed.height,
+        size_bytes: compressed.data.len(),
+    })
+}
 
-    let bytes = STANDARD.decode(base64_clean).map_err(|e| e.to_string())?;
-    let img = image::load_from_memory(&bytes).map_err(|e| e.to_string())?;
-    let (orig_width, orig_height) = img.dimensions();
+// Embedded EXIF, ICC profile, text chunks and density of a PNG / JPEG / WebP
+#[command]
+fn inspect_metadata(source: ImageSource) -> Result<ImageMetadata, AppError> {
+    metadata::inspect(&source.read_bytes()?)
+}
 
-    // Apply scale
-    let new_width = (orig_width as f32 * scale as f32 / 100.0).round() as u32;
-    let new_height = (orig_height as f32 * scale as f32 / 100.0).round() as u32;
+#[derive(serde::Serialize)]
+struct StrippedImage {
+    // Data URL, or the written path when `output_path` is given
+    output: String,
+    original_size: usize,
+    stripped_size: usize,
+}
 
-    let resized = if scale < 100 {
-        img.resize_exact(new_width, new_height, FilterType::Lanczos3)
-    } else {
-        img
+// Remove metadata without re-encoding the pixels
+#[command]
+fn strip_metadata(
+    source: ImageSource,
+    keep_color_profile: Option<bool>,
+    output_path: Option<String>,
+) -> Result<StrippedImage, AppError> {
+    let bytes = source.read_bytes()?;
+    let stripped = metadata::strip(&bytes, keep_color_profile.unwrap_or(false))?;
+    let output = match output_path {
+        Some(path) => {
+            std::fs::write(&path, &stripped)?;
+            path
+        }
+        None => {
+            let format = image::guess_format(&stripped).map_err(AppError::decode)?;
+            to_data_url(&stripped, format.to_mime_type())
+        }
     };
 
-    let rgba = resized.to_rgba8();
-    let (width, height) = rgba.dimensions();
+    Ok(StrippedImage {
+        output,
+        original_size: bytes.len(),
+        stripped_size: stripped.len(),
+    })
+}
 
-    // Convert to RGBA pixels for imagequant
-    let pixels: Vec<imagequant::RGBA> = rgba
-        .pixels()
-        .map(|p| imagequant::RGBA::new(p[0], p[1], p[2], p[3]))
-        .collect();
+#[derive(serde::Serialize)]
+struct DiffMetrics {
+    psnr: f64,
+    ssim: f64,
+    max_delta: u8,
+    differing_pixels: u64,
+}
 
-    // Use imagequant for color quantization (lossy PNG compression)
-    let mut liq = imagequant::new();
-    liq.set_quality(0, quality).map_err(|e| e.to_string())?;
+// How far an edited or compressed image is from the original
+#[command]
+fn image_diff_metrics(
+    workers: State<'_, WorkerPool>,
+    a: String,
+    b: String,
+) -> Result<DiffMetrics, AppError> {
+    let a = decode_base64_image(&a)?.to_rgba8();
+    let b = decode_base64_image(&b)?.to_rgba8();
+    if a.dimensions() != b.dimensions() {
+        return Err(AppError::invalid(
+            "b",
+            format!(
+                "Image sizes differ: {}x{} vs {}x{}",
+                a.width(),
+                a.height(),
+                b.width(),
+                b.height()
+            ),
+        ));
+    }
+
+    workers.install(|| {
+        let (max_delta, differing_pixels) = metrics::max_delta(&a, &b);
+        Ok(DiffMetrics {
+            psnr: metrics::psnr(&a, &b),
+            ssim: metrics::ssim(&a, &b),
+            max_delta,
+            differing_pixels,
+        })
+    })
+}
 
-    let mut img_liq = liq.new_image(
-        pixels,
-        width as usize,
-        height as usize,
-        0.0
-    ).map_err(|e| e.to_string())?;
+// The same image under several settings (quality / scale / format), each
+// with its size and PSNR / SSIM, for picking the best trade-off
+#[command]
+async fn compress_preview_matrix(
+    workers: State<'_, WorkerPool>,
+    base64_input: String,
+    variants: Vec<CompressSettings>,
+) -> Result<Vec<PreviewVariant>, AppError> {
+    if variants.is_empty() {
+        return Err(AppError::invalid("variants", "No settings to compare"));
+    }
+    let img = decode_base64_image(&base64_input)?;
+    Ok(workers.install(|| compress::preview_matrix(&img, &variants)))
+}
 
-    let mut res = liq.quantize(&mut img_liq).map_err(|e| e.to_string())?;
-    res.set_dithering_level(1.0).map_err(|e| e.to_string())?;
+// Compresses many images on the rayon pool, emitting "compress-progress" as
+// each one finishes. Outputs are written to `output_dir` when given. Sources
+// listed in `lossless` (by index) are kept free of lossy steps.
+#[command]
+async fn compress_images_batch(
+    app: AppHandle,
+    workers: State<'_, WorkerPool>,
+    sources: Vec<ImageSource>,
+    settings: CompressSettings,
+    output_dir: Option<String>,
+    lossless: Option<Vec<usize>>,
+) -> Result<BatchReport, AppError> {
+    let started = Instant::now();
+    let lossless: HashSet<usize> = lossless.unwrap_or_default().into_iter().collect();
+    if let Some(&index) = lossless.iter().find(|&&i| i >= sources.len()) {
+        return Err(AppError::invalid(
+            "lossless",
+            format!("Source index {} is out of range ({} sources)", index, sources.len()),
+        ));
+    }
+    let output_dir = output_dir.map(PathBuf::from);
+    if let Some(dir) = &output_dir {
+        std::fs::create_dir_all(dir)?;
+    }
 
-    let (palette, indexed_pixels) = res.remapped(&mut img_liq).map_err(|e| e.to_string())?;
+    let report = workers.install(|| {
+        compress::compress_batch(
+            &sources,
+            &settings,
+            &lossless,
+            output_dir.as_deref(),
+            |progress| {
+                let _ = app.emit("compress-progress", progress);
+            },
+        )
+    });
+    // Per-image failures are in the report; the batch itself succeeded
+    let result = Ok(report);
+    notifications::job_finished(&app, JobKind::BatchCompress, started, &result, |report| {
+        let done = report.items.len() - report.failed;
+        match report.failed {
+            0 => format!("Compressed {} images", done),
+            failed => format!("Compressed {} images, {} failed", done, failed),
+        }
+    });
+    result
+}
 
-    // Encode with lodepng
-    let mut encoder = lodepng::Encoder::new();
-    encoder.set_auto_convert(false);
-    encoder.info_raw_mut().colortype = lodepng::ColorType::PALETTE;
-    encoder.info_raw_mut().set_bitdepth(8);
-    encoder.info_png_mut().color.colortype = lodepng::ColorType::PALETTE;
-    encoder.info_png_mut().color.set_bitdepth(8);
+#[derive(serde::Serialize)]
+struct SharedPaletteResult {
+    sprites: Vec<String>,
+    palette: Vec<PaletteColor>,
+}
 
-    for color in &palette {
-        encoder.info_raw_mut().palette_add(lodepng::RGBA { r: color.r, g: color.g, b: color.b, a: color.a })
-            .map_err(|e| e.to_string())?;
-        encoder.info_png_mut().color.palette_add(lodepng::RGBA { r: color.r, g: color.g, b: color.b, a: color.a })
-            .map_err(|e| e.to_string())?;
+#[command]
+fn quantize_sprites_shared(
+    sprites: Vec<String>,
+    quality: u8,
+) -> Result<SharedPaletteResult, AppError> {
+    let images = sprites
+        .iter()
+        .map(|s| decode_base64_image(s).map(|img| img.to_rgba8()))
+        .collect::<Result<Vec<_>, _>>()?;
+    if images.is_empty() {
+        return Err(AppError::invalid("sprites", "No images to quantize"));
     }
 
-    let png_data = encoder.encode(&indexed_pixels, width as usize, height as usize)
-        .map_err(|e| e.to_string())?;
+    let (palette, indices) = quantize::quantize_shared(&images, quality)?;
 
-    let size_bytes = png_data.len();
-    let base64_output = format!("data:image/png;base64,{}", STANDARD.encode(&png_data));
+    let sprites = images
+        .iter()
+        .zip(&indices)
+        .map(|(img, idx)| {
+            let png_data = quantize::encode_indexed_png(&palette, idx, img.width(), img.height(), 8)?;
+            Ok(to_data_url(&png_data, "image/png"))
+        })
+        .collect::<Result<Vec<_>, AppError>>()?;
 
-    Ok(CompressResult {
-        base64: base64_output,
+    Ok(SharedPaletteResult {
+        sprites,
+        palette: palette.into_iter().map(PaletteColor::from).collect(),
+    })
+}
+
+#[command]
+fn add_outline(
+    base64_input: String,
+    color: Color,
+    thickness: u32,
+    mode: OutlineMode,
+) -> Result<EffectOutput, AppError> {
+    let img = decode_base64_image(&base64_input)?.to_rgba8();
+    let (output, margin) = effects::add_outline(&img, color, thickness, mode);
+
+    Ok(EffectOutput {
+        width: output.width(),
+        height: output.height(),
+        base64: encode_png_base64(&output.into())?,
+        offset_x: margin as i32,
+        offset_y: margin as i32,
+    })
+}
+
+#[command]
+fn add_drop_shadow(base64_input: String, config: ShadowConfig) -> Result<EffectOutput, AppError> {
+    let img = decode_base64_image(&base64_input)?.to_rgba8();
+    let (output, left, top) = effects::add_drop_shadow(&img, &config);
+
+    Ok(EffectOutput {
+        width: output.width(),
+        height: output.height(),
+        base64: encode_png_base64(&output.into())?,
+        offset_x: left as i32,
+        offset_y: top as i32,
+    })
+}
+
+#[command]
+fn resize_canvas(
+    base64_input: String,
+    width: u32,
+    height: u32,
+    anchor: Anchor,
+    fill_color: Option<Color>,
+) -> Result<EffectOutput, AppError> {
+    if width == 0 || height == 0 {
+        return Err(AppError::invalid("width", "Canvas size must be at least 1x1"));
+    }
+
+    let img = decode_base64_image(&base64_input)?.to_rgba8();
+    let (output, x, y) = transform::resize_canvas(&img, width, height, anchor, fill_color);
+
+    Ok(EffectOutput {
         width,
         height,
-        size_bytes,
+        base64: encode_png_base64(&output.into())?,
+        offset_x: x as i32,
+        offset_y: y as i32,
     })
 }
 
+// Applies the transforms in order, e.g. [flip-horizontal, rotate90]
 #[command]
-fn get_image_size(base64_input: String) -> Result<usize, String> {
-    let base64_clean = base64_input
-        .strip_prefix("data:image/png;base64,")
-        .unwrap_or(&base64_input);
-    let bytes = STANDARD.decode(base64_clean).map_err(|e| e.to_string())?;
-    Ok(bytes.len())
+fn transform_image(
+    base64_input: String,
+    transforms: Vec<ImageTransform>,
+) -> Result<String, AppError> {
+    let mut img = decode_base64_image(&base64_input)?.to_rgba8();
+    for transform in transforms {
+        img = transform::apply_transform(&img, transform);
+    }
+    encode_png_base64(&img.into())
 }
 
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    tauri::Builder::default()
-        .plugin(tauri_plugin_opener::init())
-        .plugin(tauri_plugin_dialog::init())
+#[command]
+fn rotate_image(
+    base64_input: String,
+    degrees: f32,
+    interpolation: Interpolation,
+) -> Result<EffectOutput, AppError> {
+    let img = decode_base64_image(&base64_input)?.to_rgba8();
+    let (output, x, y) = transform::rotate_arbitrary(&img, degrees, interpolation);
+
+    Ok(EffectOutput {
+        width: output.width(),
+        height: output.height(),
+        base64: encode_png_base64(&output.into())?,
+        offset_x: x as i32,
+        offset_y: y as i32,
+    })
+}
+
+#[command]
+fn clean_alpha(
+    base64_input: String,
+    low: u8,
+    high: u8,
+    min_neighbors: u8,
+) -> Result<String, AppError> {
+    if low > high {
+        return Err(AppError::invalid(
+            "low",
+            "Low alpha threshold must not exceed the high threshold",
+        ));
+    }
+
+    let mut img = decode_base64_image(&base64_input)?.to_rgba8();
+    alpha::clean_alpha(&mut img, low, high, min_neighbors);
+    encode_png_base64(&img.into())
+}
+
+#[command]
+fn premultiply_alpha(base64_input: String) -> Result<String, AppError> {
+    let mut img = decode_base64_image(&base64_input)?.to_rgba8();
+    alpha::premultiply(&mut img);
+    encode_png_base64(&img.into())
+}
+
+#[command]
+fn unpremultiply_alpha(base64_input: String) -> Result<String, AppError> {
+    let mut img = decode_base64_image(&base64_input)?.to_rgba8();
+    alpha::unpremultiply(&mut img);
+    encode_png_base64(&img.into())
+}
+
+#[command]
+fn bleed_colors(base64_input: String, radius: u32) -> Result<String, AppError> {
+    let mut img = decode_base64_image(&base64_input)?.to_rgba8();
+    alpha::bleed_colors(&mut img, radius);
+    encode_png_base64(&img.into())
+}
+
+#[command]
+fn resize_image(
+    base64_input: String,
+    width: u32,
+    height: u32,
+    filter: ResizeFilter,
+) -> Result<String, AppError> {
+    if width == 0 || height == 0 {
+        return Err(AppError::invalid("width", "Target size must be at least 1x1"));
+    }
+
+    let img = decode_base64_image(&base64_input)?;
+    encode_png_base64(&img.resize_exact(width, height, filter.into()))
+}
+
+#[command]
+fn scale_pixel_art(
+    base64_input: String,
+    factor: u32,
+    algorithm: PixelArtAlgorithm,
+) -> Result<String, AppError> {
+    let img = decode_base64_image(&base64_input)?.to_rgba8();
+    let scaled = transform::scale_pixel_art(&img, factor, algorithm)?;
+    encode_png_base64(&scaled.into())
+}
+
+#[derive(serde::Serialize)]
+struct OptimizeResult {
+    base64: String,
+    original_size: usize,
+    optimized_size: usize,
+}
+
+#[command]
+fn optimize_png(
+    base64_input: String,
+    level: Option<u8>,
+    zopfli: bool,
+    interlace: Option<bool>,
+    strip: Option<PngStrip>,
+) -> Result<OptimizeResult, AppError> {
+    let bytes = STANDARD.decode(strip_data_url(&base64_input))?;
+    let optimized = encode::optimize_png(
+        &bytes,
+        level.unwrap_or(4),
+        zopfli,
+        interlace,
+        strip.unwrap_or_default(),
+    )?;
+
+    Ok(OptimizeResult {
+        base64: to_data_url(&optimized, "image/png"),
+        original_size: bytes.len(),
+        optimized_size: optimized.len(),
+    })
+}
+
+#[derive(serde::Serialize)]
+struct EncodedTexture {
+    base64: String,
+    size_bytes: usize,
+}
+
+// GPU-compressed KTX2 (Basis Universal ETC1S / UASTC) for a packed atlas
+#[command]
+fn export_ktx2(base64_input: String, options: Ktx2Options) -> Result<EncodedTexture, AppError> {
+    let img = decode_base64_image(&base64_input)?.to_rgba8();
+    let data = ktx2::encode_ktx2(&[img], &options, false)?;
+
+    Ok(EncodedTexture {
+        base64: to_data_url(&data, "image/ktx2"),
+        size_bytes: data.len(),
+    })
+}
+
+#[command]
+fn export_dds(base64_input: String, options: DdsOptions) -> Result<EncodedTexture, AppError> {
+    let img = decode_base64_image(&base64_input)?.to_rgba8();
+    let data = dds::encode_dds(&img, &options)?;
+
+    Ok(EncodedTexture {
+        base64: to_data_url(&data, "image/vnd-ms.dds"),
+        size_bytes: data.len(),
+    })
+}
+
+#[derive(serde::Serialize)]
+struct MipLevel {
+    base64: String,
+    width: u32,
+    height: u32,
+    // Fraction of texels passing the alpha test at `alpha_cutoff`
+    coverage: f32,
+}
+
+#[command]
+fn generate_mipmaps(
+    base64_input: String,
+    options: MipmapOptions,
+) -> Result<Vec<MipLevel>, AppError> {
+    let img = decode_base64_image(&base64_input)?.to_rgba8();
+    mipmap::generate_mipmaps(&img, &options)
+        .into_iter()
+        .map(|level| {
+            let (width, height) = level.dimensions();
+            let coverage = mipmap::alpha_coverage(&level, options.alpha_cutoff);
+            Ok(MipLevel {
+                base64: encode_png_base64(&level.into())?,
+                width,
+                height,
+                coverage,
+            })
+        })
+        .collect()
+}
+
+#[derive(serde::Deserialize)]
+#[serde(tag = "container", rename_all = "lowercase")]
+enum MipmapContainer {
+    // Uncompressed RGBA8 levels
+    Ktx2 {
+        #[serde(default = "default_true")]
+        srgb: bool,
+    },
+    Dds {
+        format: BcFormat,
+        #[serde(default = "default_true")]
+        srgb: bool,
+    },
+}
+
+fn default_true() -> bool {
+    true
+}
+
+// Mip chain embedded in a single KTX2 or DDS file
+#[command]
+fn export_mipmaps(
+    base64_input: String,
+    options: MipmapOptions,
+    container: MipmapContainer,
+) -> Result<EncodedTexture, AppError> {
+    let img = decode_base64_image(&base64_input)?.to_rgba8();
+    let levels = mipmap::generate_mipmaps(&img, &options);
+
+    let (data, mime) = match container {
+        MipmapContainer::Ktx2 { srgb } => (ktx2::encode_rgba8_ktx2(&levels, srgb)?, "image/ktx2"),
+        MipmapContainer::Dds { format, srgb } => (
+            dds::encode_dds_levels(&levels, format, srgb)?,
+            "image/vnd-ms.dds",
+        ),
+    };
+
+    Ok(EncodedTexture {
+        base64: to_data_url(&data, mime),
+        size_bytes: data.len(),
+    })
+}
+
+#[command]
+fn save_project(
+    state: State<'_, SessionState>,
+    path: String,
+    project: serde_json::Value,
+) -> Result<(), AppError> {
+    let json = serde_json::to_string_pretty(&project).map_err(AppError::encode)?;
+    std::fs::write(&path, json)?;
+    state.touch_recent(&path)?;
+    state.discard()
+}
+
+#[command]
+fn open_project(
+    state: State<'_, SessionState>,
+    path: String,
+) -> Result<serde_json::Value, AppError> {
+    let json = std::fs::read_to_string(&path)?;
+    let project = serde_json::from_str(&json).map_err(AppError::decode)?;
+    state.touch_recent(&path)?;
+    Ok(project)
+}
+
+#[command]
+fn list_recent_projects(state: State<'_, SessionState>) -> Vec<RecentProject> {
+    state.recent_projects()
+}
+
+#[command]
+fn clear_recent_projects(state: State<'_, SessionState>) -> Result<(), AppError> {
+    state.clear_recent()
+}
+
+// The frontend reports its current unsaved state here on every change;
+// a background thread writes the latest one every AUTOSAVE_INTERVAL
+#[command]
+fn autosave_session(
+    state: State<'_, SessionState>,
+    project_path: Option<String>,
+    snapshot: serde_json::Value,
+) {
+    state.stage_snapshot(project_path, snapshot);
+}
+
+#[command]
+fn recover_session(state: State<'_, SessionState>) -> Result<Option<SessionSnapshot>, AppError> {
+    state.recover()
+}
+
+#[command]
+fn discard_session(state: State<'_, SessionState>) -> Result<(), AppError> {
+    state.discard()
+}
+
+// Re-packs whenever images in the watched folders change, emitting
+// "watch-repacked" (or "watch-error") after every build
+#[command]
+fn start_watch(
+    app: AppHandle,
+    state: State<'_, WatchState>,
+    workers: State<'_, WorkerPool>,
+    config: WatchConfig,
+) -> Result<(), AppError> {
+    watch::start(app, &state, workers.pool(), config)
+}
+
+#[command]
+fn stop_watch(state: State<'_, WatchState>) {
+    watch::stop(&state);
+}
+
+#[command]
+fn list_presets(presets: State<'_, PresetStore>) -> Vec<ExportPreset> {
+    presets.list()
+}
+
+#[command]
+fn load_preset(presets: State<'_, PresetStore>, name: String) -> Result<ExportPreset, AppError> {
+    presets.get(&name)
+}
+
+#[command]
+fn save_preset(
+    presets: State<'_, PresetStore>,
+    name: String,
+    config: serde_json::Value,
+) -> Result<(), AppError> {
+    presets.save(&name, config)
+}
+
+#[command]
+fn delete_preset(presets: State<'_, PresetStore>, name: String) -> Result<(), AppError> {
+    presets.delete(&name)
+}
+
+#[command]
+fn get_worker_threads(workers: State<'_, WorkerPool>) -> WorkerStatus {
+    workers.status()
+}
+
+// Caps the threads packing and batch commands use (None = one per core);
+// saved in the app config so it applies on the next launch too
+#[command]
+fn set_worker_threads(
+    workers: State<'_, WorkerPool>,
+    threads: Option<usize>,
+) -> Result<WorkerStatus, AppError> {
+    workers.set_threads(threads)
+}
+
+#[command]
+fn get_notification_settings(notifier: State<'_, Notifier>) -> NotificationSettings {
+    notifier.settings()
+}
+
+// Which finished jobs raise a system notification; saved in the app config
+#[command]
+fn set_notification_settings(
+    notifier: State<'_, Notifier>,
+    settings: NotificationSettings,
+) -> Result<(), AppError> {
+    notifier.set(settings)
+}
+
+#[derive(serde::Serialize)]
+struct FilmstripOutput {
+    base64: String,
+    width: u32,
+    height: u32,
+    frame_width: u32,
+    frame_height: u32,
+    columns: u32,
+    rows: u32,
+}
+
+// Composite ordered frames into a strip / grid sheet
+#[command]
+fn create_filmstrip(frames: Vec<String>, layout: StripLayout) -> Result<FilmstripOutput, AppError> {
+    let frames = frames
+        .iter()
+        .map(|f| decode_base64_image(f).map(|img| img.to_rgba8()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let strip = animation::build_filmstrip(&frames, &layout)?;
+
+    Ok(FilmstripOutput {
+        width: strip.image.width(),
+        height: strip.image.height(),
+        base64: encode_png_base64(&strip.image.into())?,
+        frame_width: strip.frame_width,
+        frame_height: strip.frame_height,
+        columns: strip.columns,
+        rows: strip.rows,
+    })
+}
+
+#[derive(serde::Deserialize)]
+struct PreviewFrame {
+    base64: String,
+    #[serde(default = "default_frame_delay")]
+    delay_ms: u32,
+}
+
+fn default_frame_delay() -> u32 {
+    100
+}
+
+// Shareable animated GIF / WebP of an animation's frames
+#[command]
+fn export_animation_preview(
+    frames: Vec<PreviewFrame>,
+    options: PreviewOptions,
+) -> Result<EncodedTexture, AppError> {
+    let images = frames
+        .iter()
+        .map(|f| decode_base64_image(&f.base64).map(|img| img.to_rgba8()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let delays: Vec<u32> = frames.iter().map(|f| f.delay_ms).collect();
+    let data = animation::encode_preview(&images, &delays, &options)?;
+
+    let mime = match options.format {
+        PreviewFormat::Gif => "image/gif",
+        PreviewFormat::Webp => "image/webp",
+    };
+    Ok(EncodedTexture {
+        base64: to_data_url(&data, mime),
+        size_bytes: data.len(),
+    })
+}
+
+#[derive(serde::Serialize)]
+struct CompareOutput {
+    base64: String,
+    width: u32,
+    height: u32,
+    changed_pixels: u64,
+    max_delta: u8,
+}
+
+// Onion-skin overlay or difference heatmap of two frames
+#[command]
+fn compare_frames(
+    base_input: String,
+    other_input: String,
+    mode: CompareMode,
+) -> Result<CompareOutput, AppError> {
+    let base = decode_base64_image(&base_input)?.to_rgba8();
+    let other = decode_base64_image(&other_input)?.to_rgba8();
+    let comparison = animation::compare_frames(&base, &other, mode);
+
+    Ok(CompareOutput {
+        width: comparison.image.width(),
+        height: comparison.image.height(),
+        base64: encode_png_base64(&comparison.image.into())?,
+        changed_pixels: comparison.changed_pixels,
+        max_delta: comparison.max_delta,
+    })
+}
+
+#[derive(serde::Serialize)]
+struct SharedCropOutput {
+    frames: Vec<String>,
+    offset_x: u32,
+    offset_y: u32,
+    width: u32,
+    height: u32,
+    source_width: u32,
+    source_height: u32,
+}
+
+// Trim an animation's frames to their union bounding box with one shared offset
+#[command]
+fn crop_frames_shared(
+    frames: Vec<String>,
+    alpha_threshold: Option<u8>,
+) -> Result<SharedCropOutput, AppError> {
+    if frames.is_empty() {
+        return Err(AppError::invalid("frames", "No frames to crop"));
+    }
+    let images = frames
+        .iter()
+        .map(|f| decode_base64_image(f).map(|img| img.to_rgba8()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let crop = animation::crop_shared_bounds(&images, alpha_threshold.unwrap_or(0));
+    let (width, height) = crop.frames[0].dimensions();
+
+    Ok(SharedCropOutput {
+        frames: crop
+            .frames
+            .into_iter()
+            .map(|f| encode_png_base64(&f.into()))
+            .collect::<Result<Vec<_>, _>>()?,
+        offset_x: crop.x,
+        offset_y: crop.y,
+        width,
+        height,
+        source_width: crop.source_width,
+        source_height: crop.source_height,
+    })
+}
+
+// Slice the tilesets of a Tiled .tsx / .tmx file into individual tiles
+#[command]
+fn import_tiled(path: String) -> Result<Vec<ImportedTileset>, AppError> {
+    tileset::import_tiled(Path::new(&path))
+}
+
+#[derive(serde::Deserialize)]
+struct ChannelInput {
+    base64: String,
+    // Which channel of the source to read
+    #[serde(default)]
+    channel: SourceChannel,
+    #[serde(default)]
+    invert: bool,
+}
+
+#[derive(serde::Serialize)]
+struct UnpackedChannels {
+    r: String,
+    g: String,
+    b: String,
+    a: String,
+}
+
+// Pack grayscale masks (e.g. metallic / roughness / AO) into one RGBA texture.
+// Unset channels are filled with 0, except alpha which defaults to opaque.
+#[command]
+fn pack_channels(
+    r: Option<ChannelInput>,
+    g: Option<ChannelInput>,
+    b: Option<ChannelInput>,
+    a: Option<ChannelInput>,
+    fill: Option<[u8; 4]>,
+) -> Result<String, AppError> {
+    let decode = |input: Option<ChannelInput>| -> Result<Option<ChannelSource>, AppError> {
+        input
+            .map(|i| {
+                Ok(ChannelSource {
+                    image: decode_base64_raw(&i.base64)?.to_rgba8(),
+                    channel: i.channel,
+                    invert: i.invert,
+                })
+            })
+            .transpose()
+    };
+    let sources = [decode(r)?, decode(g)?, decode(b)?, decode(a)?];
+    let packed = channels::pack_channels(sources, fill.unwrap_or([0, 0, 0, 255]))?;
+    encode_png_base64(&packed.into())
+}
+
+#[command]
+fn unpack_channels(base64_input: String) -> Result<UnpackedChannels, AppError> {
+    let img = decode_base64_raw(&base64_input)?.to_rgba8();
+    let [r, g, b, a] = channels::unpack_channels(&img);
+    Ok(UnpackedChannels {
+        r: encode_png_base64(&r.into())?,
+        g: encode_png_base64(&g.into())?,
+        b: encode_png_base64(&b.into())?,
+        a: encode_png_base64(&a.into())?,
+    })
+}
+
+#[derive(serde::Serialize)]
+struct CachedImage {
+    handle: u32,
+    width: u32,
+    height: u32,
+}
+
+// Decode once and keep the image on the Rust side for repeated edits
+#[command]
+fn cache_image(
+    cache: State<'_, ImageCache>,
+    base64_input: String,
+) -> Result<CachedImage, AppError> {
+    let img = decode_base64_image(&base64_input)?.to_rgba8();
+    let (width, height) = img.dimensions();
+    Ok(CachedImage {
+        handle: cache.insert(img),
+        width,
+        height,
+    })
+}
+
+// Copy of a cached sprite with optional edits (flip, recolor, scale, ...) and
+// a name derived from the original
+#[command]
+fn duplicate_sprite(
+    cache: State<'_, ImageCache>,
+    handle: u32,
+    name: String,
+    operations: Option<Vec<EditOperation>>,
+    name_pattern: Option<String>,
+) -> Result<DuplicatedSprite, AppError> {
+    let mut copies = duplicate::duplicate(
+        &cache,
+        &[SpriteRef { handle, name }],
+        &operations.unwrap_or_default(),
+        name_pattern.as_deref().unwrap_or(DEFAULT_NAME_PATTERN),
+    )?;
+    Ok(copies.remove(0))
+}
+
+// The same for many sprites at once, e.g. every frame of a walk cycle
+#[command]
+async fn duplicate_sprites(
+    cache: State<'_, ImageCache>,
+    workers: State<'_, WorkerPool>,
+    sprites: Vec<SpriteRef>,
+    operations: Option<Vec<EditOperation>>,
+    name_pattern: Option<String>,
+) -> Result<Vec<DuplicatedSprite>, AppError> {
+    workers.install(|| {
+        duplicate::duplicate(
+            &cache,
+            &sprites,
+            &operations.unwrap_or_default(),
+            name_pattern.as_deref().unwrap_or(DEFAULT_NAME_PATTERN),
+        )
+    })
+}
+
+#[command]
+fn get_cached_image(cache: State<'_, ImageCache>, handle: u32) -> Result<String, AppError> {
+    encode_png_base64(&cache.get(handle)?.into())
+}
+
+// Small preview for sprite lists, cached until the image is edited
+#[command]
+fn get_thumbnail(
+    cache: State<'_, ImageCache>,
+    handle: u32,
+    max_dimension: u32,
+) -> Result<Thumbnail, AppError> {
+    cache.thumbnail(handle, max_dimension)
+}
+
+#[derive(serde::Serialize)]
+struct PixelInfo {
+    r: u8,
+    g: u8,
+    b: u8,
+    a: u8,
+    // #rrggbbaa
+    hex: String,
+    // Degrees, and 0-100 percentages
+    h: f32,
+    s: f32,
+    l: f32,
+    // Split cell under the pixel, when a split config is given
+    cell: Option<CellHit>,
+}
+
+#[derive(serde::Serialize)]
+struct CellHit {
+    index: usize,
+    row: usize,
+    column: usize,
+}
+
+// Eyedropper for a cached image; with the current split config it also
+// reports which cell (frame) the pixel falls in
+#[command]
+fn get_pixel_info(
+    cache: State<'_, ImageCache>,
+    handle: u32,
+    x: u32,
+    y: u32,
+    config: Option<SplitConfig>,
+) -> Result<PixelInfo, AppError> {
+    let [r, g, b, a] = cache.pixel(handle, x, y)?.0;
+    let (h, s, l) = rgb_to_hsl(r, g, b);
+
+    let cell = match &config {
+        Some(config) => {
+            let rects = split_rects(config)?;
+            let columns = config.vertical_lines.len() - 1;
+            rects
+                .into_iter()
+                .position(|(cx, cy, cw, ch)| x >= cx && x < cx + cw && y >= cy && y < cy + ch)
+                .map(|index| CellHit {
+                    index,
+                    row: index / columns,
+                    column: index % columns,
+                })
+        }
+        None => None,
+    };
+
+    Ok(PixelInfo {
+        r,
+        g,
+        b,
+        a,
+        hex: format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a),
+        h: (h * 10.0).round() / 10.0,
+        s: (s * 1000.0).round() / 10.0,
+        l: (l * 1000.0).round() / 10.0,
+        cell,
+    })
+}
+
+#[derive(serde::Serialize)]
+struct RegionOutput {
+    // Source rectangle actually returned after clamping to the image
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    base64: String,
+}
+
+// Largest side of a zoomed region, to keep a runaway zoom from allocating GBs
+const MAX_REGION_OUTPUT: u32 = 8192;
+
+// Just the visible viewport of a large cached image, scaled by `zoom`:
+// nearest neighbour when magnifying so pixels stay crisp, averaged when
+// shrinking
+#[command]
+#[allow(clippy::too_many_arguments)]
+fn get_region(
+    cache: State<'_, ImageCache>,
+    handle: u32,
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+    zoom: f32,
+    checkerboard: Option<Checkerboard>,
+) -> Result<RegionOutput, AppError> {
+    if !(zoom > 0.0 && zoom.is_finite()) {
+        return Err(AppError::invalid("zoom", "Zoom must be greater than 0"));
+    }
+    let (x, y, region) = cache.region(handle, x, y, w, h)?;
+    let (width, height) = region.dimensions();
+    let out_w = ((width as f32 * zoom).round() as u32).max(1);
+    let out_h = ((height as f32 * zoom).round() as u32).max(1);
+    if out_w.max(out_h) > MAX_REGION_OUTPUT {
+        return Err(AppError::invalid(
+            "zoom",
+            format!("Zoomed region would exceed {} pixels per side", MAX_REGION_OUTPUT),
+        ));
+    }
+
+    let mut scaled = if (out_w, out_h) == (width, height) {
+        region
+    } else if zoom > 1.0 {
+        imageops::resize(&region, out_w, out_h, imageops::FilterType::Nearest)
+    } else {
+        imageops::thumbnail(&region, out_w, out_h)
+    };
+    if let Some(board) = &checkerboard {
+        apply_checkerboard(&mut scaled, board);
+    }
+    Ok(RegionOutput {
+        x,
+        y,
+        width,
+        height,
+        base64: encode_png_base64(&scaled.into())?,
+    })
+}
+
+// The cached image with a grid (every `grid_size` pixels) and guides baked in
+#[command]
+async fn render_overlay(
+    cache: State<'_, ImageCache>,
+    handle: u32,
+    grid_size: Option<u32>,
+    guides: Option<Guides>,
+    style: Option<OverlayStyle>,
+    checkerboard: Option<Checkerboard>,
+) -> Result<ImageData, AppError> {
+    let mut img = cache.get(handle)?;
+    if let Some(board) = &checkerboard {
+        apply_checkerboard(&mut img, board);
+    }
+    let img = overlay::render(
+        &img,
+        grid_size,
+        &guides.unwrap_or_default(),
+        &style.unwrap_or_default(),
+    )?;
+    Ok(ImageData {
+        width: img.width(),
+        height: img.height(),
+        base64: encode_png_base64(&img.into())?,
+    })
+}
+
+#[command]
+fn release_image(cache: State<'_, ImageCache>, handle: u32) {
+    cache.remove(handle);
+}
+
+// Apply filters in order to a cached image, updating it in place, and
+// return the result for preview
+#[command]
+fn apply_filter(
+    cache: State<'_, ImageCache>,
+    workers: State<'_, WorkerPool>,
+    handle: u32,
+    filters: Vec<ImageFilter>,
+) -> Result<String, AppError> {
+    let mut img = cache.get(handle)?;
+    workers.install(|| {
+        for filter in filters {
+            filters::apply_filter(&mut img, filter);
+        }
+    });
+    let preview = encode_png_base64(&img.clone().into())?;
+    cache.commit(handle, "filter", img)?;
+    Ok(preview)
+}
+
+#[command]
+fn remove_colors_cached(
+    cache: State<'_, ImageCache>,
+    handle: u32,
+    colors: Vec<ColorToRemove>,
+    despill: Option<bool>,
+) -> Result<String, AppError> {
+    let mut img = cache.get(handle)?;
+    color_key::remove_colors(&mut img, &colors, despill.unwrap_or(false));
+    let preview = encode_png_base64(&img.clone().into())?;
+    cache.commit(handle, "remove-colors", img)?;
+    Ok(preview)
+}
+
+#[command]
+fn crop_cached(
+    cache: State<'_, ImageCache>,
+    handle: u32,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+) -> Result<ImageData, AppError> {
+    let img = cache.get(handle)?;
+    if width == 0
+        || height == 0
+        || x.saturating_add(width) > img.width()
+        || y.saturating_add(height) > img.height()
+    {
+        return Err(AppError::invalid("width", "Crop must be a non-empty area inside the image"));
+    }
+    let cropped = imageops::crop_imm(&img, x, y, width, height).to_image();
+    let base64 = encode_png_base64(&cropped.clone().into())?;
+    cache.commit(handle, "crop", cropped)?;
+    Ok(ImageData {
+        width,
+        height,
+        base64,
+    })
+}
+
+// Undo and redo return the restored image, since a crop changes its size
+#[command]
+fn undo(cache: State<'_, ImageCache>, handle: u32) -> Result<ImageData, AppError> {
+    let img = cache.undo(handle)?;
+    Ok(ImageData {
+        width: img.width(),
+        height: img.height(),
+        base64: encode_png_base64(&img.into())?,
+    })
+}
+
+#[command]
+fn redo(cache: State<'_, ImageCache>, handle: u32) -> Result<ImageData, AppError> {
+    let img = cache.redo(handle)?;
+    Ok(ImageData {
+        width: img.width(),
+        height: img.height(),
+        base64: encode_png_base64(&img.into())?,
+    })
+}
+
+#[command]
+fn get_history(cache: State<'_, ImageCache>, handle: u32) -> Result<History, AppError> {
+    cache.history(handle)
+}
+
+// Start a non-destructive edit stack on a sprite; the source is never modified
+#[command]
+fn create_edit_pipeline(
+    pipelines: State<'_, EditPipelines>,
+    base64_input: String,
+) -> Result<CachedImage, AppError> {
+    let img = decode_base64_image(&base64_input)?.to_rgba8();
+    let (width, height) = img.dimensions();
+    Ok(CachedImage {
+        handle: pipelines.create(img),
+        width,
+        height,
+    })
+}
+
+// Edit pipeline for an image file, optionally on a downscaled working copy
+#[command]
+async fn import_edit_pipeline(
+    pipelines: State<'_, EditPipelines>,
+    path: String,
+    options: Option<ImportOptions>,
+) -> Result<ImportedImage, AppError> {
+    pipelines.import(Path::new(&path), &options.unwrap_or_default())
+}
+
+#[command]
+fn get_edit_operations(
+    pipelines: State<'_, EditPipelines>,
+    handle: u32,
+) -> Result<Vec<EditOperation>, AppError> {
+    pipelines.operations(handle)
+}
+
+#[command]
+fn insert_edit_operation(
+    pipelines: State<'_, EditPipelines>,
+    handle: u32,
+    index: Option<usize>,
+    operation: EditOperation,
+) -> Result<Vec<EditOperation>, AppError> {
+    pipelines.insert(handle, index, operation)
+}
+
+#[command]
+fn update_edit_operation(
+    pipelines: State<'_, EditPipelines>,
+    handle: u32,
+    index: usize,
+    operation: EditOperation,
+) -> Result<Vec<EditOperation>, AppError> {
+    pipelines.update(handle, index, operation)
+}
+
+#[command]
+fn remove_edit_operation(
+    pipelines: State<'_, EditPipelines>,
+    handle: u32,
+    index: usize,
+) -> Result<Vec<EditOperation>, AppError> {
+    pipelines.remove(handle, index)
+}
+
+#[command]
+fn move_edit_operation(
+    pipelines: State<'_, EditPipelines>,
+    handle: u32,
+    from: usize,
+    to: usize,
+) -> Result<Vec<EditOperation>, AppError> {
+    pipelines.reorder(handle, from, to)
+}
+
+// Source with every operation applied in order; unchanged steps are reused.
+// `full_resolution` renders from the original file of an imported working copy.
+#[command]
+async fn render_edit_pipeline(
+    pipelines: State<'_, EditPipelines>,
+    handle: u32,
+    checkerboard: Option<Checkerboard>,
+    full_resolution: Option<bool>,
+) -> Result<ImageData, AppError> {
+    let mut img = if full_resolution.unwrap_or(false) {
+        pipelines.render_full(handle)?
+    } else {
+        pipelines.render(handle)?
+    };
+    if let Some(board) = &checkerboard {
+        apply_checkerboard(&mut img, board);
+    }
+    Ok(ImageData {
+        width: img.width(),
+        height: img.height(),
+        base64: encode_png_base64(&img.into())?,
+    })
+}
+
+#[command]
+fn release_edit_pipeline(pipelines: State<'_, EditPipelines>, handle: u32) {
+    pipelines.remove_pipeline(handle);
+}
+
+// Histogram, unique count, dominant colors and alpha breakdown of one sprite
+#[command]
+fn analyze_colors(
+    base64_input: String,
+    options: Option<ColorAnalysisOptions>,
+) -> Result<ColorAnalysis, AppError> {
+    let img = decode_base64_image(&base64_input)?.to_rgba8();
+    Ok(analysis::analyze_colors(&img, &options.unwrap_or_default()))
+}
+
+// Per-sprite color loss if the atlas were quantized with `settings`, worst
+// first, so gradient-heavy sprites can be kept out of indexed output
+#[command]
+async fn quantization_report(
+    workers: State<'_, WorkerPool>,
+    sprites: Vec<SpriteInput>,
+    settings: QuantizeSettings,
+    warn_delta_e: Option<f32>,
+) -> Result<QuantizationReport, AppError> {
+    let decoded = decode_sprites(&sprites)?;
+    workers.install(|| {
+        analysis::quantization_report(
+            &decoded,
+            &settings,
+            warn_delta_e.unwrap_or(DEFAULT_WARN_DELTA_E),
+        )
+    })
+}
+
+// Near-identical sprites across the project, for deduplication before packing
+#[command]
+fn find_duplicate_frames(
+    sprites: Vec<SpriteInput>,
+    options: Option<DuplicateOptions>,
+) -> Result<Vec<DuplicateGroup>, AppError> {
+    let decoded = decode_sprites(&sprites)?;
+    Ok(analysis::find_duplicates(&decoded, &options.unwrap_or_default()))
+}
+
+// Proposed split of the project's sprites across several atlases
+#[command]
+fn suggest_atlas_groups(
+    sprites: Vec<SpriteInput>,
+    options: Option<GroupingOptions>,
+) -> Result<Vec<GroupSuggestion>, AppError> {
+    let decoded = decode_sprites(&sprites)?;
+    let infos: Vec<SpriteInfo> = sprites
+        .iter()
+        .zip(&decoded)
+        .map(|(input, sprite)| SpriteInfo {
+            name: input.name.clone(),
+            width: sprite.image.width(),
+            height: sprite.image.height(),
+            group: input.group.clone(),
+        })
+        .collect();
+    Ok(analysis::suggest_groups(&infos, &options.unwrap_or_default()))
+}
+
+#[derive(serde::Serialize)]
+struct AtlasUpdate {
+    #[serde(flatten)]
+    atlas: AtlasOutput,
+    // Sprites that were added or moved; all others kept their coordinates
+    placed: Vec<String>,
+}
+
+// Apply added / changed / removed sprites to an existing atlas without moving
+// the sprites that stay, reusing freed space for the new ones
+#[command]
+#[allow(clippy::too_many_arguments)]
+fn update_atlas(
+    builds: State<'_, BuildState>,
+    workers: State<'_, WorkerPool>,
+    previous_image: String,
+    previous_json: String,
+    sprites: Vec<SpriteInput>,
+    removed: Vec<String>,
+    padding: u32,
+    options: Option<AtlasOptions>,
+) -> Result<AtlasUpdate, AppError> {
+    workers.install(|| {
+        let mut options = options.unwrap_or_default();
+        let upserts = if sprites.is_empty() { Vec::new() } else { decode_sprites(&sprites)? };
+        let merged = incremental::merge_update(
+            decode_base64_image(&previous_image)?,
+            &previous_json,
+            options.premultiply_alpha,
+            upserts,
+            &removed,
+        )?;
+        options.min_size = merged.previous_size;
+
+        let image_name = format!("atlas.{}", options.format.extension());
+        let atlas = pack_atlas(&merged.sprites, padding, &options, &image_name)?;
+
+        builds.record_frames([(image_name.as_str(), atlas.json.as_str())])?;
+        builds.record_single(merged.sprites, padding, options);
+        Ok(AtlasUpdate {
+            atlas,
+            placed: merged.placed,
+        })
+    })
+}
+
+#[derive(serde::Serialize)]
+struct FrameImages {
+    trimmed: ImageData,
+    untrimmed: ImageData,
+    trim_x: u32,
+    trim_y: u32,
+}
+
+// One frame of a cached atlas image, as stored and at its original size
+#[command]
+fn extract_frame(
+    cache: State<'_, ImageCache>,
+    atlas_handle: u32,
+    atlas_json: String,
+    frame_name: String,
+    premultiplied: Option<bool>,
+) -> Result<FrameImages, AppError> {
+    let atlas = cache.get(atlas_handle)?;
+    let frame = incremental::extract_frame(
+        &atlas,
+        &atlas_json,
+        &frame_name,
+        premultiplied.unwrap_or(false),
+    )?;
+    let image_data = |img: RgbaImage| -> Result<ImageData, AppError> {
+        Ok(ImageData {
+            width: img.width(),
+            height: img.height(),
+            base64: encode_png_base64(&img.into())?,
+        })
+    };
+    Ok(FrameImages {
+        trimmed: image_data(frame.trimmed)?,
+        untrimmed: image_data(frame.untrimmed)?,
+        trim_x: frame.trim_x,
+        trim_y: frame.trim_y,
+    })
+}
+
+#[derive(serde::Serialize)]
+struct OverdrawOutput {
+    sprites: Vec<SpriteOverdraw>,
+    total_transparent_area: u64,
+    heatmap: String,
+}
+
+// Which frames of a packed atlas waste the most fill rate on empty pixels
+#[command]
+fn analyze_overdraw(atlas_image: String, atlas_json: String) -> Result<OverdrawOutput, AppError> {
+    let atlas = decode_base64_image(&atlas_image)?.to_rgba8();
+    let frames = incremental::frame_regions(&atlas_json)?;
+    let report = analysis::overdraw_report(&atlas, &frames);
+
+    Ok(OverdrawOutput {
+        sprites: report.sprites,
+        total_transparent_area: report.total_transparent_area,
+        heatmap: encode_png_base64(&report.heatmap.into())?,
+    })
+}
+
+#[command]
+fn get_image_size(base64_input: String) -> Result<usize, AppError> {
+    let bytes = STANDARD.decode(strip_data_url(&base64_input))?;
+    Ok(bytes.len())
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    tauri::Builder::default()
+        .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_notification::init())
+        .manage(WatchState::default())
+        .manage(BuildState::default())
+        .manage(ImageCache::default())
+        .manage(EditPipelines::default())
+        .manage(ClipboardState::default())
+        .setup(|app| {
+            app.manage(SessionState::load(app.path().app_data_dir()?));
+            app.manage(PresetStore::load(app.path().app_config_dir()?));
+            app.manage(WorkerPool::load(app.path().app_config_dir()?)?);
+            app.manage(Notifier::load(app.path().app_config_dir()?));
+
+            let handle = app.handle().clone();
+            std::thread::spawn(move || loop {
+                std::thread::sleep(session::AUTOSAVE_INTERVAL);
+                let _ = handle.state::<SessionState>().flush();
+            });
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
-            load_image, remove_colors, split_image, save_image, create_atlas, save_file,
-            compress_image, get_image_size
+            load_image, remove_colors, remove_background_floodfill, split_image,
+            split_image_stream, split_image_to_dir, save_image, create_atlas,
+            create_atlas_variants, save_file, compress_image, quantize_sprites_shared,
+            add_outline, add_drop_shadow, resize_canvas, transform_image, rotate_image,
+            clean_alpha, premultiply_alpha, unpremultiply_alpha, bleed_colors, resize_image,
+            scale_pixel_art, optimize_png, export_ktx2, export_dds, generate_mipmaps,
+            export_mipmaps, compress_images_batch, save_project, open_project,
+            list_recent_projects, clear_recent_projects, autosave_session, recover_session,
+            discard_session, start_watch, stop_watch, list_presets, load_preset, save_preset,
+            delete_preset, re_export, create_filmstrip, export_animation_preview,
+            compare_frames, crop_frames_shared, import_tiled, pack_channels, unpack_channels,
+            cache_image, get_cached_image, release_image, apply_filter, find_duplicate_frames,
+            suggest_atlas_groups, update_atlas, analyze_overdraw, remove_colors_cached,
+            crop_cached, undo, redo, get_history, create_edit_pipeline, get_edit_operations,
+            insert_edit_operation, update_edit_operation, remove_edit_operation,
+            move_edit_operation, render_edit_pipeline, release_edit_pipeline, get_thumbnail,
+            get_region, get_pixel_info, analyze_colors, suggest_background_colors,
+            compress_preview_matrix, image_diff_metrics, inspect_metadata, strip_metadata,
+            generate_bitmap_font, generate_sdf, inspect_psd, import_psd_layers,
+            paste_image_from_clipboard, copy_image_to_clipboard, export_sprites, export_bundle,
+            import_folder, render_overlay, import_edit_pipeline, rename_frames, sort_frames,
+            move_frames, duplicate_sprite, duplicate_sprites, quantization_report,
+            load_time_report, hit_test_atlas, extract_frame, repack_subset, check_budget,
+            export_texture_array, get_worker_threads, set_worker_threads,
+            get_notification_settings, set_notification_settings, get_image_size
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
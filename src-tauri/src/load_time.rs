@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+
+use crate::encode::OutputFormat;
+use crate::error::AppError;
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Network {
+    pub name: String,
+    pub downlink_kbps: u32,
+    // Round trip before the first byte arrives
+    pub latency_ms: u32,
+}
+
+// Throttling presets in the spirit of browser dev tools
+pub fn default_networks() -> Vec<Network> {
+    [("3G", 1_600, 300), ("4G", 9_000, 170), ("WiFi", 30_000, 20)]
+        .into_iter()
+        .map(|(name, downlink_kbps, latency_ms)| Network {
+            name: name.to_string(),
+            downlink_kbps,
+            latency_ms,
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkLoadTime {
+    pub name: String,
+    pub download_ms: f64,
+    // Download plus decode
+    pub total_ms: f64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormatLoadTime {
+    // Position in the requested format list
+    pub index: usize,
+    pub extension: &'static str,
+    // Every exported file: images, JSON, companions
+    pub total_bytes: usize,
+    pub image_bytes: usize,
+    pub pixels: u64,
+    pub decode_ms: f64,
+    pub networks: Vec<NetworkLoadTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<AppError>,
+}
+
+// Rough browser decode throughput on a mid-range phone, megapixels per second
+fn decode_rate(format: &OutputFormat) -> f64 {
+    match format {
+        OutputFormat::Png { .. } => 60.0,
+        OutputFormat::Jpeg { .. } => 120.0,
+        OutputFormat::Webp { .. } => 70.0,
+        OutputFormat::Avif { .. } => 25.0,
+    }
+}
+
+pub fn decode_ms(format: &OutputFormat, pixels: u64) -> f64 {
+    pixels as f64 / (decode_rate(format) * 1_000.0)
+}
+
+// Files are requested together, so one round trip plus the transfer
+pub fn download_ms(network: &Network, bytes: usize) -> f64 {
+    network.latency_ms as f64 + bytes as f64 * 8.0 / network.downlink_kbps.max(1) as f64
+}
+
+pub fn estimate(
+    index: usize,
+    format: &OutputFormat,
+    total_bytes: usize,
+    image_bytes: usize,
+    pixels: u64,
+    networks: &[Network],
+) -> FormatLoadTime {
+    let decode_ms = decode_ms(format, pixels);
+    FormatLoadTime {
+        index,
+        extension: format.extension(),
+        total_bytes,
+        image_bytes,
+        pixels,
+        decode_ms,
+        networks: networks
+            .iter()
+            .map(|network| {
+                let download_ms = download_ms(network, total_bytes);
+                NetworkLoadTime {
+                    name: network.name.clone(),
+                    download_ms,
+                    total_ms: download_ms + decode_ms,
+                }
+            })
+            .collect(),
+        error: None,
+    }
+}
@@ -0,0 +1,123 @@
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Region {
+    pub fn right(&self) -> u32 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> u32 {
+        self.y + self.height
+    }
+
+    pub fn intersects(&self, other: &Region) -> bool {
+        self.x < other.right()
+            && other.x < self.right()
+            && self.y < other.bottom()
+            && other.y < self.bottom()
+    }
+
+    fn contains(&self, other: &Region) -> bool {
+        other.x >= self.x
+            && other.y >= self.y
+            && other.right() <= self.right()
+            && other.bottom() <= self.bottom()
+    }
+}
+
+// MaxRects bin: tracks the maximal free rectangles, so areas can be marked as
+// occupied up front (reserved regions, sprites that must not move) before
+// placing the rest
+pub struct MaxRects {
+    free: Vec<Region>,
+}
+
+impl MaxRects {
+    pub fn new(width: u32, height: u32) -> Self {
+        MaxRects {
+            free: vec![Region {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            }],
+        }
+    }
+
+    // Mark `used` as taken, splitting every free rectangle it overlaps
+    pub fn occupy(&mut self, used: Region) {
+        let mut next = Vec::with_capacity(self.free.len() + 4);
+        for f in &self.free {
+            if !f.intersects(&used) {
+                next.push(*f);
+                continue;
+            }
+            if used.x > f.x {
+                next.push(Region {
+                    width: used.x - f.x,
+                    ..*f
+                });
+            }
+            if used.right() < f.right() {
+                next.push(Region {
+                    x: used.right(),
+                    width: f.right() - used.right(),
+                    ..*f
+                });
+            }
+            if used.y > f.y {
+                next.push(Region {
+                    height: used.y - f.y,
+                    ..*f
+                });
+            }
+            if used.bottom() < f.bottom() {
+                next.push(Region {
+                    y: used.bottom(),
+                    height: f.bottom() - used.bottom(),
+                    ..*f
+                });
+            }
+        }
+
+        // Drop rectangles contained in another (keeping one of any duplicates)
+        let mut pruned: Vec<Region> = Vec::with_capacity(next.len());
+        for (i, r) in next.iter().enumerate() {
+            let redundant = next
+                .iter()
+                .enumerate()
+                .any(|(j, other)| i != j && other.contains(r) && (other != r || j < i));
+            if !redundant {
+                pruned.push(*r);
+            }
+        }
+        self.free = pruned;
+    }
+
+    // Best-short-side-fit placement; None when nothing fits
+    pub fn insert(&mut self, width: u32, height: u32) -> Option<Region> {
+        let best = self
+            .free
+            .iter()
+            .filter(|f| f.width >= width && f.height >= height)
+            .min_by_key(|f| {
+                let (dw, dh) = (f.width - width, f.height - height);
+                (dw.min(dh), dw.max(dh))
+            })?;
+        let placed = Region {
+            x: best.x,
+            y: best.y,
+            width,
+            height,
+        };
+        self.occupy(placed);
+        Some(placed)
+    }
+}
@@ -0,0 +1,524 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+use crate::error::AppError;
+use crate::image_io::strip_data_url;
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MetadataKind {
+    Exif,
+    Icc,
+    Xmp,
+    Text,
+    // Color space hints other than a full profile (sRGB intent, gamma, primaries)
+    Color,
+    // Density, timestamps, comments and anything else not needed to decode
+    Other,
+}
+
+#[derive(Serialize)]
+pub struct MetadataEntry {
+    // PNG chunk type, JPEG segment marker (e.g. "APP1") or WebP chunk FourCC
+    pub id: String,
+    pub kind: MetadataKind,
+    pub size: usize,
+    // Keyword and text of PNG text chunks and JPEG comments
+    pub key: Option<String>,
+    pub value: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ImageMetadata {
+    pub format: String,
+    // Horizontal and vertical dots per inch, when the file records a density
+    pub dpi: Option<(f32, f32)>,
+    // Description from the embedded ICC profile
+    pub icc_profile: Option<String>,
+    pub entries: Vec<MetadataEntry>,
+    // Bytes that stripping would remove (with color profile data)
+    pub metadata_size: usize,
+}
+
+fn be16(data: &[u8], at: usize) -> usize {
+    u16::from_be_bytes([data[at], data[at + 1]]) as usize
+}
+
+fn be32(data: &[u8], at: usize) -> usize {
+    u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]) as usize
+}
+
+// be32 for fields a short or corrupt block may not have
+fn checked_be32(data: &[u8], at: usize) -> Option<usize> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?) as usize)
+}
+
+fn le32(data: &[u8], at: usize) -> usize {
+    u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]) as usize
+}
+
+fn truncated(format: &str) -> AppError {
+    AppError::decode(format!("Truncated {} file", format))
+}
+
+// Latin-1 / UTF-8 text up to the first NUL, and the rest after it
+fn split_nul(data: &[u8]) -> (String, &[u8]) {
+    match data.iter().position(|&b| b == 0) {
+        Some(i) => (
+            String::from_utf8_lossy(&data[..i]).into_owned(),
+            &data[i + 1..],
+        ),
+        None => (String::from_utf8_lossy(data).into_owned(), &[]),
+    }
+}
+
+// Profile description from the 'desc' tag: ASCII in v2 profiles, the first
+// UTF-16 record of a 'mluc' in v4
+fn icc_description(profile: &[u8]) -> Option<String> {
+    if profile.len() < 132 {
+        return None;
+    }
+    let tag_count = be32(profile, 128);
+    (0..tag_count)
+        .map(|i| 132 + i * 12)
+        .take_while(|&at| at + 12 <= profile.len())
+        .find(|&at| &profile[at..at + 4] == b"desc")
+        .and_then(|at| {
+            let (offset, size) = (be32(profile, at + 4), be32(profile, at + 8));
+            let tag = profile.get(offset..offset.checked_add(size)?)?;
+            match tag.get(0..4)? {
+                b"desc" => {
+                    let count = checked_be32(tag, 8)?;
+                    let text = tag.get(12..12 + count)?;
+                    Some(split_nul(text).0)
+                }
+                b"mluc" => {
+                    let (length, start) = (checked_be32(tag, 20)?, checked_be32(tag, 24)?);
+                    let units: Vec<u16> = tag
+                        .get(start..start + length)?
+                        .chunks_exact(2)
+                        .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                        .collect();
+                    Some(String::from_utf16_lossy(&units))
+                }
+                _ => None,
+            }
+        })
+        .map(|s| s.trim_end_matches('\0').to_string())
+}
+
+fn entry(id: &str, kind: MetadataKind, size: usize) -> MetadataEntry {
+    MetadataEntry {
+        id: id.to_string(),
+        kind,
+        size,
+        key: None,
+        value: None,
+    }
+}
+
+// Chunk type or marker, offset of the whole chunk, and its length including
+// headers, CRC and padding
+type Chunk<Id> = (Id, usize, usize);
+
+fn png_chunks(data: &[u8]) -> Result<Vec<Chunk<[u8; 4]>>, AppError> {
+    let mut chunks = Vec::new();
+    let mut at = 8;
+    while at + 12 <= data.len() {
+        let length = be32(data, at);
+        let total = length + 12;
+        if at + total > data.len() {
+            return Err(truncated("PNG"));
+        }
+        let id = [data[at + 4], data[at + 5], data[at + 6], data[at + 7]];
+        chunks.push((id, at, total));
+        at += total;
+        if &id == b"IEND" {
+            break;
+        }
+    }
+    Ok(chunks)
+}
+
+fn png_kind(id: &[u8; 4]) -> Option<MetadataKind> {
+    match id {
+        b"IHDR" | b"PLTE" | b"tRNS" | b"IDAT" | b"IEND" | b"acTL" | b"fcTL" | b"fdAT" => None,
+        b"eXIf" => Some(MetadataKind::Exif),
+        b"iCCP" => Some(MetadataKind::Icc),
+        b"tEXt" | b"zTXt" | b"iTXt" => Some(MetadataKind::Text),
+        b"sRGB" | b"gAMA" | b"cHRM" | b"cICP" => Some(MetadataKind::Color),
+        _ => Some(MetadataKind::Other),
+    }
+}
+
+fn inspect_png(data: &[u8]) -> Result<ImageMetadata, AppError> {
+    let mut meta = ImageMetadata {
+        format: "png".to_string(),
+        dpi: None,
+        icc_profile: None,
+        entries: Vec::new(),
+        metadata_size: 0,
+    };
+    for (id, at, total) in png_chunks(data)? {
+        let Some(kind) = png_kind(&id) else { continue };
+        let body = &data[at + 8..at + total - 4];
+        let mut item = entry(&String::from_utf8_lossy(&id), kind, total);
+        match &id {
+            b"tEXt" => {
+                let (key, rest) = split_nul(body);
+                item.key = Some(key);
+                item.value = Some(rest.iter().map(|&b| b as char).collect());
+            }
+            // Compressed text: only the keyword is shown
+            b"zTXt" => item.key = Some(split_nul(body).0),
+            b"iTXt" => {
+                let (key, rest) = split_nul(body);
+                item.key = Some(key.clone());
+                if rest.len() >= 2 && rest[0] == 0 {
+                    let (_, rest) = split_nul(&rest[2..]);
+                    let (_, text) = split_nul(rest);
+                    item.value = Some(String::from_utf8_lossy(text).into_owned());
+                }
+                if key == "XML:com.adobe.xmp" {
+                    item.kind = MetadataKind::Xmp;
+                }
+            }
+            b"pHYs" if body.len() >= 9 && body[8] == 1 => {
+                let per_meter = |at: usize| be32(body, at) as f32 * 0.0254;
+                meta.dpi = Some((per_meter(0), per_meter(4)));
+            }
+            b"iCCP" => {
+                // The profile is zlib-compressed; only the name is stored plainly
+                meta.icc_profile = Some(split_nul(body).0);
+            }
+            _ => {}
+        }
+        meta.metadata_size += total;
+        meta.entries.push(item);
+    }
+    Ok(meta)
+}
+
+// Segments before start-of-scan, and the scan's offset; everything from
+// there on is image data
+fn jpeg_segments(data: &[u8]) -> Result<(Vec<Chunk<u8>>, usize), AppError> {
+    let mut segments = Vec::new();
+    let mut at = 2;
+    loop {
+        if at + 4 > data.len() || data[at] != 0xFF {
+            return Err(truncated("JPEG"));
+        }
+        let marker = data[at + 1];
+        // The length counts its own two bytes
+        let length = be16(data, at + 2);
+        if length < 2 {
+            return Err(truncated("JPEG"));
+        }
+        let total = length + 2;
+        if marker == 0xDA {
+            return Ok((segments, at));
+        }
+        if at + total > data.len() {
+            return Err(truncated("JPEG"));
+        }
+        segments.push((marker, at, total));
+        at += total;
+    }
+}
+
+fn jpeg_kind(marker: u8, body: &[u8]) -> Option<MetadataKind> {
+    match marker {
+        // JFIF's APP0 header is part of the format and only 18 bytes
+        0xE0 if body.starts_with(b"JFIF\0") => None,
+        0xE1 if body.starts_with(b"Exif\0") => Some(MetadataKind::Exif),
+        0xE1 => Some(MetadataKind::Xmp),
+        0xE2 if body.starts_with(b"ICC_PROFILE\0") => Some(MetadataKind::Icc),
+        // APP14 "Adobe" decides how CMYK / YCCK data is decoded, so it stays
+        0xEE => None,
+        0xE0 | 0xE2..=0xEF | 0xFE => Some(MetadataKind::Other),
+        _ => None,
+    }
+}
+
+fn inspect_jpeg(data: &[u8]) -> Result<ImageMetadata, AppError> {
+    let mut meta = ImageMetadata {
+        format: "jpeg".to_string(),
+        dpi: None,
+        icc_profile: None,
+        entries: Vec::new(),
+        metadata_size: 0,
+    };
+    let mut icc = Vec::new();
+    let (segments, _) = jpeg_segments(data)?;
+    for (marker, at, total) in segments {
+        let body = &data[at + 4..at + total];
+        // JFIF density: units 1 = per inch, 2 = per centimetre
+        if marker == 0xE0 && body.starts_with(b"JFIF\0") && body.len() >= 12 {
+            let scale = match body[7] {
+                1 => Some(1.0),
+                2 => Some(2.54),
+                _ => None,
+            };
+            meta.dpi = scale.map(|s| (be16(body, 8) as f32 * s, be16(body, 10) as f32 * s));
+        }
+        let Some(kind) = jpeg_kind(marker, body) else {
+            continue;
+        };
+        let id = match marker {
+            0xFE => "COM".to_string(),
+            _ => format!("APP{}", marker - 0xE0),
+        };
+        let mut item = entry(&id, kind, total);
+        match marker {
+            0xE2 if kind == MetadataKind::Icc && body.len() > 14 => icc.extend(&body[14..]),
+            0xFE => item.value = Some(String::from_utf8_lossy(body).into_owned()),
+            _ => {}
+        }
+        meta.metadata_size += total;
+        meta.entries.push(item);
+    }
+    meta.icc_profile = icc_description(&icc);
+    Ok(meta)
+}
+
+fn webp_chunks(data: &[u8]) -> Result<Vec<Chunk<[u8; 4]>>, AppError> {
+    let mut chunks = Vec::new();
+    let mut at = 12;
+    while at + 8 <= data.len() {
+        let size = le32(data, at + 4);
+        let total = 8 + size + size % 2;
+        if at + 8 + size > data.len() {
+            return Err(truncated("WebP"));
+        }
+        let id = [data[at], data[at + 1], data[at + 2], data[at + 3]];
+        chunks.push((id, at, total.min(data.len() - at)));
+        at += total;
+    }
+    Ok(chunks)
+}
+
+fn webp_kind(id: &[u8; 4]) -> Option<MetadataKind> {
+    match id {
+        b"EXIF" => Some(MetadataKind::Exif),
+        b"ICCP" => Some(MetadataKind::Icc),
+        b"XMP " => Some(MetadataKind::Xmp),
+        _ => None,
+    }
+}
+
+fn inspect_webp(data: &[u8]) -> Result<ImageMetadata, AppError> {
+    let mut meta = ImageMetadata {
+        format: "webp".to_string(),
+        dpi: None,
+        icc_profile: None,
+        entries: Vec::new(),
+        metadata_size: 0,
+    };
+    for (id, at, total) in webp_chunks(data)? {
+        let Some(kind) = webp_kind(&id) else { continue };
+        if kind == MetadataKind::Icc {
+            meta.icc_profile = icc_description(&data[at + 8..at + total]);
+        }
+        meta.metadata_size += total;
+        meta.entries
+            .push(entry(&String::from_utf8_lossy(&id), kind, total));
+    }
+    Ok(meta)
+}
+
+fn is_png(data: &[u8]) -> bool {
+    data.starts_with(b"\x89PNG\r\n\x1a\n")
+}
+
+fn is_jpeg(data: &[u8]) -> bool {
+    data.starts_with(&[0xFF, 0xD8])
+}
+
+fn is_webp(data: &[u8]) -> bool {
+    data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP"
+}
+
+fn unsupported() -> AppError {
+    AppError::decode("Metadata is only read from PNG, JPEG and WebP files")
+}
+
+pub fn inspect(data: &[u8]) -> Result<ImageMetadata, AppError> {
+    if is_png(data) {
+        inspect_png(data)
+    } else if is_jpeg(data) {
+        inspect_jpeg(data)
+    } else if is_webp(data) {
+        inspect_webp(data)
+    } else {
+        Err(unsupported())
+    }
+}
+
+// Drop everything not needed to decode the pixels. With `keep_color_profile`
+// the ICC profile and color space hints stay so colors don't shift.
+pub fn strip(data: &[u8], keep_color_profile: bool) -> Result<Vec<u8>, AppError> {
+    let keep = |kind: Option<MetadataKind>| match kind {
+        None => true,
+        Some(MetadataKind::Icc) | Some(MetadataKind::Color) => keep_color_profile,
+        Some(_) => false,
+    };
+
+    if is_png(data) {
+        let mut out = data[..8].to_vec();
+        for (id, at, total) in png_chunks(data)? {
+            if keep(png_kind(&id)) {
+                out.extend_from_slice(&data[at..at + total]);
+            }
+        }
+        Ok(out)
+    } else if is_jpeg(data) {
+        let (segments, scan) = jpeg_segments(data)?;
+        let mut out = data[..2].to_vec();
+        for (marker, at, total) in segments {
+            if keep(jpeg_kind(marker, &data[at + 4..at + total])) {
+                out.extend_from_slice(&data[at..at + total]);
+            }
+        }
+        out.extend_from_slice(&data[scan..]);
+        Ok(out)
+    } else if is_webp(data) {
+        let mut out = data[..12].to_vec();
+        for (id, at, total) in webp_chunks(data)? {
+            if keep(webp_kind(&id)) {
+                out.extend_from_slice(&data[at..at + total]);
+            }
+        }
+        // VP8X flags advertise ICC (0x20), EXIF (0x08) and XMP (0x04)
+        if out.len() > 20 && &out[12..16] == b"VP8X" {
+            out[20] &= if keep_color_profile { !0x0C } else { !0x2C };
+        }
+        let riff_size = (out.len() - 8) as u32;
+        out[4..8].copy_from_slice(&riff_size.to_le_bytes());
+        Ok(out)
+    } else {
+        Err(unsupported())
+    }
+}
+
+// Encoding gamma of a PNG that declares one with gAMA but has no sRGB or
+// iCCP chunk taking precedence
+pub fn png_gamma(data: &[u8]) -> Option<f32> {
+    if !is_png(data) {
+        return None;
+    }
+    let mut gamma = None;
+    for (id, at, _) in png_chunks(data).ok()? {
+        match &id {
+            b"sRGB" | b"iCCP" => return None,
+            b"gAMA" if be32(data, at) == 4 => gamma = Some(be32(data, at + 8) as f32 / 100000.0),
+            b"IDAT" => break,
+            _ => {}
+        }
+    }
+    gamma.filter(|g| *g > 0.0)
+}
+
+// Color chunks written into exported PNGs. Engines disagree: some apply gamma
+// twice when a profile is present, others need one to show colors right.
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PngColorChunks {
+    // Remove every color chunk (sRGB, gAMA, cHRM, iCCP, cICP) from the file
+    pub omit: bool,
+    // sRGB chunk with this rendering intent: 0 perceptual, 1 relative
+    // colorimetric, 2 saturation, 3 absolute colorimetric
+    pub srgb: Option<u8>,
+    // gAMA chunk with the encoding gamma, e.g. 0.45455 for sRGB
+    pub gamma: Option<f32>,
+    // iCCP chunk holding this ICC profile (base64 or data URL)
+    pub icc_profile: Option<String>,
+    // Profile name stored in the iCCP chunk
+    pub icc_name: Option<String>,
+}
+
+impl PngColorChunks {
+    pub fn is_empty(&self) -> bool {
+        !self.omit && self.srgb.is_none() && self.gamma.is_none() && self.icc_profile.is_none()
+    }
+}
+
+fn png_chunk(id: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut chunk = Vec::with_capacity(body.len() + 12);
+    chunk.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    chunk.extend_from_slice(id);
+    chunk.extend_from_slice(body);
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(&chunk[4..]);
+    chunk.extend_from_slice(&crc.finalize().to_be_bytes());
+    chunk
+}
+
+// The PNG with its color chunks replaced by the requested ones, placed right
+// after IHDR as the spec requires them before PLTE and IDAT
+pub fn set_png_color_chunks(data: &[u8], chunks: &PngColorChunks) -> Result<Vec<u8>, AppError> {
+    if !is_png(data) {
+        return Err(AppError::invalid(
+            "pngColor",
+            "Color chunks can only be set on PNG files",
+        ));
+    }
+    if chunks.srgb.is_some() && chunks.icc_profile.is_some() {
+        return Err(AppError::invalid(
+            "pngColor",
+            "A PNG can't have both an sRGB and an iCCP chunk",
+        ));
+    }
+
+    let mut added = Vec::new();
+    if let Some(intent) = chunks.srgb {
+        if intent > 3 {
+            return Err(AppError::invalid(
+                "pngColor.srgb",
+                "Rendering intent must be 0 to 3",
+            ));
+        }
+        added.push(png_chunk(b"sRGB", &[intent]));
+    }
+    if let Some(gamma) = chunks.gamma {
+        if !(gamma > 0.0 && gamma <= 10.0) {
+            return Err(AppError::invalid(
+                "pngColor.gamma",
+                "Gamma must be between 0 and 10",
+            ));
+        }
+        let value = (gamma * 100000.0).round() as u32;
+        added.push(png_chunk(b"gAMA", &value.to_be_bytes()));
+    }
+    if let Some(profile) = &chunks.icc_profile {
+        let profile = STANDARD.decode(strip_data_url(profile))?;
+        let name = chunks.icc_name.as_deref().unwrap_or("ICC profile");
+        if name.is_empty() || name.len() > 79 || name.contains('\0') {
+            return Err(AppError::invalid(
+                "pngColor.iccName",
+                "Profile name must be 1 to 79 characters",
+            ));
+        }
+        let mut body = name.as_bytes().to_vec();
+        body.extend_from_slice(&[0, 0]); // separator, compression method
+        let mut encoder = ZlibEncoder::new(body, Compression::best());
+        encoder.write_all(&profile)?;
+        added.push(png_chunk(b"iCCP", &encoder.finish()?));
+    }
+
+    let mut out = data[..8].to_vec();
+    for (id, at, total) in png_chunks(data)? {
+        if png_kind(&id) == Some(MetadataKind::Color) || &id == b"iCCP" {
+            continue;
+        }
+        out.extend_from_slice(&data[at..at + total]);
+        if &id == b"IHDR" {
+            for chunk in &added {
+                out.extend_from_slice(chunk);
+            }
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,112 @@
+use image::RgbaImage;
+use rayon::prelude::*;
+
+// Reported for identical images instead of infinity, which JSON can't carry
+pub const MAX_PSNR: f64 = 100.0;
+
+// Window size and step for SSIM; 8x8 blocks overlapping by half
+const SSIM_WINDOW: u32 = 8;
+const SSIM_STEP: u32 = 4;
+
+// Color premultiplied by alpha, plus alpha, so differences hidden under
+// transparent pixels don't count
+fn planes(img: &RgbaImage) -> [Vec<f64>; 4] {
+    let mut planes: [Vec<f64>; 4] = Default::default();
+    for plane in planes.iter_mut() {
+        plane.reserve((img.width() * img.height()) as usize);
+    }
+    for p in img.pixels() {
+        let a = p[3] as f64 / 255.0;
+        for c in 0..3 {
+            planes[c].push(p[c] as f64 * a);
+        }
+        planes[3].push(p[3] as f64);
+    }
+    planes
+}
+
+// Both images must have the same dimensions
+pub fn psnr(a: &RgbaImage, b: &RgbaImage) -> f64 {
+    let (pa, pb) = (planes(a), planes(b));
+    let count = (pa[0].len() * 4).max(1) as f64;
+    let sum: f64 = pa
+        .iter()
+        .zip(&pb)
+        .flat_map(|(x, y)| x.iter().zip(y))
+        .map(|(x, y)| (x - y).powi(2))
+        .sum();
+    let mse = sum / count;
+    if mse == 0.0 {
+        return MAX_PSNR;
+    }
+    (10.0 * (255.0 * 255.0 / mse).log10()).min(MAX_PSNR)
+}
+
+fn ssim_plane(a: &[f64], b: &[f64], width: u32, height: u32) -> f64 {
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+    let window = SSIM_WINDOW.min(width).min(height);
+    let starts = |len: u32| {
+        (0..=len - window)
+            .step_by(SSIM_STEP as usize)
+            .collect::<Vec<_>>()
+    };
+    let (xs, ys) = (starts(width), starts(height));
+
+    let total: f64 = ys
+        .par_iter()
+        .map(|&wy| {
+            let mut row = 0.0;
+            for &wx in &xs {
+                let (mut sa, mut sb, mut saa, mut sbb, mut sab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+                for y in wy..wy + window {
+                    for x in wx..wx + window {
+                        let i = (y * width + x) as usize;
+                        let (va, vb) = (a[i], b[i]);
+                        sa += va;
+                        sb += vb;
+                        saa += va * va;
+                        sbb += vb * vb;
+                        sab += va * vb;
+                    }
+                }
+                let n = (window * window) as f64;
+                let (ma, mb) = (sa / n, sb / n);
+                let var_a = saa / n - ma * ma;
+                let var_b = sbb / n - mb * mb;
+                let cov = sab / n - ma * mb;
+                row += ((2.0 * ma * mb + C1) * (2.0 * cov + C2))
+                    / ((ma * ma + mb * mb + C1) * (var_a + var_b + C2));
+            }
+            row
+        })
+        .sum();
+    total / (xs.len() * ys.len()) as f64
+}
+
+// Mean SSIM over the three premultiplied color planes and alpha; 1.0 means
+// identical. Both images must have the same dimensions.
+pub fn ssim(a: &RgbaImage, b: &RgbaImage) -> f64 {
+    let (width, height) = a.dimensions();
+    if width == 0 || height == 0 {
+        return 1.0;
+    }
+    let (pa, pb) = (planes(a), planes(b));
+    pa.iter()
+        .zip(&pb)
+        .map(|(x, y)| ssim_plane(x, y, width, height))
+        .sum::<f64>()
+        / 4.0
+}
+
+// Largest absolute difference of any channel at any pixel, and how many
+// pixels differ at all
+pub fn max_delta(a: &RgbaImage, b: &RgbaImage) -> (u8, u64) {
+    let (mut max, mut count) = (0u8, 0u64);
+    for (pa, pb) in a.pixels().zip(b.pixels()) {
+        let delta = (0..4).map(|c| pa[c].abs_diff(pb[c])).max().unwrap_or(0);
+        max = max.max(delta);
+        count += (delta > 0) as u64;
+    }
+    (max, count)
+}
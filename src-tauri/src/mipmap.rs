@@ -0,0 +1,101 @@
+use image::imageops::{self, FilterType};
+use image::RgbaImage;
+use serde::Deserialize;
+
+use crate::alpha;
+use crate::transform::ResizeFilter;
+
+// Default alpha-test cutoff when preserving coverage
+const COVERAGE_CUTOFF: u8 = 128;
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct MipmapOptions {
+    #[serde(default = "default_filter")]
+    pub filter: ResizeFilter,
+    // Rescale alpha per level so alpha-tested sprites keep the same coverage
+    #[serde(default)]
+    pub preserve_coverage: bool,
+    // Alpha the renderer's alpha test discards below, matching e.g. a shader's
+    // `if (a < 0.5) discard` (128)
+    #[serde(default = "default_alpha_cutoff")]
+    pub alpha_cutoff: u8,
+}
+
+fn default_filter() -> ResizeFilter {
+    ResizeFilter::Triangle
+}
+
+fn default_alpha_cutoff() -> u8 {
+    COVERAGE_CUTOFF
+}
+
+impl Default for MipmapOptions {
+    fn default() -> Self {
+        MipmapOptions {
+            filter: default_filter(),
+            preserve_coverage: false,
+            alpha_cutoff: COVERAGE_CUTOFF,
+        }
+    }
+}
+
+// Fraction of texels that pass the alpha test when alpha is scaled by `scale`
+fn coverage(img: &RgbaImage, scale: f32, cutoff: u8) -> f32 {
+    let passing = img
+        .pixels()
+        .filter(|p| (p[3] as f32 * scale).min(255.0) >= cutoff as f32)
+        .count();
+    passing as f32 / (img.width() * img.height()) as f32
+}
+
+// Fraction of texels an alpha test at `cutoff` keeps
+pub fn alpha_coverage(img: &RgbaImage, cutoff: u8) -> f32 {
+    coverage(img, 1.0, cutoff)
+}
+
+// Binary search for the alpha scale that matches the target coverage. The
+// range reaches 255 so even faint distant levels can be brought up to a
+// high cutoff.
+fn preserve_coverage(img: &mut RgbaImage, target: f32, cutoff: u8) {
+    if target <= 0.0 {
+        return;
+    }
+
+    let (mut low, mut high) = (0.0f32, 255.0f32);
+    for _ in 0..24 {
+        let mid = (low + high) / 2.0;
+        if coverage(img, mid, cutoff) < target {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+
+    for pixel in img.pixels_mut() {
+        pixel[3] = (pixel[3] as f32 * high).round().min(255.0) as u8;
+    }
+}
+
+// Full mip chain down to 1x1, level 0 first. Each level is filtered from the
+// previous one in premultiplied space so transparent texels don't bleed color.
+pub fn generate_mipmaps(img: &RgbaImage, options: &MipmapOptions) -> Vec<RgbaImage> {
+    let target_coverage = alpha_coverage(img, options.alpha_cutoff);
+    let mut levels = vec![img.clone()];
+    let mut current = img.clone();
+    alpha::premultiply(&mut current);
+
+    while current.width() > 1 || current.height() > 1 {
+        let width = (current.width() / 2).max(1);
+        let height = (current.height() / 2).max(1);
+        current = imageops::resize(&current, width, height, FilterType::from(options.filter));
+
+        let mut level = current.clone();
+        alpha::unpremultiply(&mut level);
+        if options.preserve_coverage {
+            preserve_coverage(&mut level, target_coverage, options.alpha_cutoff);
+        }
+        levels.push(level);
+    }
+
+    levels
+}
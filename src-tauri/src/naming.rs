@@ -0,0 +1,152 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::error::AppError;
+
+// Replace each {key} in `pattern` with `resolve(key)`; {key:3} left-pads the
+// value with zeros to three characters. Errors name `field`.
+pub fn fill_pattern(
+    pattern: &str,
+    field: &str,
+    resolve: impl Fn(&str) -> Option<String>,
+) -> Result<String, AppError> {
+    let mut out = String::new();
+    let mut rest = pattern;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let close = rest[open..]
+            .find('}')
+            .ok_or_else(|| AppError::invalid(field, "Unclosed \"{\" in pattern"))?;
+        let token = &rest[open + 1..open + close];
+        let (key, pad) = match token.split_once(':') {
+            Some((key, pad)) => (
+                key,
+                pad.parse::<usize>().map_err(|_| {
+                    AppError::invalid(field, format!("Invalid padding in {{{}}}", token))
+                })?,
+            ),
+            None => (token, 0),
+        };
+        let value = resolve(key).ok_or_else(|| {
+            AppError::invalid(field, format!("Unknown placeholder {{{}}}", token))
+        })?;
+        out.push_str(&format!("{:0>width$}", value, width = pad));
+        rest = &rest[open + close + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum RenameRule {
+    // New name from placeholders: {name} (current name), {index} (position
+    // among the renamed frames, counting from `start`)
+    Pattern {
+        pattern: String,
+        #[serde(default = "default_start")]
+        start: usize,
+    },
+    // Every match of `regex` replaced; `replacement` may use $1 / ${name}
+    Regex {
+        regex: String,
+        replacement: String,
+    },
+}
+
+fn default_start() -> usize {
+    1
+}
+
+#[derive(Serialize)]
+pub struct FrameRename {
+    pub from: String,
+    pub to: String,
+    // Another frame ends up with the same name
+    pub collides: bool,
+}
+
+#[derive(Serialize)]
+pub struct RenamePreview {
+    // One entry per input name, in input order
+    pub renames: Vec<FrameRename>,
+    // Names that more than one frame would have
+    pub collisions: Vec<String>,
+}
+
+// New name from (position among the renamed frames, current name)
+type Renamer<'a> = Box<dyn Fn(usize, &str) -> Result<String, AppError> + 'a>;
+
+// Resulting names for `names` with the rule applied to the `selected` indices
+// (all when None). Frames left alone still count for collisions, so the
+// preview can be applied only when `collisions` is empty.
+pub fn rename_frames(
+    names: &[String],
+    rule: &RenameRule,
+    selected: Option<&[usize]>,
+) -> Result<RenamePreview, AppError> {
+    let all: Vec<usize> = (0..names.len()).collect();
+    let selected = selected.unwrap_or(&all);
+    if let Some(&index) = selected.iter().find(|&&i| i >= names.len()) {
+        return Err(AppError::invalid(
+            "selected",
+            format!(
+                "Frame index {} is out of range ({} frames)",
+                index,
+                names.len()
+            ),
+        ));
+    }
+
+    let rename: Renamer = match rule {
+        RenameRule::Pattern { pattern, start } => Box::new(move |position, name| {
+            fill_pattern(pattern, "pattern", |key| match key {
+                "name" => Some(name.to_string()),
+                "index" => Some((start + position).to_string()),
+                _ => None,
+            })
+        }),
+        RenameRule::Regex { regex, replacement } => {
+            let regex = Regex::new(regex).map_err(|e| AppError::invalid("regex", e))?;
+            Box::new(move |_, name| Ok(regex.replace_all(name, replacement.as_str()).into_owned()))
+        }
+    };
+    let mut new_names = names.to_vec();
+    for (position, &index) in selected.iter().enumerate() {
+        let name = &names[index];
+        let renamed = rename(position, name)?;
+        if renamed.is_empty() {
+            return Err(AppError::invalid(
+                "replacement",
+                format!("\"{}\" would be renamed to an empty name", name),
+            ));
+        }
+        new_names[index] = renamed;
+    }
+
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for name in &new_names {
+        *counts.entry(name).or_default() += 1;
+    }
+    let mut collisions: Vec<String> = counts
+        .iter()
+        .filter(|(_, &count)| count > 1)
+        .map(|(name, _)| name.to_string())
+        .collect();
+    collisions.sort();
+
+    let renames = names
+        .iter()
+        .zip(&new_names)
+        .map(|(from, to)| FrameRename {
+            from: from.clone(),
+            collides: counts[to.as_str()] > 1,
+            to: to.clone(),
+        })
+        .collect();
+    Ok(RenamePreview {
+        renames,
+        collisions,
+    })
+}
@@ -0,0 +1,60 @@
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::Chars;
+
+fn take_digits(chars: &mut Peekable<Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        digits.push(c);
+    }
+    digits
+}
+
+// Numeric-aware ordering: runs of digits compare by value, so "frame2" comes
+// before "frame10". Names that only differ in zero padding ("walk_01" and
+// "walk_1") fall back to plain string order.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a_chars, mut b_chars) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (a_chars.peek(), b_chars.peek()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (x, y) = (take_digits(&mut a_chars), take_digits(&mut b_chars));
+                let (x, y) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                let ordering = x.len().cmp(&y.len()).then_with(|| x.cmp(y));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.cmp(y);
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a_chars.next();
+                b_chars.next();
+            }
+        }
+    }
+}
+
+// `natural_cmp` per path component, so a folder's files stay together
+pub fn natural_path_cmp(a: &Path, b: &Path) -> Ordering {
+    let (mut a, mut b) = (a.iter(), b.iter());
+    loop {
+        match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => {
+                let ordering = natural_cmp(&x.to_string_lossy(), &y.to_string_lossy());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+        }
+    }
+}
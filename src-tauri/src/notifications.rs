@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Instant;
+use tauri::{AppHandle, Manager, Runtime};
+use tauri_plugin_notification::NotificationExt;
+
+use crate::error::AppError;
+use crate::session::write_json;
+
+#[derive(Clone, Copy)]
+pub enum JobKind {
+    Pack,
+    BatchCompress,
+    Export,
+}
+
+impl JobKind {
+    fn label(&self) -> &'static str {
+        match self {
+            JobKind::Pack => "Atlas packing",
+            JobKind::BatchCompress => "Batch compression",
+            JobKind::Export => "Export",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct JobNotifications {
+    pub success: bool,
+    pub failure: bool,
+}
+
+impl Default for JobNotifications {
+    fn default() -> Self {
+        JobNotifications {
+            success: true,
+            failure: true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NotificationSettings {
+    pub pack: JobNotifications,
+    pub batch_compress: JobNotifications,
+    pub export: JobNotifications,
+    // Jobs that finish sooner never notify
+    pub min_seconds: f64,
+    // Also notify while an app window has focus
+    pub when_focused: bool,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        NotificationSettings {
+            pack: JobNotifications::default(),
+            batch_compress: JobNotifications::default(),
+            export: JobNotifications::default(),
+            min_seconds: 10.0,
+            when_focused: false,
+        }
+    }
+}
+
+impl NotificationSettings {
+    fn validate(&self) -> Result<(), AppError> {
+        if !(self.min_seconds.is_finite() && self.min_seconds >= 0.0) {
+            return Err(AppError::invalid(
+                "minSeconds",
+                "Minimum duration must be 0 or more seconds",
+            ));
+        }
+        Ok(())
+    }
+
+    fn job(&self, job: JobKind) -> JobNotifications {
+        match job {
+            JobKind::Pack => self.pack,
+            JobKind::BatchCompress => self.batch_compress,
+            JobKind::Export => self.export,
+        }
+    }
+}
+
+// Managed state backed by notifications.json in the app config directory
+pub struct Notifier {
+    path: PathBuf,
+    settings: Mutex<NotificationSettings>,
+}
+
+impl Notifier {
+    pub fn load(config_dir: PathBuf) -> Self {
+        let path = config_dir.join("notifications.json");
+        let settings = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|json| serde_json::from_str::<NotificationSettings>(&json).ok())
+            // A hand-edited file with a bad value falls back to the defaults
+            .filter(|settings| settings.validate().is_ok())
+            .unwrap_or_default();
+
+        Notifier {
+            path,
+            settings: Mutex::new(settings),
+        }
+    }
+
+    pub fn settings(&self) -> NotificationSettings {
+        self.settings.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, settings: NotificationSettings) -> Result<(), AppError> {
+        settings.validate()?;
+        write_json(&self.path, &settings)?;
+        *self.settings.lock().unwrap() = settings;
+        Ok(())
+    }
+}
+
+// Raises a system notification for a job that just finished, when that
+// outcome is enabled for its kind, it ran long enough and (unless configured
+// otherwise) no app window has focus. `describe` words a success.
+pub fn job_finished<R: Runtime, T>(
+    app: &AppHandle<R>,
+    job: JobKind,
+    started: Instant,
+    result: &Result<T, AppError>,
+    describe: impl FnOnce(&T) -> String,
+) {
+    let Some(notifier) = app.try_state::<Notifier>() else {
+        return;
+    };
+    let settings = notifier.settings();
+    let enabled = match result {
+        Ok(_) => settings.job(job).success,
+        Err(_) => settings.job(job).failure,
+    };
+    if !enabled || started.elapsed().as_secs_f64() < settings.min_seconds {
+        return;
+    }
+    let focused = app
+        .webview_windows()
+        .values()
+        .any(|window| window.is_focused().unwrap_or(false));
+    if focused && !settings.when_focused {
+        return;
+    }
+
+    let (title, body) = match result {
+        Ok(value) => (format!("{} finished", job.label()), describe(value)),
+        Err(e) => (format!("{} failed", job.label()), e.to_string()),
+    };
+    // The job's own result matters more than whether the OS showed this
+    let _ = app.notification().builder().title(title).body(body).show();
+}
@@ -0,0 +1,168 @@
+use image::{Rgba, RgbaImage};
+use rayon::prelude::*;
+use serde::Deserialize;
+
+use crate::effects::blend_over;
+use crate::error::AppError;
+use crate::image_io::Color;
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Guides {
+    pub horizontal: Vec<u32>, // y positions
+    pub vertical: Vec<u32>,   // x positions
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OverlayStyle {
+    pub grid_color: Color,
+    pub guide_color: Color,
+    // Thickness in image pixels, growing right / down from each position
+    pub line_width: u32,
+}
+
+impl Default for OverlayStyle {
+    fn default() -> Self {
+        OverlayStyle {
+            grid_color: Color {
+                r: 128,
+                g: 128,
+                b: 128,
+                a: 128,
+            },
+            guide_color: Color {
+                r: 0,
+                g: 200,
+                b: 255,
+                a: 255,
+            },
+            line_width: 1,
+        }
+    }
+}
+
+// Background that makes transparency visible in previews
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Checkerboard {
+    // Square size in output pixels
+    pub size: u32,
+    pub light: Color,
+    pub dark: Color,
+}
+
+impl Default for Checkerboard {
+    fn default() -> Self {
+        Checkerboard {
+            size: 8,
+            light: Color {
+                r: 255,
+                g: 255,
+                b: 255,
+                a: 255,
+            },
+            dark: Color {
+                r: 204,
+                g: 204,
+                b: 204,
+                a: 255,
+            },
+        }
+    }
+}
+
+// Composite `img` over the checkerboard in place
+pub fn apply_checkerboard(img: &mut RgbaImage, board: &Checkerboard) {
+    let size = board.size.max(1);
+    let (light, dark) = (board.light.to_rgba(), board.dark.to_rgba());
+    let width = img.width() as usize;
+    img.par_chunks_mut(width * 4)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                let light_square = (x as u32 / size + y as u32 / size).is_multiple_of(2);
+                let background = if light_square { light } else { dark };
+                let Rgba(blended) =
+                    blend_over(Rgba([pixel[0], pixel[1], pixel[2], pixel[3]]), background);
+                pixel.copy_from_slice(&blended);
+            }
+        });
+}
+
+const NONE: u8 = 0;
+const GRID: u8 = 1;
+const GUIDE: u8 = 2;
+
+// What covers each row (or column): guides are drawn over the grid
+fn line_mask(len: u32, grid_size: Option<u32>, guides: &[u32], width: u32) -> Vec<u8> {
+    let mut mask = vec![NONE; len as usize];
+    let mut mark = |start: u32, kind: u8| {
+        let end = start.saturating_add(width).min(len);
+        for slot in &mut mask[start.min(len) as usize..end as usize] {
+            *slot = (*slot).max(kind);
+        }
+    };
+    if let Some(size) = grid_size {
+        for start in (0..len).step_by(size as usize) {
+            mark(start, GRID);
+        }
+    }
+    for &start in guides {
+        mark(start, GUIDE);
+    }
+    mask
+}
+
+// A copy of `img` with a square grid every `grid_size` pixels and the given
+// guides blended in, for previewing sheets too large to draw over on a canvas
+pub fn render(
+    img: &RgbaImage,
+    grid_size: Option<u32>,
+    guides: &Guides,
+    style: &OverlayStyle,
+) -> Result<RgbaImage, AppError> {
+    if grid_size == Some(0) {
+        return Err(AppError::invalid(
+            "grid_size",
+            "Grid size must be at least 1",
+        ));
+    }
+    if style.line_width == 0 {
+        return Err(AppError::invalid(
+            "line_width",
+            "Line width must be at least 1",
+        ));
+    }
+
+    let (width, height) = img.dimensions();
+    let rows = line_mask(height, grid_size, &guides.horizontal, style.line_width);
+    let columns = line_mask(width, grid_size, &guides.vertical, style.line_width);
+    let marked: Vec<usize> = (0..columns.len()).filter(|&x| columns[x] != NONE).collect();
+    let color = |kind: u8| match kind {
+        GUIDE => style.guide_color.to_rgba(),
+        _ => style.grid_color.to_rgba(),
+    };
+
+    let mut out = img.clone();
+    out.par_chunks_mut(width as usize * 4)
+        .zip(rows)
+        .for_each(|(row, row_kind)| {
+            let mut blend = |x: usize, kind: u8| {
+                let pixel = &mut row[x * 4..x * 4 + 4];
+                let Rgba(blended) =
+                    blend_over(color(kind), Rgba([pixel[0], pixel[1], pixel[2], pixel[3]]));
+                pixel.copy_from_slice(&blended);
+            };
+            if row_kind != NONE {
+                for (x, &column_kind) in columns.iter().enumerate() {
+                    blend(x, row_kind.max(column_kind));
+                }
+            } else {
+                for &x in &marked {
+                    blend(x, columns[x]);
+                }
+            }
+        });
+    Ok(out)
+}
@@ -0,0 +1,70 @@
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::atlas_packer::SourceSprite;
+use crate::encode::OutputFormat;
+use crate::quantize::QuantizeSettings;
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SpriteProfile {
+    // No transparency at all, so JPEG loses nothing to alpha
+    Opaque,
+    // Any pixel with alpha below 255
+    Transparent,
+    // Hard-edged alpha and at most 256 colors: an indexed page stores it
+    // without loss
+    PixelArt,
+}
+
+// Pages for sprites matching `profile`, encoded with their own settings
+#[derive(Deserialize, Clone)]
+pub struct PageProfile {
+    pub profile: SpriteProfile,
+    pub format: OutputFormat,
+    #[serde(default)]
+    pub quantize: Option<QuantizeSettings>,
+}
+
+const PALETTE_SIZE: usize = 256;
+
+fn is_opaque(img: &RgbaImage) -> bool {
+    img.pixels().all(|p| p[3] == 255)
+}
+
+fn is_pixel_art(img: &RgbaImage) -> bool {
+    let mut colors = HashSet::new();
+    for p in img.pixels() {
+        match p[3] {
+            0 => {}
+            255 => {
+                if colors.insert(p.0) && colors.len() > PALETTE_SIZE {
+                    return false;
+                }
+            }
+            _ => return false,
+        }
+    }
+    true
+}
+
+fn matches(profile: SpriteProfile, img: &RgbaImage) -> bool {
+    match profile {
+        SpriteProfile::Opaque => is_opaque(img),
+        SpriteProfile::Transparent => !is_opaque(img),
+        SpriteProfile::PixelArt => is_pixel_art(img),
+    }
+}
+
+// Index of the first profile every sprite in `unit` matches. Lossless
+// sprites never move to a profile page, whose encoding may be lossy.
+pub fn assign(sprites: &[SourceSprite], unit: &[usize], profiles: &[PageProfile]) -> Option<usize> {
+    if unit.iter().any(|&i| sprites[i].lossless) {
+        return None;
+    }
+    let images: Vec<RgbaImage> = unit.iter().map(|&i| sprites[i].image.to_rgba8()).collect();
+    profiles
+        .iter()
+        .position(|p| images.iter().all(|img| matches(p.profile, img)))
+}
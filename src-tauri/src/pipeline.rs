@@ -0,0 +1,404 @@
+use image::{imageops, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::alpha::{premultiply, unpremultiply};
+use crate::bit_depth::DepthOptions;
+use crate::color_key::{self, ColorToRemove};
+use crate::error::AppError;
+use crate::filters::{self, ImageFilter};
+use crate::image_io::{hsl_to_rgb, open_image_with, rgb_to_hsl};
+use crate::transform::{self, ImageTransform, ResizeFilter};
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum EditOperation {
+    // Crop to the pixels with alpha above `alpha_threshold`
+    Trim {
+        #[serde(default)]
+        alpha_threshold: u8,
+    },
+    RemoveColors {
+        colors: Vec<ColorToRemove>,
+        #[serde(default)]
+        despill: bool,
+    },
+    // Rotate hue by `hue` degrees, then scale saturation and lightness
+    Recolor {
+        #[serde(default)]
+        hue: f32,
+        #[serde(default = "unchanged")]
+        saturation: f32,
+        #[serde(default = "unchanged")]
+        lightness: f32,
+    },
+    Scale {
+        factor: f32,
+        #[serde(default)]
+        filter: ResizeFilter,
+    },
+    Transform {
+        transform: ImageTransform,
+    },
+    Filter {
+        filter: ImageFilter,
+    },
+}
+
+fn unchanged() -> f32 {
+    1.0
+}
+
+// Largest side of a scaled image, to keep a large factor from allocating GBs
+const MAX_SCALED_SIDE: u32 = 16384;
+
+impl EditOperation {
+    pub fn validate(&self) -> Result<(), AppError> {
+        match self {
+            EditOperation::Scale { factor, .. } if !(*factor > 0.0 && factor.is_finite()) => Err(
+                AppError::invalid("factor", "Scale factor must be greater than 0"),
+            ),
+            EditOperation::Recolor {
+                saturation,
+                lightness,
+                ..
+            } if *saturation < 0.0 || *lightness < 0.0 => Err(AppError::invalid(
+                "saturation",
+                "Saturation and lightness factors must not be negative",
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    // The operation as it applies to an image `factor` times the size of the
+    // one it was set up on; only pixel-sized filter parameters change
+    pub fn scaled(&self, factor: f32) -> EditOperation {
+        match self {
+            EditOperation::Filter { filter } => EditOperation::Filter {
+                filter: filter.scaled(factor),
+            },
+            other => other.clone(),
+        }
+    }
+
+    pub fn apply(&self, img: &RgbaImage) -> Result<RgbaImage, AppError> {
+        Ok(match self {
+            EditOperation::Trim { alpha_threshold } => trim(img, *alpha_threshold),
+            EditOperation::RemoveColors { colors, despill } => {
+                let mut out = img.clone();
+                color_key::remove_colors(&mut out, colors, *despill);
+                out
+            }
+            EditOperation::Recolor {
+                hue,
+                saturation,
+                lightness,
+            } => {
+                let mut out = img.clone();
+                for p in out.pixels_mut() {
+                    let (h, s, l) = rgb_to_hsl(p[0], p[1], p[2]);
+                    let (r, g, b) =
+                        hsl_to_rgb(h + hue, (s * saturation).min(1.0), (l * lightness).min(1.0));
+                    p.0 = [r, g, b, p[3]];
+                }
+                out
+            }
+            EditOperation::Scale { factor, filter } => {
+                let largest = (img.width().max(img.height()) as f64 * *factor as f64).round();
+                if largest > MAX_SCALED_SIDE as f64 {
+                    return Err(AppError::invalid(
+                        "factor",
+                        format!(
+                            "Scaled image would exceed {} pixels per side",
+                            MAX_SCALED_SIDE
+                        ),
+                    ));
+                }
+                let width = ((img.width() as f32 * factor).round() as u32).max(1);
+                let height = ((img.height() as f32 * factor).round() as u32).max(1);
+                imageops::resize(img, width, height, (*filter).into())
+            }
+            EditOperation::Transform { transform } => transform::apply_transform(img, *transform),
+            EditOperation::Filter { filter } => {
+                let mut out = img.clone();
+                filters::apply_filter(&mut out, *filter);
+                out
+            }
+        })
+    }
+}
+
+// Fully transparent images are left as they are rather than cropped to nothing
+fn trim(img: &RgbaImage, alpha_threshold: u8) -> RgbaImage {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, p) in img.enumerate_pixels() {
+        if p[3] > alpha_threshold {
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+    }
+    if min_x > max_x {
+        return img.clone();
+    }
+    imageops::crop_imm(img, min_x, min_y, max_x - min_x + 1, max_y - min_y + 1).to_image()
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ImportOptions {
+    // Downscale the working copy so neither side exceeds this; the file is
+    // kept as the full-resolution source
+    pub max_dimension: Option<u32>,
+    pub depth: DepthOptions,
+}
+
+#[derive(Serialize)]
+pub struct ImportedImage {
+    pub handle: u32,
+    // Working copy
+    pub width: u32,
+    pub height: u32,
+    pub original_width: u32,
+    pub original_height: u32,
+}
+
+// File a downscaled working copy was made from
+#[derive(Clone)]
+struct OriginalSource {
+    path: PathBuf,
+    depth: DepthOptions,
+    // Full-resolution size over working-copy size
+    scale: f32,
+}
+
+struct Pipeline {
+    source: RgbaImage,
+    original: Option<OriginalSource>,
+    operations: Vec<EditOperation>,
+    // Output of each operation, filled on render and cut back to the first
+    // operation that changed, so tweaking a late step doesn't redo the rest
+    rendered: Vec<RgbaImage>,
+    // Bumped on every edit, so a render finished after one isn't cached
+    revision: u64,
+}
+
+impl Pipeline {
+    fn invalidate_from(&mut self, index: usize) {
+        self.rendered.truncate(index);
+        self.revision += 1;
+    }
+}
+
+// Managed state holding each sprite as its untouched source plus an ordered
+// list of edits, so any step can be changed or removed later
+#[derive(Default)]
+pub struct EditPipelines {
+    inner: Mutex<PipelinesInner>,
+}
+
+#[derive(Default)]
+struct PipelinesInner {
+    next_id: u32,
+    pipelines: HashMap<u32, Pipeline>,
+}
+
+fn out_of_range(index: usize, len: usize) -> AppError {
+    AppError::invalid(
+        "index",
+        format!(
+            "Operation index {} is out of range ({} operations)",
+            index, len
+        ),
+    )
+}
+
+impl EditPipelines {
+    pub fn create(&self, source: RgbaImage) -> u32 {
+        self.insert_pipeline(source, None)
+    }
+
+    fn insert_pipeline(&self, source: RgbaImage, original: Option<OriginalSource>) -> u32 {
+        let mut inner = self.inner.lock().unwrap();
+        inner.next_id += 1;
+        let id = inner.next_id;
+        inner.pipelines.insert(
+            id,
+            Pipeline {
+                source,
+                original,
+                operations: Vec::new(),
+                rendered: Vec::new(),
+                revision: 0,
+            },
+        );
+        id
+    }
+
+    fn with_pipeline<T>(
+        &self,
+        id: u32,
+        f: impl FnOnce(&mut Pipeline) -> Result<T, AppError>,
+    ) -> Result<T, AppError> {
+        let mut inner = self.inner.lock().unwrap();
+        let pipeline = inner.pipelines.get_mut(&id).ok_or_else(|| {
+            AppError::invalid("handle", format!("No edit pipeline with handle {}", id))
+        })?;
+        f(pipeline)
+    }
+
+    pub fn operations(&self, id: u32) -> Result<Vec<EditOperation>, AppError> {
+        self.with_pipeline(id, |p| Ok(p.operations.clone()))
+    }
+
+    // Append when `index` is None
+    pub fn insert(
+        &self,
+        id: u32,
+        index: Option<usize>,
+        operation: EditOperation,
+    ) -> Result<Vec<EditOperation>, AppError> {
+        operation.validate()?;
+        self.with_pipeline(id, |p| {
+            let index = index.unwrap_or(p.operations.len());
+            if index > p.operations.len() {
+                return Err(out_of_range(index, p.operations.len()));
+            }
+            p.operations.insert(index, operation);
+            p.invalidate_from(index);
+            Ok(p.operations.clone())
+        })
+    }
+
+    pub fn update(
+        &self,
+        id: u32,
+        index: usize,
+        operation: EditOperation,
+    ) -> Result<Vec<EditOperation>, AppError> {
+        operation.validate()?;
+        self.with_pipeline(id, |p| {
+            let len = p.operations.len();
+            let slot = p
+                .operations
+                .get_mut(index)
+                .ok_or_else(|| out_of_range(index, len))?;
+            *slot = operation;
+            p.invalidate_from(index);
+            Ok(p.operations.clone())
+        })
+    }
+
+    pub fn remove(&self, id: u32, index: usize) -> Result<Vec<EditOperation>, AppError> {
+        self.with_pipeline(id, |p| {
+            if index >= p.operations.len() {
+                return Err(out_of_range(index, p.operations.len()));
+            }
+            p.operations.remove(index);
+            p.invalidate_from(index);
+            Ok(p.operations.clone())
+        })
+    }
+
+    pub fn reorder(&self, id: u32, from: usize, to: usize) -> Result<Vec<EditOperation>, AppError> {
+        self.with_pipeline(id, |p| {
+            let len = p.operations.len();
+            if from >= len || to >= len {
+                return Err(out_of_range(from.max(to), len));
+            }
+            let operation = p.operations.remove(from);
+            p.operations.insert(to, operation);
+            p.invalidate_from(from.min(to));
+            Ok(p.operations.clone())
+        })
+    }
+
+    // Opens `path` as a new pipeline. With `max_dimension`, edits run on a
+    // downscaled copy and `render_full` replays them on the file.
+    pub fn import(&self, path: &Path, options: &ImportOptions) -> Result<ImportedImage, AppError> {
+        if options.max_dimension == Some(0) {
+            return Err(AppError::invalid(
+                "max_dimension",
+                "Maximum dimension must be at least 1",
+            ));
+        }
+        let mut img = open_image_with(path, &options.depth)?.to_rgba8();
+        let (original_width, original_height) = img.dimensions();
+        let max_dimension = options.max_dimension.unwrap_or(u32::MAX);
+        let scale = (max_dimension as f32 / original_width.max(original_height) as f32).min(1.0);
+
+        let original = if scale < 1.0 {
+            let width = ((original_width as f32 * scale).round() as u32).max(1);
+            let height = ((original_height as f32 * scale).round() as u32).max(1);
+            // Averaged in premultiplied space so transparent pixels don't
+            // darken the edges
+            premultiply(&mut img);
+            img = imageops::thumbnail(&img, width, height);
+            unpremultiply(&mut img);
+            Some(OriginalSource {
+                path: path.to_path_buf(),
+                depth: options.depth,
+                scale: original_width.max(original_height) as f32 / width.max(height) as f32,
+            })
+        } else {
+            None
+        };
+        let (width, height) = img.dimensions();
+        Ok(ImportedImage {
+            handle: self.insert_pipeline(img, original),
+            width,
+            height,
+            original_width,
+            original_height,
+        })
+    }
+
+    // Runs the operations past the cached ones without holding the lock, so
+    // a slow filter doesn't stall edits and renders of other sprites
+    pub fn render(&self, id: u32) -> Result<RgbaImage, AppError> {
+        let (input, pending, cached, revision) = self.with_pipeline(id, |p| {
+            let input = p.rendered.last().unwrap_or(&p.source).clone();
+            let pending = p.operations[p.rendered.len()..].to_vec();
+            Ok((input, pending, p.rendered.len(), p.revision))
+        })?;
+        if pending.is_empty() {
+            return Ok(input);
+        }
+        let mut outputs = Vec::with_capacity(pending.len());
+        for operation in &pending {
+            let output = operation.apply(outputs.last().unwrap_or(&input))?;
+            outputs.push(output);
+        }
+        let image = outputs.last().cloned().unwrap_or(input);
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(p) = inner.pipelines.get_mut(&id) {
+            if p.revision == revision && p.rendered.len() == cached {
+                p.rendered.extend(outputs);
+            }
+        }
+        Ok(image)
+    }
+
+    // The operations applied to the full-resolution file a working copy was
+    // made from, or the normal render when there is none. Blur radii and other
+    // pixel-sized parameters are scaled up by the working copy's downscale
+    // factor, so the result looks like the preview the user approved.
+    pub fn render_full(&self, id: u32) -> Result<RgbaImage, AppError> {
+        let (original, operations) =
+            self.with_pipeline(id, |p| Ok((p.original.clone(), p.operations.clone())))?;
+        let Some(original) = original else {
+            return self.render(id);
+        };
+        let source = open_image_with(&original.path, &original.depth)?.to_rgba8();
+        operations.iter().try_fold(source, |img, operation| {
+            operation.scaled(original.scale).apply(&img)
+        })
+    }
+
+    pub fn remove_pipeline(&self, id: u32) {
+        self.inner.lock().unwrap().pipelines.remove(&id);
+    }
+}
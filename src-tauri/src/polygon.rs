@@ -0,0 +1,359 @@
+use image::RgbaImage;
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PolygonOptions {
+    // Pixels with alpha at or below this are treated as empty
+    pub alpha_threshold: u8,
+    // Max distance (px) the simplified outline may deviate from the traced one
+    pub tolerance: f32,
+}
+
+impl Default for PolygonOptions {
+    fn default() -> Self {
+        PolygonOptions {
+            alpha_threshold: 0,
+            tolerance: 1.5,
+        }
+    }
+}
+
+// Tight outline of a sprite. The sprite is cropped to the polygon's bounds;
+// vertices are relative to that crop.
+#[derive(Clone)]
+pub struct SpriteShape {
+    pub trim_x: u32,
+    pub trim_y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub source_width: u32,
+    pub source_height: u32,
+    pub vertices: Vec<[f32; 2]>,
+    pub triangles: Vec<[u32; 3]>,
+}
+
+type Point = (f32, f32);
+
+struct Mask {
+    width: i32,
+    height: i32,
+    solid: Vec<bool>,
+}
+
+impl Mask {
+    fn new(img: &RgbaImage, threshold: u8) -> Self {
+        Mask {
+            width: img.width() as i32,
+            height: img.height() as i32,
+            solid: img.pixels().map(|p| p[3] > threshold).collect(),
+        }
+    }
+
+    fn get(&self, x: i32, y: i32) -> bool {
+        x >= 0
+            && y >= 0
+            && x < self.width
+            && y < self.height
+            && self.solid[(y * self.width + x) as usize]
+    }
+}
+
+const UP: (i32, i32) = (0, -1);
+const DOWN: (i32, i32) = (0, 1);
+const LEFT: (i32, i32) = (-1, 0);
+const RIGHT: (i32, i32) = (1, 0);
+
+// Marching squares along pixel corners, starting at the top-left corner of
+// the first solid pixel. Returns the corners where the direction changes.
+fn trace(mask: &Mask, start: (i32, i32)) -> Vec<(i32, i32)> {
+    let (mut x, mut y) = start;
+    let mut prev = (0, 0);
+    let mut points = Vec::new();
+
+    loop {
+        let state = mask.get(x - 1, y - 1) as u8
+            | (mask.get(x, y - 1) as u8) << 1
+            | (mask.get(x - 1, y) as u8) << 2
+            | (mask.get(x, y) as u8) << 3;
+        let dir = match state {
+            1 | 5 | 13 => UP,
+            8 | 10 | 11 => DOWN,
+            4 | 12 | 14 => LEFT,
+            2 | 3 | 7 => RIGHT,
+            // Saddles: keep turning the same way we came in
+            6 => {
+                if prev == UP {
+                    LEFT
+                } else {
+                    RIGHT
+                }
+            }
+            9 => {
+                if prev == RIGHT {
+                    UP
+                } else {
+                    DOWN
+                }
+            }
+            _ => return points,
+        };
+        if dir != prev {
+            points.push((x, y));
+        }
+        x += dir.0;
+        y += dir.1;
+        prev = dir;
+        if (x, y) == start {
+            return points;
+        }
+    }
+}
+
+// Whether every solid pixel center lies inside the axis-aligned outline
+fn covers(outline: &[(i32, i32)], mask: &Mask) -> bool {
+    for y in 0..mask.height {
+        let mut crossings: Vec<i32> = Vec::new();
+        for (i, &(x0, y0)) in outline.iter().enumerate() {
+            let (x1, y1) = outline[(i + 1) % outline.len()];
+            if x0 == x1 && y0.min(y1) <= y && y < y0.max(y1) {
+                crossings.push(x0);
+            }
+        }
+        crossings.sort_unstable();
+
+        let inside = |x: i32| crossings.iter().filter(|&&c| c <= x).count() % 2 == 1;
+        if (0..mask.width).any(|x| mask.get(x, y) && !inside(x)) {
+            return false;
+        }
+    }
+    true
+}
+
+fn cross(o: Point, a: Point, b: Point) -> f32 {
+    (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+}
+
+// Convex hull of all solid pixels, for sprites made of several islands
+fn hull(mask: &Mask) -> Vec<Point> {
+    let mut points = Vec::new();
+    for y in 0..mask.height {
+        let row: Vec<i32> = (0..mask.width).filter(|&x| mask.get(x, y)).collect();
+        if let (Some(&first), Some(&last)) = (row.first(), row.last()) {
+            for (x, dy) in [(first, 0), (first, 1), (last + 1, 0), (last + 1, 1)] {
+                points.push((x as f32, (y + dy) as f32));
+            }
+        }
+    }
+    points.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    points.dedup();
+
+    // Andrew's monotone chain
+    let mut lower: Vec<Point> = Vec::new();
+    for &p in &points {
+        while lower.len() >= 2 && cross(lower[lower.len() - 2], lower[lower.len() - 1], p) <= 0.0 {
+            lower.pop();
+        }
+        lower.push(p);
+    }
+    let mut upper: Vec<Point> = Vec::new();
+    for &p in points.iter().rev() {
+        while upper.len() >= 2 && cross(upper[upper.len() - 2], upper[upper.len() - 1], p) <= 0.0 {
+            upper.pop();
+        }
+        upper.push(p);
+    }
+    lower.pop();
+    upper.pop();
+    lower.extend(upper);
+    lower
+}
+
+fn distance_to_segment(p: Point, a: Point, b: Point) -> f32 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let len_sq = dx * dx + dy * dy;
+    let t = if len_sq > 0.0 {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / len_sq).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let (cx, cy) = (a.0 + dx * t, a.1 + dy * t);
+    ((p.0 - cx).powi(2) + (p.1 - cy).powi(2)).sqrt()
+}
+
+// Douglas-Peucker on an open chain; keeps both endpoints
+fn simplify_chain(points: &[Point], tolerance: f32, out: &mut Vec<Point>) {
+    let (first, last) = (points[0], points[points.len() - 1]);
+    let farthest = (1..points.len() - 1)
+        .map(|i| (i, distance_to_segment(points[i], first, last)))
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+
+    match farthest {
+        Some((i, d)) if d > tolerance => {
+            simplify_chain(&points[..=i], tolerance, out);
+            out.pop();
+            simplify_chain(&points[i..], tolerance, out);
+        }
+        _ => {
+            out.push(first);
+            out.push(last);
+        }
+    }
+}
+
+// Split the closed ring at its two most distant points and simplify both halves
+pub fn simplify(ring: &[Point], tolerance: f32) -> Vec<Point> {
+    if ring.len() <= 4 {
+        return ring.to_vec();
+    }
+    let far = (1..ring.len())
+        .max_by(|&a, &b| {
+            let da = (ring[a].0 - ring[0].0).powi(2) + (ring[a].1 - ring[0].1).powi(2);
+            let db = (ring[b].0 - ring[0].0).powi(2) + (ring[b].1 - ring[0].1).powi(2);
+            da.total_cmp(&db)
+        })
+        .unwrap_or(1);
+
+    let mut closed = ring.to_vec();
+    closed.push(ring[0]);
+    let mut out = Vec::new();
+    simplify_chain(&closed[..=far], tolerance, &mut out);
+    out.pop();
+    simplify_chain(&closed[far..], tolerance, &mut out);
+    out.pop();
+    out
+}
+
+fn signed_area(poly: &[Point]) -> f32 {
+    (0..poly.len())
+        .map(|i| {
+            let (a, b) = (poly[i], poly[(i + 1) % poly.len()]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum::<f32>()
+        / 2.0
+}
+
+// Push every vertex outward by `distance` (mitered, capped at 3x) so pixels
+// shaved off by simplification are covered again, then clamp to the sprite
+fn inflate(poly: &[Point], distance: f32, width: f32, height: f32) -> Vec<Point> {
+    let sign = if signed_area(poly) > 0.0 { 1.0 } else { -1.0 };
+    let normal = |a: Point, b: Point| {
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let len = (dx * dx + dy * dy).sqrt().max(1e-6);
+        (sign * dy / len, -sign * dx / len)
+    };
+
+    let n = poly.len();
+    (0..n)
+        .map(|i| {
+            let (prev, cur, next) = (poly[(i + n - 1) % n], poly[i], poly[(i + 1) % n]);
+            let (n1, n2) = (normal(prev, cur), normal(cur, next));
+            let (mx, my) = (n1.0 + n2.0, n1.1 + n2.1);
+            let len = (mx * mx + my * my).sqrt();
+            let (mx, my) = if len < 1e-3 { n1 } else { (mx / len, my / len) };
+            let scale = distance / (mx * n1.0 + my * n1.1).max(1.0 / 3.0);
+            (
+                (cur.0 + mx * scale).clamp(0.0, width),
+                (cur.1 + my * scale).clamp(0.0, height),
+            )
+        })
+        .collect()
+}
+
+fn in_triangle(p: Point, a: Point, b: Point, c: Point) -> bool {
+    let (d1, d2, d3) = (cross(a, b, p), cross(b, c, p), cross(c, a, p));
+    let negative = d1 < 0.0 || d2 < 0.0 || d3 < 0.0;
+    let positive = d1 > 0.0 || d2 > 0.0 || d3 > 0.0;
+    !(negative && positive)
+}
+
+// Ear clipping; None for degenerate or self-intersecting outlines
+fn triangulate(poly: &[Point]) -> Option<Vec<[u32; 3]>> {
+    let area = signed_area(poly);
+    if poly.len() < 3 || area.abs() < 1e-3 {
+        return None;
+    }
+    let sign = area.signum();
+
+    let mut remaining: Vec<usize> = (0..poly.len()).collect();
+    let mut triangles = Vec::with_capacity(poly.len() - 2);
+    while remaining.len() > 3 {
+        let m = remaining.len();
+        let ear = (0..m).find(|&i| {
+            let (a, b, c) = (
+                remaining[(i + m - 1) % m],
+                remaining[i],
+                remaining[(i + 1) % m],
+            );
+            cross(poly[a], poly[b], poly[c]) * sign > 0.0
+                && !remaining
+                    .iter()
+                    .filter(|&&j| j != a && j != b && j != c)
+                    .any(|&j| in_triangle(poly[j], poly[a], poly[b], poly[c]))
+        })?;
+        triangles.push([
+            remaining[(ear + m - 1) % m] as u32,
+            remaining[ear] as u32,
+            remaining[(ear + 1) % m] as u32,
+        ]);
+        remaining.remove(ear);
+    }
+    triangles.push([
+        remaining[0] as u32,
+        remaining[1] as u32,
+        remaining[2] as u32,
+    ]);
+    Some(triangles)
+}
+
+// None for fully transparent sprites
+pub fn sprite_shape(img: &RgbaImage, options: &PolygonOptions) -> Option<SpriteShape> {
+    let mask = Mask::new(img, options.alpha_threshold);
+    let first = mask.solid.iter().position(|&s| s)?;
+    let start = (first as i32 % mask.width, first as i32 / mask.width);
+    let (width, height) = (img.width() as f32, img.height() as f32);
+
+    let outline = trace(&mask, start);
+    let ring: Vec<Point> = if outline.len() >= 4 && covers(&outline, &mask) {
+        outline.iter().map(|&(x, y)| (x as f32, y as f32)).collect()
+    } else {
+        hull(&mask)
+    };
+
+    let tolerance = options.tolerance.max(0.0);
+    let mut polygon = simplify(&ring, tolerance);
+    if tolerance > 0.0 {
+        polygon = inflate(&polygon, tolerance, width, height);
+    }
+
+    let mut triangles = triangulate(&polygon);
+    if triangles.is_none() {
+        // Fall back to the bounding box of the solid pixels
+        let xs = ring.iter().map(|p| p.0);
+        let ys = ring.iter().map(|p| p.1);
+        let (x0, x1) = (xs.clone().fold(width, f32::min), xs.fold(0.0, f32::max));
+        let (y0, y1) = (ys.clone().fold(height, f32::min), ys.fold(0.0, f32::max));
+        polygon = vec![(x0, y0), (x1, y0), (x1, y1), (x0, y1)];
+        triangles = Some(vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    let trim_x = polygon.iter().map(|p| p.0).fold(width, f32::min).floor() as u32;
+    let trim_y = polygon.iter().map(|p| p.1).fold(height, f32::min).floor() as u32;
+    let right = polygon.iter().map(|p| p.0).fold(0.0, f32::max).ceil() as u32;
+    let bottom = polygon.iter().map(|p| p.1).fold(0.0, f32::max).ceil() as u32;
+
+    Some(SpriteShape {
+        trim_x,
+        trim_y,
+        width: (right - trim_x).max(1),
+        height: (bottom - trim_y).max(1),
+        source_width: img.width(),
+        source_height: img.height(),
+        vertices: polygon
+            .iter()
+            .map(|p| [p.0 - trim_x as f32, p.1 - trim_y as f32])
+            .collect(),
+        triangles: triangles.unwrap_or_default(),
+    })
+}
@@ -0,0 +1,58 @@
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Deserialize;
+use std::io::Write;
+
+use crate::error::AppError;
+
+// Pre-compressed copies for static hosts that serve `.gz` / `.br` files
+// directly (nginx gzip_static, brotli_static)
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Precompression {
+    Gzip,
+    Brotli,
+}
+
+impl Precompression {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Precompression::Gzip => "gz",
+            Precompression::Brotli => "br",
+        }
+    }
+
+    // Highest compression level: files are compressed once and served often
+    pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>, AppError> {
+        match self {
+            Precompression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+                encoder.write_all(data)?;
+                Ok(encoder.finish()?)
+            }
+            Precompression::Brotli => {
+                let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 11, 22);
+                encoder.write_all(data)?;
+                Ok(encoder.into_inner())
+            }
+        }
+    }
+}
+
+// `name` with each compression's extension appended ("atlas.json.gz"), and
+// the compressed data
+pub fn precompressed(
+    name: &str,
+    data: &[u8],
+    kinds: &[Precompression],
+) -> Result<Vec<(String, Vec<u8>)>, AppError> {
+    kinds
+        .iter()
+        .map(|kind| {
+            Ok((
+                format!("{}.{}", name, kind.extension()),
+                kind.compress(data)?,
+            ))
+        })
+        .collect()
+}
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::error::AppError;
+use crate::session::{now_secs, write_json};
+
+// A named export configuration. `config` holds whatever the export dialog
+// needs (format, padding, max size, trim, extrude, compression) as-is.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ExportPreset {
+    pub name: String,
+    pub config: Value,
+    pub updated_at: u64,
+}
+
+// Managed state backed by presets.json in the app config directory
+pub struct PresetStore {
+    path: PathBuf,
+    presets: Mutex<BTreeMap<String, ExportPreset>>,
+}
+
+impl PresetStore {
+    pub fn load(config_dir: PathBuf) -> Self {
+        let path = config_dir.join("presets.json");
+        let presets = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+
+        PresetStore {
+            path,
+            presets: Mutex::new(presets),
+        }
+    }
+
+    pub fn list(&self) -> Vec<ExportPreset> {
+        self.presets.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn get(&self, name: &str) -> Result<ExportPreset, AppError> {
+        self.presets
+            .lock()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| AppError::invalid("name", format!("No preset named \"{}\"", name)))
+    }
+
+    // Creates the preset or overwrites one with the same name
+    pub fn save(&self, name: &str, config: Value) -> Result<(), AppError> {
+        if name.trim().is_empty() {
+            return Err(AppError::invalid("name", "Preset name cannot be empty"));
+        }
+
+        let mut presets = self.presets.lock().unwrap();
+        presets.insert(
+            name.to_string(),
+            ExportPreset {
+                name: name.to_string(),
+                config,
+                updated_at: now_secs(),
+            },
+        );
+        write_json(&self.path, &*presets)
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), AppError> {
+        let mut presets = self.presets.lock().unwrap();
+        if presets.remove(name).is_none() {
+            return Err(AppError::invalid("name", format!("No preset named \"{}\"", name)));
+        }
+        write_json(&self.path, &*presets)
+    }
+}
@@ -0,0 +1,218 @@
+use image::RgbaImage;
+use imagequant::RGBA;
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+
+#[derive(Serialize, Clone, Copy)]
+pub struct PaletteColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl From<RGBA> for PaletteColor {
+    fn from(c: RGBA) -> Self {
+        PaletteColor { r: c.r, g: c.g, b: c.b, a: c.a }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DitherAlgorithm {
+    None,
+    #[default]
+    FloydSteinberg,
+    // 4x4 Bayer matrix: regular pattern that suits flat pixel-art colors
+    Ordered,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct Dithering {
+    #[serde(default)]
+    pub algorithm: DitherAlgorithm,
+    // 0.0 - 1.0
+    #[serde(default = "default_dither_level")]
+    pub level: f32,
+}
+
+fn default_dither_level() -> f32 {
+    1.0
+}
+
+impl Default for Dithering {
+    fn default() -> Self {
+        Dithering {
+            algorithm: DitherAlgorithm::default(),
+            level: default_dither_level(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct QuantizeSettings {
+    #[serde(default = "default_quality")]
+    pub quality: u8,
+    // Palette cap (2 - 256), e.g. 16 for retro palettes
+    #[serde(default = "default_max_colors")]
+    pub max_colors: u32,
+    #[serde(default)]
+    pub dithering: Dithering,
+}
+
+fn default_quality() -> u8 {
+    100
+}
+
+fn default_max_colors() -> u32 {
+    256
+}
+
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+// Nearest palette entry for each pixel after a Bayer threshold offset
+fn remap_ordered(img: &RgbaImage, palette: &[RGBA], level: f32) -> Vec<u8> {
+    let spread = 64.0 * level.clamp(0.0, 1.0);
+    img.enumerate_pixels()
+        .map(|(x, y, p)| {
+            let threshold = (BAYER_4X4[y as usize % 4][x as usize % 4] as f32 + 0.5) / 16.0 - 0.5;
+            let target = [0, 1, 2].map(|c| p[c] as f32 + threshold * spread);
+            let distance = |c: &RGBA| {
+                let dr = c.r as f32 - target[0];
+                let dg = c.g as f32 - target[1];
+                let db = c.b as f32 - target[2];
+                let da = c.a as f32 - p[3] as f32;
+                dr * dr + dg * dg + db * db + da * da
+            };
+            (0..palette.len())
+                .min_by(|&a, &b| distance(&palette[a]).total_cmp(&distance(&palette[b])))
+                .unwrap_or(0) as u8
+        })
+        .collect()
+}
+
+fn to_liq_pixels(img: &RgbaImage) -> Vec<RGBA> {
+    img.pixels()
+        .map(|p| RGBA::new(p[0], p[1], p[2], p[3]))
+        .collect()
+}
+
+fn new_attributes(quality: u8, max_colors: u32) -> Result<imagequant::Attributes, AppError> {
+    let mut liq = imagequant::new();
+    liq.set_quality(0, quality).map_err(AppError::encode)?;
+    liq.set_max_colors(max_colors.clamp(2, 256))
+        .map_err(AppError::encode)?;
+    Ok(liq)
+}
+
+// Color quantization of a single image (lossy PNG compression)
+pub fn quantize_image(
+    img: &RgbaImage,
+    settings: &QuantizeSettings,
+) -> Result<(Vec<RGBA>, Vec<u8>), AppError> {
+    let (width, height) = img.dimensions();
+    let mut liq = new_attributes(settings.quality, settings.max_colors)?;
+
+    let mut img_liq = liq
+        .new_image(to_liq_pixels(img), width as usize, height as usize, 0.0)
+        .map_err(AppError::encode)?;
+
+    let mut res = liq.quantize(&mut img_liq).map_err(AppError::encode)?;
+    let dithering = settings.dithering;
+    let level = match dithering.algorithm {
+        DitherAlgorithm::FloydSteinberg => dithering.level.clamp(0.0, 1.0),
+        DitherAlgorithm::None | DitherAlgorithm::Ordered => 0.0,
+    };
+    res.set_dithering_level(level).map_err(AppError::encode)?;
+
+    let (palette, indices) = res.remapped(&mut img_liq).map_err(AppError::encode)?;
+    if dithering.algorithm == DitherAlgorithm::Ordered {
+        let indices = remap_ordered(img, &palette, dithering.level);
+        return Ok((palette, indices));
+    }
+    Ok((palette, indices))
+}
+
+// Quantize several images against one palette built from a histogram of all
+// of them, so every sprite in the atlas shares the same colors
+pub fn quantize_shared(
+    images: &[RgbaImage],
+    quality: u8,
+) -> Result<(Vec<RGBA>, Vec<Vec<u8>>), AppError> {
+    let liq = new_attributes(quality, 256)?;
+    let mut histogram = imagequant::Histogram::new(&liq);
+
+    let mut liq_images = Vec::with_capacity(images.len());
+    for img in images {
+        let (width, height) = img.dimensions();
+        let mut img_liq = liq
+            .new_image(to_liq_pixels(img), width as usize, height as usize, 0.0)
+            .map_err(AppError::encode)?;
+        histogram.add_image(&liq, &mut img_liq).map_err(AppError::encode)?;
+        liq_images.push(img_liq);
+    }
+
+    let mut res = histogram.quantize(&liq).map_err(AppError::encode)?;
+    res.set_dithering_level(1.0).map_err(AppError::encode)?;
+
+    let mut palette = Vec::new();
+    let mut all_indices = Vec::with_capacity(liq_images.len());
+    for img_liq in &mut liq_images {
+        let (p, indices) = res.remapped(img_liq).map_err(AppError::encode)?;
+        palette = p;
+        all_indices.push(indices);
+    }
+
+    Ok((palette, all_indices))
+}
+
+// The palette one quantization of all `images` together would use, e.g. to
+// estimate what a quantized atlas does to each sprite
+pub fn shared_palette(
+    images: &[RgbaImage],
+    quality: u8,
+    max_colors: u32,
+) -> Result<Vec<RGBA>, AppError> {
+    let liq = new_attributes(quality, max_colors)?;
+    let mut histogram = imagequant::Histogram::new(&liq);
+    for img in images {
+        let (width, height) = img.dimensions();
+        let mut img_liq = liq
+            .new_image(to_liq_pixels(img), width as usize, height as usize, 0.0)
+            .map_err(AppError::encode)?;
+        histogram
+            .add_image(&liq, &mut img_liq)
+            .map_err(AppError::encode)?;
+    }
+    let mut res = histogram.quantize(&liq).map_err(AppError::encode)?;
+    Ok(res.palette_vec())
+}
+
+// `bit_depth` (1, 2, 4 or 8) must be large enough for the palette size
+pub fn encode_indexed_png(
+    palette: &[RGBA],
+    indices: &[u8],
+    width: u32,
+    height: u32,
+    bit_depth: u32,
+) -> Result<Vec<u8>, AppError> {
+    let mut encoder = lodepng::Encoder::new();
+    encoder.set_auto_convert(false);
+    encoder.info_raw_mut().colortype = lodepng::ColorType::PALETTE;
+    encoder.info_raw_mut().set_bitdepth(8);
+    encoder.info_png_mut().color.colortype = lodepng::ColorType::PALETTE;
+    encoder.info_png_mut().color.set_bitdepth(bit_depth);
+
+    for color in palette {
+        encoder.info_raw_mut().palette_add(lodepng::RGBA { r: color.r, g: color.g, b: color.b, a: color.a })
+            .map_err(AppError::encode)?;
+        encoder.info_png_mut().color.palette_add(lodepng::RGBA { r: color.r, g: color.g, b: color.b, a: color.a })
+            .map_err(AppError::encode)?;
+    }
+
+    encoder
+        .encode(indices, width as usize, height as usize)
+        .map_err(AppError::encode)
+}
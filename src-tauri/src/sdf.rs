@@ -0,0 +1,494 @@
+use image::RgbaImage;
+use rayon::prelude::*;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+use crate::polygon::simplify;
+
+type Point = (f32, f32);
+
+// Channels an edge contributes to in a multi-channel field
+const RED: u8 = 1;
+const GREEN: u8 = 2;
+const BLUE: u8 = 4;
+const WHITE: u8 = RED | GREEN | BLUE;
+const CYAN: u8 = GREEN | BLUE;
+const MAGENTA: u8 = RED | BLUE;
+const YELLOW: u8 = RED | GREEN;
+
+// Direction change (radians) above which two edges meet at a corner that
+// MSDF keeps sharp
+const CORNER_ANGLE: f32 = 0.6;
+
+// Traced sprite outlines are simplified to within this many pixels
+const OUTLINE_TOLERANCE: f32 = 0.1;
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum FieldKind {
+    // Single channel, stored in alpha under white
+    #[default]
+    Sdf,
+    // Median of RGB keeps corners sharp; alpha holds the plain distance
+    Msdf,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SdfOptions {
+    pub kind: FieldKind,
+    // Distance in output pixels from the edge, on either side, that the 0-255
+    // range covers. The output gets this much margin around the shape.
+    pub range: f32,
+    // Sprites only: alpha level that counts as the edge
+    pub alpha_threshold: u8,
+    // Sprites only: output size relative to the source
+    pub scale: f32,
+}
+
+impl Default for SdfOptions {
+    fn default() -> Self {
+        SdfOptions {
+            kind: FieldKind::Sdf,
+            range: 4.0,
+            alpha_threshold: 127,
+            scale: 1.0,
+        }
+    }
+}
+
+impl SdfOptions {
+    pub fn margin(&self) -> u32 {
+        self.range.ceil() as u32
+    }
+}
+
+// Closed contours, each a loop of edges; an edge is a polyline. Coordinates
+// are output pixels, y down.
+#[derive(Default)]
+pub struct Shape {
+    contours: Vec<Vec<Vec<Point>>>,
+}
+
+impl Shape {
+    pub fn add_contour(&mut self, edges: Vec<Vec<Point>>) {
+        if !edges.is_empty() {
+            self.contours.push(edges);
+        }
+    }
+
+    // (min, max) corners of all points
+    pub fn bounds(&self) -> Option<(Point, Point)> {
+        let mut points = self.contours.iter().flatten().flatten();
+        let first = *points.next()?;
+        Some(points.fold((first, first), |(min, max), p| {
+            (
+                (min.0.min(p.0), min.1.min(p.1)),
+                (max.0.max(p.0), max.1.max(p.1)),
+            )
+        }))
+    }
+
+    pub fn transform(&mut self, f: impl Fn(Point) -> Point) {
+        for p in self.contours.iter_mut().flatten().flatten() {
+            *p = f(*p);
+        }
+    }
+}
+
+fn lerp(a: Point, b: Point, t: f32) -> Point {
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+}
+
+fn length(a: Point, b: Point) -> f32 {
+    ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt()
+}
+
+// Enough steps that the chord error stays well under a pixel
+fn curve_steps(control_length: f32) -> usize {
+    ((control_length / 4.0).ceil() as usize).clamp(1, 32)
+}
+
+pub fn flatten_quad(p0: Point, p1: Point, p2: Point) -> Vec<Point> {
+    let steps = curve_steps(length(p0, p1) + length(p1, p2));
+    (0..=steps)
+        .map(|i| {
+            let t = i as f32 / steps as f32;
+            lerp(lerp(p0, p1, t), lerp(p1, p2, t), t)
+        })
+        .collect()
+}
+
+pub fn flatten_cubic(p0: Point, p1: Point, p2: Point, p3: Point) -> Vec<Point> {
+    let steps = curve_steps(length(p0, p1) + length(p1, p2) + length(p2, p3));
+    (0..=steps)
+        .map(|i| {
+            let t = i as f32 / steps as f32;
+            let (a, b, c) = (lerp(p0, p1, t), lerp(p1, p2, t), lerp(p2, p3, t));
+            lerp(lerp(a, b, t), lerp(b, c, t), t)
+        })
+        .collect()
+}
+
+// Grid edge between two pixel centers: (x, y, vertical) runs from (x, y) to
+// (x + 1, y), or to (x, y + 1) when vertical
+type GridEdge = (i32, i32, bool);
+
+// Contours of the sprite's alpha at `threshold`, interpolated between pixel
+// centers so anti-aliased edges land between pixels. Holes and separate
+// islands become their own contours; every segment is its own edge.
+pub fn trace_sprite(img: &RgbaImage, threshold: u8) -> Shape {
+    let (width, height) = (img.width() as i32, img.height() as i32);
+    let level = threshold as f32 / 255.0;
+    let value = |x: i32, y: i32| {
+        if x >= 0 && y >= 0 && x < width && y < height {
+            img.get_pixel(x as u32, y as u32)[3] as f32 / 255.0
+        } else {
+            0.0
+        }
+    };
+    let crossing = |(x, y, vertical): GridEdge| {
+        let (nx, ny) = if vertical { (x, y + 1) } else { (x + 1, y) };
+        let (a, b) = (value(x, y), value(nx, ny));
+        let t = ((level - a) / (b - a)).clamp(0.0, 1.0);
+        lerp(
+            (x as f32 + 0.5, y as f32 + 0.5),
+            (nx as f32 + 0.5, ny as f32 + 0.5),
+            t,
+        )
+    };
+
+    // Marching squares over cells between pixel centers, with the solid side
+    // of every segment on its left (see `signed_side`)
+    let mut next: BTreeMap<GridEdge, GridEdge> = BTreeMap::new();
+    for cy in -1..height {
+        for cx in -1..width {
+            // Corners and their edges, clockwise from the top-left
+            let corners = [(cx, cy), (cx + 1, cy), (cx + 1, cy + 1), (cx, cy + 1)];
+            let edges = [
+                (cx, cy, false),
+                (cx + 1, cy, true),
+                (cx, cy + 1, false),
+                (cx, cy, true),
+            ];
+            let solid = corners.map(|(x, y)| value(x, y) > level);
+            // (edge, whether it leaves the solid area going clockwise)
+            let crossings: Vec<(GridEdge, bool)> = (0..4)
+                .filter(|&k| solid[k] != solid[(k + 1) % 4])
+                .map(|k| (edges[k], solid[k]))
+                .collect();
+            match crossings.len() {
+                2 => {
+                    let (from, to) = if crossings[0].1 {
+                        (crossings[0].0, crossings[1].0)
+                    } else {
+                        (crossings[1].0, crossings[0].0)
+                    };
+                    next.insert(from, to);
+                }
+                4 => {
+                    // Saddle: the cell center decides whether the two solid
+                    // corners connect
+                    let center = corners.iter().map(|&(x, y)| value(x, y)).sum::<f32>() / 4.0;
+                    let step = if center > level { 1 } else { 3 };
+                    for k in (0..4).filter(|&k| crossings[k].1) {
+                        next.insert(crossings[k].0, crossings[(k + step) % 4].0);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut shape = Shape::default();
+    while let Some((&start, _)) = next.first_key_value() {
+        let mut ring = Vec::new();
+        let mut edge = start;
+        while let Some(to) = next.remove(&edge) {
+            ring.push(crossing(edge));
+            edge = to;
+        }
+        let ring = simplify(&ring, OUTLINE_TOLERANCE);
+        if ring.len() >= 3 {
+            let edges = (0..ring.len())
+                .map(|i| vec![ring[i], ring[(i + 1) % ring.len()]])
+                .collect();
+            shape.add_contour(edges);
+        }
+    }
+    shape
+}
+
+struct Segment {
+    a: Point,
+    b: Point,
+    color: u8,
+    // First / last segment of its edge, where the edge's direction continues
+    // past the end point (pseudo-distance)
+    first: bool,
+    last: bool,
+}
+
+// Positive when `p` is on the left of a -> b, which is inside once contours
+// are oriented
+fn signed_side(a: Point, b: Point, p: Point) -> f32 {
+    (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)
+}
+
+// Even-odd rule
+fn is_inside(segments: &[Segment], p: Point) -> bool {
+    let mut inside = false;
+    for s in segments {
+        if (s.a.1 > p.1) != (s.b.1 > p.1) {
+            let x = s.a.0 + (p.1 - s.a.1) * (s.b.0 - s.a.0) / (s.b.1 - s.a.1);
+            if x > p.0 {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+fn direction(from: Point, to: Point) -> Point {
+    let len = length(from, to);
+    ((to.0 - from.0) / len, (to.1 - from.1) / len)
+}
+
+fn is_corner(incoming: Point, outgoing: Point) -> bool {
+    incoming.0 * outgoing.0 + incoming.1 * outgoing.1 < CORNER_ANGLE.cos()
+}
+
+fn start_direction(edge: &[Point]) -> Option<Point> {
+    let start = edge[0];
+    let to = edge.iter().find(|&&p| length(start, p) > 0.0)?;
+    Some(direction(start, *to))
+}
+
+fn end_direction(edge: &[Point]) -> Option<Point> {
+    let end = edge[edge.len() - 1];
+    let from = edge.iter().rev().find(|&&p| length(p, end) > 0.0)?;
+    Some(direction(*from, end))
+}
+
+// Split the contour into runs between corners and give neighboring runs
+// different channel pairs, so each corner is where two channels disagree
+fn color_contour(edges: &[Vec<Point>]) -> Vec<u8> {
+    let n = edges.len();
+    let corners: Vec<usize> = (0..n)
+        .filter(|&i| {
+            match (
+                end_direction(&edges[(i + n - 1) % n]),
+                start_direction(&edges[i]),
+            ) {
+                (Some(a), Some(b)) => is_corner(a, b),
+                _ => false,
+            }
+        })
+        .collect();
+
+    let mut colors = vec![WHITE; n];
+    match corners.len() {
+        0 => {}
+        // Teardrop: thirds after the only corner
+        1 if n >= 3 => {
+            for k in 0..n {
+                colors[(corners[0] + k) % n] = [MAGENTA, WHITE, YELLOW][3 * k / n];
+            }
+        }
+        1 => {}
+        count => {
+            let mut run = 0;
+            for k in 0..n {
+                let i = (corners[0] + k) % n;
+                if k > 0 && corners.contains(&i) {
+                    run += 1;
+                }
+                // The last run wraps around to the first one, so it can't
+                // repeat its color
+                colors[i] = if run == count - 1 && count % 3 == 1 {
+                    MAGENTA
+                } else {
+                    [CYAN, MAGENTA, YELLOW][run % 3]
+                };
+            }
+        }
+    }
+    colors
+}
+
+fn contour_segments(edges: &[Vec<Point>], colors: &[u8]) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    for (edge, &color) in edges.iter().zip(colors) {
+        let start = segments.len();
+        for pair in edge.windows(2) {
+            if length(pair[0], pair[1]) > 0.0 {
+                segments.push(Segment {
+                    a: pair[0],
+                    b: pair[1],
+                    color,
+                    first: false,
+                    last: false,
+                });
+            }
+        }
+        if segments.len() > start {
+            segments[start].first = true;
+            segments.last_mut().unwrap().last = true;
+        }
+    }
+    segments
+}
+
+fn reverse_contour(edges: &[Vec<Point>]) -> Vec<Vec<Point>> {
+    edges
+        .iter()
+        .rev()
+        .map(|edge| edge.iter().rev().copied().collect())
+        .collect()
+}
+
+// Colored segments with every contour turned so the inside is on the left
+fn prepare(shape: &Shape) -> Vec<Segment> {
+    let uncolored: Vec<Segment> = shape
+        .contours
+        .iter()
+        .flat_map(|edges| contour_segments(edges, &vec![WHITE; edges.len()]))
+        .collect();
+
+    let mut segments = Vec::new();
+    for edges in &shape.contours {
+        let reversed;
+        let mut edges = edges;
+        // Probe just left of the contour's longest segment
+        let own = contour_segments(edges, &vec![WHITE; edges.len()]);
+        if let Some(s) = own
+            .iter()
+            .max_by(|x, y| length(x.a, x.b).total_cmp(&length(y.a, y.b)))
+        {
+            let (dx, dy) = direction(s.a, s.b);
+            let mid = lerp(s.a, s.b, 0.5);
+            let probe = (mid.0 - dy * 0.01, mid.1 + dx * 0.01);
+            if !is_inside(&uncolored, probe) {
+                reversed = reverse_contour(edges);
+                edges = &reversed;
+            }
+        }
+        segments.extend(contour_segments(edges, &color_contour(edges)));
+    }
+    segments
+}
+
+struct Nearest {
+    distance: f32,
+    // How squarely `p` faces the segment; breaks ties at shared end points
+    orthogonality: f32,
+    index: usize,
+    t: f32,
+}
+
+// Signed distances (positive inside): one per channel, then the true one
+fn distances(segments: &[Segment], p: Point, kind: FieldKind) -> [f32; 4] {
+    let mut min = f32::MAX;
+    let mut nearest: [Option<Nearest>; 3] = [None, None, None];
+    for (index, s) in segments.iter().enumerate() {
+        let (dx, dy) = (s.b.0 - s.a.0, s.b.1 - s.a.1);
+        let len_sq = dx * dx + dy * dy;
+        let t = ((p.0 - s.a.0) * dx + (p.1 - s.a.1) * dy) / len_sq;
+        let closest = lerp(s.a, s.b, t.clamp(0.0, 1.0));
+        let distance = length(p, closest);
+        min = min.min(distance);
+        if kind == FieldKind::Sdf {
+            continue;
+        }
+
+        let orthogonality = if distance > 0.0 {
+            (signed_side(s.a, s.b, p) / (len_sq.sqrt() * distance)).abs()
+        } else {
+            1.0
+        };
+        for (c, slot) in nearest.iter_mut().enumerate() {
+            if s.color & (1 << c) == 0 {
+                continue;
+            }
+            let closer = match slot {
+                None => true,
+                Some(n) => {
+                    distance < n.distance - 1e-4
+                        || (distance <= n.distance + 1e-4 && orthogonality > n.orthogonality)
+                }
+            };
+            if closer {
+                *slot = Some(Nearest {
+                    distance,
+                    orthogonality,
+                    index,
+                    t,
+                });
+            }
+        }
+    }
+
+    let sdf = if is_inside(segments, p) { min } else { -min };
+    if kind == FieldKind::Sdf {
+        return [sdf; 4];
+    }
+    let channel = |n: &Option<Nearest>| match n {
+        None => sdf,
+        Some(n) => {
+            let s = &segments[n.index];
+            let side = signed_side(s.a, s.b, p);
+            if (n.t < 0.0 && s.first) || (n.t > 1.0 && s.last) {
+                side / length(s.a, s.b)
+            } else if side < 0.0 {
+                -n.distance
+            } else {
+                n.distance
+            }
+        }
+    };
+    let [r, g, b] = [0, 1, 2].map(|c| channel(&nearest[c]));
+    // Where the channels would reconstruct the wrong side, fall back to the
+    // plain distance
+    let median = r.max(g).min(r.min(g).max(b));
+    if (median > 0.0) != (sdf > 0.0) {
+        return [sdf; 4];
+    }
+    [r, g, b, sdf]
+}
+
+// Render `shape` into a `width` x `height` field; pixel centers are sampled
+pub fn render(shape: &Shape, width: u32, height: u32, options: &SdfOptions) -> RgbaImage {
+    let segments = prepare(shape);
+    let range = options.range.max(0.5);
+    let encode = |d: f32| {
+        ((0.5 + d / (2.0 * range)) * 255.0)
+            .round()
+            .clamp(0.0, 255.0) as u8
+    };
+
+    let mut out = RgbaImage::new(width, height);
+    out.par_chunks_mut(4).enumerate().for_each(|(i, px)| {
+        let (x, y) = ((i as u32 % width) as f32, (i as u32 / width) as f32);
+        let d = distances(&segments, (x + 0.5, y + 0.5), options.kind);
+        let rgba = match options.kind {
+            FieldKind::Sdf => [255, 255, 255, encode(d[3])],
+            FieldKind::Msdf => d.map(encode),
+        };
+        px.copy_from_slice(&rgba);
+    });
+    out
+}
+
+// Distance field of a sprite's alpha, scaled by `options.scale` and with
+// `options.margin()` pixels added on every side
+pub fn sprite_field(img: &RgbaImage, options: &SdfOptions) -> RgbaImage {
+    let margin = options.margin();
+    let scale = options.scale;
+    let width = ((img.width() as f32 * scale).round() as u32).max(1) + margin * 2;
+    let height = ((img.height() as f32 * scale).round() as u32).max(1) + margin * 2;
+
+    let mut shape = trace_sprite(img, options.alpha_threshold);
+    let offset = margin as f32;
+    shape.transform(|(x, y)| (x * scale + offset, y * scale + offset));
+    render(&shape, width, height, options)
+}
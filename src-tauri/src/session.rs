@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::AppError;
+
+const MAX_RECENT_PROJECTS: usize = 10;
+pub const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RecentProject {
+    pub path: String,
+    pub name: String,
+    pub opened_at: u64,
+}
+
+// Unsaved frontend state; `data` is opaque to the backend
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SessionSnapshot {
+    pub project_path: Option<String>,
+    pub saved_at: u64,
+    pub data: Value,
+}
+
+// Managed state: recent projects plus the latest snapshot waiting for the
+// autosave thread, both persisted under the app data directory
+pub struct SessionState {
+    dir: PathBuf,
+    recent: Mutex<Vec<RecentProject>>,
+    pending: Mutex<Option<SessionSnapshot>>,
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+pub fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), AppError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_string_pretty(value).map_err(AppError::encode)?;
+    // Write then rename so a crash mid-write never leaves a truncated file
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, json)?;
+    Ok(std::fs::rename(&tmp, path)?)
+}
+
+impl SessionState {
+    pub fn load(dir: PathBuf) -> Self {
+        let recent = std::fs::read_to_string(dir.join("recent_projects.json"))
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+
+        SessionState {
+            dir,
+            recent: Mutex::new(recent),
+            pending: Mutex::new(None),
+        }
+    }
+
+    fn recent_path(&self) -> PathBuf {
+        self.dir.join("recent_projects.json")
+    }
+
+    fn autosave_path(&self) -> PathBuf {
+        self.dir.join("autosave").join("session.json")
+    }
+
+    pub fn recent_projects(&self) -> Vec<RecentProject> {
+        self.recent.lock().unwrap().clone()
+    }
+
+    // Move (or add) a project to the front of the recent list
+    pub fn touch_recent(&self, path: &str) -> Result<(), AppError> {
+        let mut recent = self.recent.lock().unwrap();
+        recent.retain(|p| p.path != path);
+        recent.insert(
+            0,
+            RecentProject {
+                path: path.to_string(),
+                name: Path::new(path)
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| path.to_string()),
+                opened_at: now_secs(),
+            },
+        );
+        recent.truncate(MAX_RECENT_PROJECTS);
+        write_json(&self.recent_path(), &*recent)
+    }
+
+    pub fn clear_recent(&self) -> Result<(), AppError> {
+        let mut recent = self.recent.lock().unwrap();
+        recent.clear();
+        write_json(&self.recent_path(), &*recent)
+    }
+
+    // Cheap: just records the snapshot, the autosave thread writes it
+    pub fn stage_snapshot(&self, project_path: Option<String>, data: Value) {
+        *self.pending.lock().unwrap() = Some(SessionSnapshot {
+            project_path,
+            saved_at: now_secs(),
+            data,
+        });
+    }
+
+    pub fn flush(&self) -> Result<(), AppError> {
+        let snapshot = self.pending.lock().unwrap().take();
+        match snapshot {
+            Some(snapshot) => write_json(&self.autosave_path(), &snapshot),
+            None => Ok(()),
+        }
+    }
+
+    // Called once the state is safely saved (or deliberately thrown away)
+    pub fn discard(&self) -> Result<(), AppError> {
+        self.pending.lock().unwrap().take();
+        match std::fs::remove_file(self.autosave_path()) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    // An autosave left on disk means the last session ended with unsaved work
+    pub fn recover(&self) -> Result<Option<SessionSnapshot>, AppError> {
+        let json = match std::fs::read_to_string(self.autosave_path()) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        serde_json::from_str(&json)
+            .map(Some)
+            .map_err(AppError::decode)
+    }
+}
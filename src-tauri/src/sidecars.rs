@@ -0,0 +1,41 @@
+use std::path::Path;
+
+use crate::atlas_packer::CompanionAtlas;
+use crate::error::AppError;
+use crate::precompress::{self, Precompression};
+
+// `image_name` with its extension swapped ("atlas.png" -> "atlas.uv")
+pub fn sidecar_name(image_name: &str, extension: &str) -> String {
+    Path::new(image_name)
+        .with_extension(extension)
+        .to_string_lossy()
+        .into_owned()
+}
+
+// Files written next to an atlas image and its JSON: companion pages, the
+// .uv and .bin metadata and the Tiled tileset, when the build produced them
+pub fn sidecars(
+    image_name: &str,
+    companions: Vec<CompanionAtlas>,
+    uv: Option<Vec<u8>>,
+    binary: Option<Vec<u8>>,
+    tsx: Option<String>,
+) -> Vec<(String, Vec<u8>)> {
+    let mut files: Vec<(String, Vec<u8>)> = companions
+        .into_iter()
+        .map(|c| (c.image_name, c.image_bytes))
+        .collect();
+    files.extend(uv.map(|data| (sidecar_name(image_name, "uv"), data)));
+    files.extend(binary.map(|data| (sidecar_name(image_name, "bin"), data)));
+    files.extend(tsx.map(|tsx| (sidecar_name(image_name, "tsx"), tsx.into_bytes())));
+    files
+}
+
+// Pre-compressed copies of the atlas JSON ("atlas.json.gz")
+pub fn precompressed_json(
+    image_name: &str,
+    json: &str,
+    kinds: &[Precompression],
+) -> Result<Vec<(String, Vec<u8>)>, AppError> {
+    precompress::precompressed(&sidecar_name(image_name, "json"), json.as_bytes(), kinds)
+}
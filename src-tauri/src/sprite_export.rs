@@ -0,0 +1,98 @@
+use image::RgbaImage;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::encode::{self, encode_image, OutputFormat};
+use crate::error::AppError;
+use crate::naming::fill_pattern;
+
+#[derive(Serialize)]
+pub struct ExportedSprite {
+    pub handle: u32,
+    // Written file, None when the sprite failed
+    pub path: Option<String>,
+    pub size: usize,
+    pub error: Option<AppError>,
+}
+
+// Expand the placeholders in `pattern`: {index} (1-based; {index:3} pads to
+// three digits), {handle}, {width} and {height}
+fn expand(pattern: &str, index: usize, handle: u32, size: (u32, u32)) -> Result<String, AppError> {
+    let out = fill_pattern(pattern, "name_pattern", |key| {
+        let value = match key {
+            "index" => index + 1,
+            "handle" => handle as usize,
+            "width" => size.0 as usize,
+            "height" => size.1 as usize,
+            _ => return None,
+        };
+        Some(value.to_string())
+    })?;
+    if out.is_empty() || out.contains(['/', '\\']) || out == "." || out == ".." {
+        return Err(AppError::invalid(
+            "name_pattern",
+            format!("\"{}\" is not a valid file name", out),
+        ));
+    }
+    Ok(out)
+}
+
+fn write_one(img: &RgbaImage, path: &Path, format: &OutputFormat) -> Result<usize, AppError> {
+    let bytes = encode::finish_png(encode_image(img, format)?, format, None)?;
+    std::fs::write(path, &bytes).map_err(|e| AppError::from(e).context(path.display()))?;
+    Ok(bytes.len())
+}
+
+// Encode and write every sprite in parallel. The pattern is checked for all
+// sprites before anything is written; after that, failures are reported per
+// sprite. `sprites` pairs each handle with its image (or why it couldn't be
+// fetched).
+pub fn export_sprites(
+    sprites: Vec<(u32, Result<RgbaImage, AppError>)>,
+    dir: &Path,
+    name_pattern: &str,
+    format: &OutputFormat,
+) -> Result<Vec<ExportedSprite>, AppError> {
+    let mut seen = HashSet::new();
+    let mut names = Vec::with_capacity(sprites.len());
+    for (index, (handle, img)) in sprites.iter().enumerate() {
+        let size = img.as_ref().map_or((0, 0), |img| img.dimensions());
+        let name = format!(
+            "{}.{}",
+            expand(name_pattern, index, *handle, size)?,
+            format.extension()
+        );
+        if !seen.insert(name.clone()) {
+            return Err(AppError::invalid(
+                "name_pattern",
+                format!("Several sprites would be written to {}", name),
+            ));
+        }
+        names.push(name);
+    }
+    std::fs::create_dir_all(dir)?;
+
+    Ok(sprites
+        .into_par_iter()
+        .zip(names)
+        .map(|((handle, img), name)| {
+            let path = dir.join(name);
+            match img.and_then(|img| write_one(&img, &path, format)) {
+                Ok(size) => ExportedSprite {
+                    handle,
+                    path: Some(path.to_string_lossy().into_owned()),
+                    size,
+                    error: None,
+                },
+                Err(e) => ExportedSprite {
+                    handle,
+                    path: None,
+                    size: 0,
+                    error: Some(e),
+                },
+            }
+        })
+        .collect())
+}
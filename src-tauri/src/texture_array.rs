@@ -0,0 +1,158 @@
+use image::{imageops, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::error::AppError;
+use crate::hit_test::FrameInfo;
+use crate::ktx2::{self, Ktx2Options};
+
+#[derive(Deserialize)]
+struct PageMeta {
+    #[serde(default = "default_scale")]
+    scale: f32,
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize)]
+struct PageJson {
+    frames: BTreeMap<String, FrameInfo>,
+    #[serde(default)]
+    animations: BTreeMap<String, serde_json::Value>,
+    meta: PageMeta,
+}
+
+#[derive(Serialize)]
+struct LayerFrame {
+    // Array layer the frame is on
+    layer: u32,
+    // Normalized [u0, v0, u1, v1] within the layer
+    uv: [f32; 4],
+    #[serde(flatten)]
+    info: FrameInfo,
+}
+
+#[derive(Serialize)]
+struct ArraySize {
+    w: u32,
+    h: u32,
+}
+
+#[derive(Serialize)]
+struct ArrayMeta {
+    image: String,
+    size: ArraySize,
+    layers: u32,
+    // Atlas page each layer was built from
+    pages: Vec<String>,
+    scale: f32,
+}
+
+#[derive(Serialize)]
+struct ArrayJson {
+    frames: BTreeMap<String, LayerFrame>,
+    animations: BTreeMap<String, serde_json::Value>,
+    meta: ArrayMeta,
+}
+
+pub struct TextureArray {
+    pub ktx2: Vec<u8>,
+    pub json: String,
+}
+
+// Stacks atlas pages (image name, pixels, JSON) into one KTX2 array texture.
+// Smaller pages are padded transparent on the right and bottom to the
+// largest page, so frame rectangles keep their pixel positions.
+pub fn stack_pages(
+    pages: Vec<(String, RgbaImage, String)>,
+    image_name: &str,
+    options: &Ktx2Options,
+) -> Result<TextureArray, AppError> {
+    let width = pages
+        .iter()
+        .map(|(_, img, _)| img.width())
+        .max()
+        .unwrap_or(0);
+    let height = pages
+        .iter()
+        .map(|(_, img, _)| img.height())
+        .max()
+        .unwrap_or(0);
+    if width == 0 || height == 0 {
+        return Err(AppError::invalid("build", "No atlas pages to stack"));
+    }
+
+    let mut layers = Vec::with_capacity(pages.len());
+    let mut names = Vec::with_capacity(pages.len());
+    let mut frames = BTreeMap::new();
+    let mut animations = BTreeMap::new();
+    let mut scale = None;
+    for (layer, (name, img, json)) in pages.into_iter().enumerate() {
+        let page: PageJson = serde_json::from_str(&json).map_err(AppError::decode)?;
+        for (frame, info) in page.frames {
+            if frames.contains_key(&frame) {
+                return Err(AppError::invalid(
+                    "frames",
+                    format!("Frame \"{}\" is on more than one page", frame),
+                ));
+            }
+            let r = info.frame;
+            let uv = [
+                r.x as f32 / width as f32,
+                r.y as f32 / height as f32,
+                (r.x + r.w) as f32 / width as f32,
+                (r.y + r.h) as f32 / height as f32,
+            ];
+            frames.insert(
+                frame,
+                LayerFrame {
+                    layer: layer as u32,
+                    uv,
+                    info,
+                },
+            );
+        }
+        animations.extend(page.animations);
+        // One scale in the array meta has to hold for every layer
+        if *scale.get_or_insert(page.meta.scale) != page.meta.scale {
+            return Err(AppError::invalid(
+                "build",
+                format!(
+                    "Page \"{}\" has a different scale from the first page",
+                    name
+                ),
+            ));
+        }
+
+        let layer = if img.dimensions() == (width, height) {
+            img
+        } else {
+            let mut padded = RgbaImage::new(width, height);
+            imageops::replace(&mut padded, &img, 0, 0);
+            padded
+        };
+        layers.push(layer);
+        names.push(name);
+    }
+
+    let array = ArrayJson {
+        frames,
+        animations,
+        meta: ArrayMeta {
+            image: image_name.to_string(),
+            size: ArraySize {
+                w: width,
+                h: height,
+            },
+            layers: layers.len() as u32,
+            pages: names,
+            scale: scale.unwrap_or(1.0),
+        },
+    };
+    Ok(TextureArray {
+        ktx2: ktx2::encode_ktx2(&layers, options, true)?,
+        json: serde_json::to_string_pretty(&array).map_err(AppError::encode)?,
+    })
+}
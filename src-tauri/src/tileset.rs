@@ -0,0 +1,322 @@
+use image::{DynamicImage, GenericImageView};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::error::AppError;
+use crate::image_io::{self, encode_png_base64};
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TilesetOptions {
+    // Cell size; defaults to the largest sprite
+    pub tile_width: Option<u32>,
+    pub tile_height: Option<u32>,
+    // Tiles per row; defaults to a roughly square grid
+    pub columns: Option<u32>,
+    // Border around the whole grid
+    pub margin: u32,
+    // Gap between neighbouring tiles
+    pub spacing: u32,
+}
+
+pub struct TileGrid {
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub columns: u32,
+    pub rows: u32,
+    pub margin: u32,
+    pub spacing: u32,
+}
+
+impl TileGrid {
+    // Every sprite must fit in one cell
+    pub fn new(options: &TilesetOptions, sizes: &[(u32, u32)]) -> Result<TileGrid, AppError> {
+        let max_w = sizes.iter().map(|s| s.0).max().unwrap_or(1);
+        let max_h = sizes.iter().map(|s| s.1).max().unwrap_or(1);
+        let tile_width = options.tile_width.unwrap_or(max_w);
+        let tile_height = options.tile_height.unwrap_or(max_h);
+        if tile_width == 0 || tile_height == 0 {
+            return Err(AppError::invalid("tileset", "Tile size must be positive"));
+        }
+        if max_w > tile_width || max_h > tile_height {
+            return Err(AppError::invalid(
+                "tileset",
+                format!(
+                    "Sprite of {}x{} does not fit a {}x{} tile",
+                    max_w, max_h, tile_width, tile_height
+                ),
+            ));
+        }
+
+        let count = sizes.len().max(1) as u32;
+        let columns = options
+            .columns
+            .unwrap_or_else(|| (count as f64).sqrt().ceil() as u32)
+            .clamp(1, count);
+        Ok(TileGrid {
+            tile_width,
+            tile_height,
+            columns,
+            rows: count.div_ceil(columns),
+            margin: options.margin,
+            spacing: options.spacing,
+        })
+    }
+
+    // Top-left of tile `id` in the image
+    pub fn position(&self, id: u32) -> (u32, u32) {
+        let col = id % self.columns;
+        let row = id / self.columns;
+        // Saturating so a grid read from a file can't overflow
+        let offset = |index: u32, size: u32| {
+            self.margin
+                .saturating_add(index.saturating_mul(size.saturating_add(self.spacing)))
+        };
+        (offset(col, self.tile_width), offset(row, self.tile_height))
+    }
+
+    pub fn image_size(&self) -> (u32, u32) {
+        let span = |count: u32, size: u32| {
+            self.margin * 2 + count * size + count.saturating_sub(1) * self.spacing
+        };
+        (
+            span(self.columns, self.tile_width),
+            span(self.rows, self.tile_height),
+        )
+    }
+}
+
+pub fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Tiled external tileset (.tsx); each tile keeps its sprite name as a property
+pub fn write_tsx(grid: &TileGrid, image_name: &str, tile_names: &[String]) -> String {
+    let name = image_name
+        .rsplit_once('.')
+        .map_or(image_name, |(stem, _)| stem);
+    let (width, height) = grid.image_size();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<tileset version=\"1.10\" name=\"{}\" tilewidth=\"{}\" tileheight=\"{}\" spacing=\"{}\" margin=\"{}\" tilecount=\"{}\" columns=\"{}\">\n",
+        escape_xml(name),
+        grid.tile_width,
+        grid.tile_height,
+        grid.spacing,
+        grid.margin,
+        tile_names.len(),
+        grid.columns
+    ));
+    xml.push_str(&format!(
+        " <image source=\"{}\" width=\"{}\" height=\"{}\"/>\n",
+        escape_xml(image_name),
+        width,
+        height
+    ));
+    for (id, tile_name) in tile_names.iter().enumerate() {
+        xml.push_str(&format!(
+            " <tile id=\"{}\">\n  <properties>\n   <property name=\"name\" value=\"{}\"/>\n  </properties>\n </tile>\n",
+            id,
+            escape_xml(tile_name)
+        ));
+    }
+    xml.push_str("</tileset>\n");
+    xml
+}
+
+#[derive(Serialize)]
+pub struct ImportedTile {
+    // Local tile ID within its tileset (gid = first_gid + id)
+    pub id: u32,
+    pub name: String,
+    // Tiled's per-tile `type` / `class`
+    pub tile_type: Option<String>,
+    pub properties: BTreeMap<String, String>,
+    pub base64: String,
+}
+
+#[derive(Serialize)]
+pub struct ImportedTileset {
+    pub name: String,
+    pub first_gid: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub tiles: Vec<ImportedTile>,
+}
+
+fn attr_u32(node: roxmltree::Node, name: &str) -> Option<u32> {
+    node.attribute(name).and_then(|v| v.parse().ok())
+}
+
+fn read_xml(path: &Path) -> Result<String, AppError> {
+    std::fs::read_to_string(path).map_err(|e| AppError::from(e).context(path.display()))
+}
+
+fn open_image(dir: &Path, source: &str) -> Result<DynamicImage, AppError> {
+    image_io::open_image(&dir.join(source))
+}
+
+struct TileMeta {
+    tile_type: Option<String>,
+    properties: BTreeMap<String, String>,
+    // Image collection tilesets give every tile its own image
+    image: Option<String>,
+}
+
+fn tile_meta(tile: roxmltree::Node) -> TileMeta {
+    let mut properties = BTreeMap::new();
+    for prop in tile.descendants().filter(|n| n.has_tag_name("property")) {
+        if let Some(name) = prop.attribute("name") {
+            // Multi-line string properties keep their value as text content
+            let value = prop.attribute("value").or(prop.text()).unwrap_or_default();
+            properties.insert(name.to_string(), value.to_string());
+        }
+    }
+    TileMeta {
+        tile_type: tile
+            .attribute("class")
+            .or(tile.attribute("type"))
+            .map(str::to_string),
+        properties,
+        image: tile
+            .children()
+            .find(|n| n.has_tag_name("image"))
+            .and_then(|n| n.attribute("source"))
+            .map(str::to_string),
+    }
+}
+
+// `dir` resolves image paths, which Tiled stores relative to the tileset file
+fn parse_tileset(
+    node: roxmltree::Node,
+    dir: &Path,
+    first_gid: u32,
+) -> Result<ImportedTileset, AppError> {
+    let name = node.attribute("name").unwrap_or("tileset").to_string();
+    let tile_width = attr_u32(node, "tilewidth")
+        .ok_or_else(|| AppError::decode("Tileset is missing tilewidth"))?;
+    let tile_height = attr_u32(node, "tileheight")
+        .ok_or_else(|| AppError::decode("Tileset is missing tileheight"))?;
+    if tile_width == 0 || tile_height == 0 {
+        return Err(AppError::decode("Tileset tile size must be positive"));
+    }
+    let margin = attr_u32(node, "margin").unwrap_or(0);
+    let spacing = attr_u32(node, "spacing").unwrap_or(0);
+
+    let mut metas: BTreeMap<u32, TileMeta> = BTreeMap::new();
+    for tile in node.children().filter(|n| n.has_tag_name("tile")) {
+        if let Some(id) = attr_u32(tile, "id") {
+            metas.insert(id, tile_meta(tile));
+        }
+    }
+
+    let mut images: Vec<(u32, DynamicImage)> = Vec::new();
+    match node.children().find(|n| n.has_tag_name("image")) {
+        Some(image_node) => {
+            let source = image_node
+                .attribute("source")
+                .ok_or_else(|| AppError::decode("Tileset image has no source"))?;
+            let sheet = open_image(dir, source)?;
+            let (sheet_w, sheet_h) = sheet.dimensions();
+            // Saturating, as the attributes come straight from the file
+            let fit = |size: u32, tile: u32| {
+                size.saturating_sub(margin.saturating_mul(2))
+                    .saturating_add(spacing)
+                    / tile.saturating_add(spacing)
+            };
+            let columns = attr_u32(node, "columns")
+                .filter(|&c| c > 0)
+                .unwrap_or_else(|| fit(sheet_w, tile_width));
+            let rows = fit(sheet_h, tile_height);
+            let count = attr_u32(node, "tilecount").unwrap_or(columns.saturating_mul(rows));
+
+            let grid = TileGrid {
+                tile_width,
+                tile_height,
+                columns: columns.max(1),
+                rows,
+                margin,
+                spacing,
+            };
+            for id in 0..count {
+                let (x, y) = grid.position(id);
+                if x.saturating_add(tile_width) > sheet_w || y.saturating_add(tile_height) > sheet_h
+                {
+                    break;
+                }
+                images.push((id, sheet.crop_imm(x, y, tile_width, tile_height)));
+            }
+        }
+        None => {
+            for (&id, meta) in &metas {
+                if let Some(source) = &meta.image {
+                    images.push((id, open_image(dir, source)?));
+                }
+            }
+        }
+    }
+
+    let mut tiles = Vec::with_capacity(images.len());
+    for (id, image) in images {
+        let meta = metas.remove(&id);
+        let (tile_type, properties) = meta
+            .map(|m| (m.tile_type, m.properties))
+            .unwrap_or_default();
+        tiles.push(ImportedTile {
+            id,
+            name: properties
+                .get("name")
+                .cloned()
+                .unwrap_or_else(|| format!("{}_{}", name, id)),
+            tile_type,
+            properties,
+            base64: encode_png_base64(&image)?,
+        });
+    }
+
+    Ok(ImportedTileset {
+        name,
+        first_gid,
+        tile_width,
+        tile_height,
+        tiles,
+    })
+}
+
+// A .tsx yields its one tileset; a .tmx yields every tileset it uses,
+// following external `source` references
+pub fn import_tiled(path: &Path) -> Result<Vec<ImportedTileset>, AppError> {
+    let text = read_xml(path)?;
+    let doc = roxmltree::Document::parse(&text).map_err(AppError::decode)?;
+    let root = doc.root_element();
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    match root.tag_name().name() {
+        "tileset" => Ok(vec![parse_tileset(root, dir, 1)?]),
+        "map" => {
+            let mut tilesets = Vec::new();
+            for node in root.children().filter(|n| n.has_tag_name("tileset")) {
+                let first_gid = attr_u32(node, "firstgid").unwrap_or(1);
+                match node.attribute("source") {
+                    Some(source) => {
+                        let tsx_path = dir.join(source);
+                        let tsx_text = read_xml(&tsx_path)?;
+                        let tsx =
+                            roxmltree::Document::parse(&tsx_text).map_err(AppError::decode)?;
+                        let tsx_dir = tsx_path.parent().unwrap_or(Path::new(""));
+                        tilesets.push(parse_tileset(tsx.root_element(), tsx_dir, first_gid)?);
+                    }
+                    None => tilesets.push(parse_tileset(node, dir, first_gid)?),
+                }
+            }
+            Ok(tilesets)
+        }
+        other => Err(AppError::decode(format!("Not a Tiled map or tileset: <{}>", other))),
+    }
+}
@@ -0,0 +1,466 @@
+use crate::error::AppError;
+use crate::image_io::Color;
+use image::{imageops, imageops::FilterType, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+    // Explicit position of the source's top-left corner on the new canvas
+    Custom { x: i64, y: i64 },
+}
+
+impl Anchor {
+    fn position(self, src_w: u32, src_h: u32, dst_w: u32, dst_h: u32) -> (i64, i64) {
+        let free_x = dst_w as i64 - src_w as i64;
+        let free_y = dst_h as i64 - src_h as i64;
+        let (fx, fy) = match self {
+            Anchor::Custom { x, y } => return (x, y),
+            Anchor::TopLeft => (0, 0),
+            Anchor::Top => (1, 0),
+            Anchor::TopRight => (2, 0),
+            Anchor::Left => (0, 1),
+            Anchor::Center => (1, 1),
+            Anchor::Right => (2, 1),
+            Anchor::BottomLeft => (0, 2),
+            Anchor::Bottom => (1, 2),
+            Anchor::BottomRight => (2, 2),
+        };
+        // Halves round toward the top-left like most editors
+        (free_x * fx / 2, free_y * fy / 2)
+    }
+}
+
+// Pad or crop to exactly `width` x `height`. Returns the canvas and where the
+// source's top-left pixel was placed (negative when cropped away).
+pub fn resize_canvas(
+    img: &RgbaImage,
+    width: u32,
+    height: u32,
+    anchor: Anchor,
+    fill_color: Option<Color>,
+) -> (RgbaImage, i64, i64) {
+    let (x, y) = anchor.position(img.width(), img.height(), width, height);
+
+    let output = match fill_color {
+        Some(fill) => {
+            let mut canvas = RgbaImage::from_pixel(width, height, fill.to_rgba());
+            imageops::overlay(&mut canvas, img, x, y);
+            canvas
+        }
+        None => {
+            let mut canvas = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 0]));
+            imageops::replace(&mut canvas, img, x, y);
+            canvas
+        }
+    };
+
+    (output, x, y)
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum ImageTransform {
+    FlipHorizontal,
+    FlipVertical,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+// Pixel-exact flips and quarter turns (no resampling)
+pub fn apply_transform(img: &RgbaImage, transform: ImageTransform) -> RgbaImage {
+    match transform {
+        ImageTransform::FlipHorizontal => imageops::flip_horizontal(img),
+        ImageTransform::FlipVertical => imageops::flip_vertical(img),
+        ImageTransform::Rotate90 => imageops::rotate90(img),
+        ImageTransform::Rotate180 => imageops::rotate180(img),
+        ImageTransform::Rotate270 => imageops::rotate270(img),
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Interpolation {
+    Nearest,
+    Bilinear,
+    Bicubic,
+}
+
+// Premultiplied pixel fetch; anything outside the image is transparent
+fn fetch(img: &RgbaImage, x: i64, y: i64) -> [f32; 4] {
+    if x < 0 || y < 0 || x >= img.width() as i64 || y >= img.height() as i64 {
+        return [0.0; 4];
+    }
+    let p = img.get_pixel(x as u32, y as u32);
+    let a = p[3] as f32 / 255.0;
+    [
+        p[0] as f32 * a,
+        p[1] as f32 * a,
+        p[2] as f32 * a,
+        p[3] as f32,
+    ]
+}
+
+// Catmull-Rom weights
+fn cubic_weight(t: f32) -> f32 {
+    let t = t.abs();
+    if t < 1.0 {
+        1.5 * t * t * t - 2.5 * t * t + 1.0
+    } else if t < 2.0 {
+        -0.5 * t * t * t + 2.5 * t * t - 4.0 * t + 2.0
+    } else {
+        0.0
+    }
+}
+
+// Sample at continuous coordinates where pixel centers sit at i + 0.5
+fn sample(img: &RgbaImage, x: f32, y: f32, interpolation: Interpolation) -> [f32; 4] {
+    let (x, y) = (x - 0.5, y - 0.5);
+    let (x0, y0) = (x.floor() as i64, y.floor() as i64);
+    let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+
+    match interpolation {
+        Interpolation::Nearest => fetch(img, x.round() as i64, y.round() as i64),
+        Interpolation::Bilinear => {
+            let mut out = [0.0; 4];
+            for (dy, wy) in [(0, 1.0 - ty), (1, ty)] {
+                for (dx, wx) in [(0, 1.0 - tx), (1, tx)] {
+                    let p = fetch(img, x0 + dx, y0 + dy);
+                    for c in 0..4 {
+                        out[c] += p[c] * wx * wy;
+                    }
+                }
+            }
+            out
+        }
+        Interpolation::Bicubic => {
+            let mut out = [0.0; 4];
+            for dy in -1..=2 {
+                let wy = cubic_weight(ty - dy as f32);
+                for dx in -1..=2 {
+                    let wx = cubic_weight(tx - dx as f32);
+                    let p = fetch(img, x0 + dx, y0 + dy);
+                    for c in 0..4 {
+                        out[c] += p[c] * wx * wy;
+                    }
+                }
+            }
+            out
+        }
+    }
+}
+
+fn unpremultiply(p: [f32; 4]) -> Rgba<u8> {
+    let a = p[3].clamp(0.0, 255.0);
+    if a < 0.5 {
+        return Rgba([0, 0, 0, 0]);
+    }
+    let scale = 255.0 / a;
+    Rgba([
+        (p[0] * scale).round().clamp(0.0, 255.0) as u8,
+        (p[1] * scale).round().clamp(0.0, 255.0) as u8,
+        (p[2] * scale).round().clamp(0.0, 255.0) as u8,
+        a.round() as u8,
+    ])
+}
+
+// Rotate clockwise by `degrees` around the center, growing the canvas to fit.
+// Returns the image plus the shift of the original canvas inside the new one.
+pub fn rotate_arbitrary(
+    img: &RgbaImage,
+    degrees: f32,
+    interpolation: Interpolation,
+) -> (RgbaImage, i64, i64) {
+    let (w, h) = (img.width() as f32, img.height() as f32);
+    let (sin, cos) = degrees.to_radians().sin_cos();
+
+    let new_w = (w * cos.abs() + h * sin.abs()).round().max(1.0) as u32;
+    let new_h = (w * sin.abs() + h * cos.abs()).round().max(1.0) as u32;
+    let (cx, cy) = (w / 2.0, h / 2.0);
+    let (ncx, ncy) = (new_w as f32 / 2.0, new_h as f32 / 2.0);
+
+    let output = RgbaImage::from_fn(new_w, new_h, |x, y| {
+        // Inverse-map the destination pixel center back into the source
+        let dx = x as f32 + 0.5 - ncx;
+        let dy = y as f32 + 0.5 - ncy;
+        let sx = dx * cos + dy * sin + cx;
+        let sy = -dx * sin + dy * cos + cy;
+        unpremultiply(sample(img, sx, sy, interpolation))
+    });
+
+    let shift_x = (new_w as i64 - img.width() as i64) / 2;
+    let shift_y = (new_h as i64 - img.height() as i64) / 2;
+    (output, shift_x, shift_y)
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ResizeFilter {
+    Nearest,
+    Triangle,
+    CatmullRom,
+    Gaussian,
+    #[default]
+    Lanczos3,
+}
+
+impl From<ResizeFilter> for FilterType {
+    fn from(filter: ResizeFilter) -> Self {
+        match filter {
+            ResizeFilter::Nearest => FilterType::Nearest,
+            ResizeFilter::Triangle => FilterType::Triangle,
+            ResizeFilter::CatmullRom => FilterType::CatmullRom,
+            ResizeFilter::Gaussian => FilterType::Gaussian,
+            ResizeFilter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum PixelArtAlgorithm {
+    Nearest,
+    // AdvMAME Scale2x / Scale3x (EPX): rounds diagonal edges without new colors
+    ScaleNx,
+    // Hyllian's xBR: finds edges by color distance and blends along them, so
+    // shallow slopes come out smooth rather than stepped
+    Xbr,
+}
+
+// Largest side of a scaled image, to keep a large factor from allocating GBs
+const MAX_PIXEL_ART_OUTPUT: u32 = 16384;
+
+fn clamped(img: &RgbaImage, x: i64, y: i64) -> Rgba<u8> {
+    let cx = x.clamp(0, img.width() as i64 - 1) as u32;
+    let cy = y.clamp(0, img.height() as i64 - 1) as u32;
+    *img.get_pixel(cx, cy)
+}
+
+fn scale2x(img: &RgbaImage) -> RgbaImage {
+    let mut out = RgbaImage::new(img.width() * 2, img.height() * 2);
+    for (x, y, &p) in img.enumerate_pixels() {
+        let (xi, yi) = (x as i64, y as i64);
+        let a = clamped(img, xi, yi - 1);
+        let b = clamped(img, xi + 1, yi);
+        let c = clamped(img, xi - 1, yi);
+        let d = clamped(img, xi, yi + 1);
+
+        let e0 = if c == a && c != d && a != b { a } else { p };
+        let e1 = if a == b && a != c && b != d { b } else { p };
+        let e2 = if d == c && d != b && c != a { c } else { p };
+        let e3 = if b == d && b != a && d != c { d } else { p };
+
+        out.put_pixel(x * 2, y * 2, e0);
+        out.put_pixel(x * 2 + 1, y * 2, e1);
+        out.put_pixel(x * 2, y * 2 + 1, e2);
+        out.put_pixel(x * 2 + 1, y * 2 + 1, e3);
+    }
+    out
+}
+
+fn scale3x(img: &RgbaImage) -> RgbaImage {
+    let mut out = RgbaImage::new(img.width() * 3, img.height() * 3);
+    for (x, y, &e) in img.enumerate_pixels() {
+        let (xi, yi) = (x as i64, y as i64);
+        let a = clamped(img, xi - 1, yi - 1);
+        let b = clamped(img, xi, yi - 1);
+        let c = clamped(img, xi + 1, yi - 1);
+        let d = clamped(img, xi - 1, yi);
+        let f = clamped(img, xi + 1, yi);
+        let g = clamped(img, xi - 1, yi + 1);
+        let h = clamped(img, xi, yi + 1);
+        let i = clamped(img, xi + 1, yi + 1);
+
+        let block = [
+            if d == b && b != f && d != h { d } else { e },
+            if (d == b && b != f && d != h && e != c) || (b == f && b != d && f != h && e != a) {
+                b
+            } else {
+                e
+            },
+            if b == f && b != d && f != h { f } else { e },
+            if (d == b && b != f && d != h && e != g) || (d == h && d != b && h != f && e != a) {
+                d
+            } else {
+                e
+            },
+            e,
+            if (b == f && b != d && f != h && e != i) || (h == f && d != h && b != f && e != c) {
+                f
+            } else {
+                e
+            },
+            if d == h && d != b && h != f { d } else { e },
+            if (d == h && d != b && h != f && e != i) || (h == f && d != h && b != f && e != g) {
+                h
+            } else {
+                e
+            },
+            if h == f && d != h && b != f { f } else { e },
+        ];
+
+        for (n, pixel) in block.into_iter().enumerate() {
+            out.put_pixel(x * 3 + n as u32 % 3, y * 3 + n as u32 / 3, pixel);
+        }
+    }
+    out
+}
+
+// Luma / chroma distance plus alpha, as xBR compares colors
+fn xbr_distance(a: Rgba<u8>, b: Rgba<u8>) -> u32 {
+    let yuv = |p: Rgba<u8>| {
+        let (r, g, b) = (p[0] as f32, p[1] as f32, p[2] as f32);
+        [
+            0.299 * r + 0.587 * g + 0.114 * b,
+            -0.169 * r - 0.331 * g + 0.5 * b,
+            0.5 * r - 0.419 * g - 0.081 * b,
+        ]
+    };
+    let (ya, yb) = (yuv(a), yuv(b));
+    let color: f32 = ya.iter().zip(&yb).map(|(x, y)| (x - y).abs()).sum();
+    color.round() as u32 + a[3].abs_diff(b[3]) as u32
+}
+
+fn xbr_equal(a: Rgba<u8>, b: Rgba<u8>) -> bool {
+    xbr_distance(a, b) < 155
+}
+
+// `dst` moved `eighths`/8 of the way to `src`, weighted by alpha so
+// transparent pixels don't darken the blend
+fn xbr_blend(dst: Rgba<u8>, src: Rgba<u8>, eighths: u32) -> Rgba<u8> {
+    let wd = dst[3] as u32 * (8 - eighths);
+    let ws = src[3] as u32 * eighths;
+    let total = wd + ws;
+    if total == 0 {
+        return Rgba([0, 0, 0, 0]);
+    }
+    let mut out = [0u8; 4];
+    for c in 0..3 {
+        out[c] = ((dst[c] as u32 * wd + src[c] as u32 * ws + total / 2) / total) as u8;
+    }
+    out[3] = ((total + 4) / 8) as u8;
+    Rgba(out)
+}
+
+// A neighbourhood offset turned `turns` quarter turns; each turn maps the
+// bottom-right corner onto another corner of the block
+fn quarter_turn(turns: u8, dx: i64, dy: i64) -> (i64, i64) {
+    match turns % 4 {
+        0 => (dx, dy),
+        1 => (dy, -dx),
+        2 => (-dx, -dy),
+        _ => (-dy, dx),
+    }
+}
+
+// 2xBR. Each pixel becomes a 2x2 block; the same edge test runs once per
+// corner, with the neighbourhood turned so that corner is bottom-right.
+fn xbr2x(img: &RgbaImage) -> RgbaImage {
+    let mut out = RgbaImage::new(img.width() * 2, img.height() * 2);
+    for (x, y, &e) in img.enumerate_pixels() {
+        let (xi, yi) = (x as i64, y as i64);
+        // Top-left, top-right, bottom-left, bottom-right
+        let mut block = [e; 4];
+
+        for turns in 0..4 {
+            let at = |dx: i64, dy: i64| {
+                let (dx, dy) = quarter_turn(turns, dx, dy);
+                clamped(img, xi + dx, yi + dy)
+            };
+            let slot = |dx: i64, dy: i64| {
+                let (dx, dy) = quarter_turn(turns, dx, dy);
+                (dx > 0) as usize + 2 * (dy > 0) as usize
+            };
+            let (b, c, d, f) = (at(0, -1), at(1, -1), at(-1, 0), at(1, 0));
+            let (g, h, i) = (at(-1, 1), at(0, 1), at(1, 1));
+            let (f4, i4, h5, i5) = (at(2, 0), at(2, 1), at(0, 2), at(1, 2));
+            if e == h || e == f {
+                continue;
+            }
+
+            let df = xbr_distance;
+            let edge_e = df(e, c) + df(e, g) + df(i, h5) + df(i, f4) + (df(h, f) << 2);
+            let edge_i = df(h, d) + df(h, i5) + df(f, i4) + df(f, b) + (df(e, i) << 2);
+            if edge_e > edge_i {
+                continue;
+            }
+            let px = if df(e, f) <= df(e, h) { f } else { h };
+            let (n1, n2, n3) = (slot(1, -1), slot(-1, 1), slot(1, 1));
+
+            let sloped = edge_e < edge_i
+                && ((!xbr_equal(f, b) && !xbr_equal(h, d))
+                    || (xbr_equal(e, i) && !xbr_equal(f, i4) && !xbr_equal(h, i5))
+                    || xbr_equal(e, g)
+                    || xbr_equal(e, c));
+            let (ke, ki) = (df(f, g), df(h, c));
+            let left = sloped && ke << 1 <= ki && e != g && d != g;
+            let up = sloped && ke >= ki << 1 && e != c && b != c;
+            match (left, up) {
+                (true, true) => {
+                    block[n3] = xbr_blend(block[n3], px, 7);
+                    block[n2] = xbr_blend(block[n2], px, 2);
+                    block[n1] = block[n2];
+                }
+                (true, false) => {
+                    block[n3] = xbr_blend(block[n3], px, 6);
+                    block[n2] = xbr_blend(block[n2], px, 2);
+                }
+                (false, true) => {
+                    block[n3] = xbr_blend(block[n3], px, 6);
+                    block[n1] = xbr_blend(block[n1], px, 2);
+                }
+                (false, false) => block[n3] = xbr_blend(block[n3], px, 4),
+            }
+        }
+
+        for (n, pixel) in block.into_iter().enumerate() {
+            out.put_pixel(x * 2 + n as u32 % 2, y * 2 + n as u32 / 2, pixel);
+        }
+    }
+    out
+}
+
+pub fn scale_pixel_art(
+    img: &RgbaImage,
+    factor: u32,
+    algorithm: PixelArtAlgorithm,
+) -> Result<RgbaImage, AppError> {
+    let largest = img.width().max(img.height()) as u64 * factor as u64;
+    if largest > MAX_PIXEL_ART_OUTPUT as u64 {
+        return Err(AppError::invalid(
+            "factor",
+            format!("Scaled image would exceed {} pixels per side", MAX_PIXEL_ART_OUTPUT),
+        ));
+    }
+    match (algorithm, factor) {
+        (_, 0) => Err(AppError::invalid("factor", "Scale factor must be at least 1")),
+        (_, 1) => Ok(img.clone()),
+        (PixelArtAlgorithm::Nearest, _) => Ok(imageops::resize(
+            img,
+            img.width() * factor,
+            img.height() * factor,
+            FilterType::Nearest,
+        )),
+        (PixelArtAlgorithm::ScaleNx, 2) => Ok(scale2x(img)),
+        (PixelArtAlgorithm::ScaleNx, 3) => Ok(scale3x(img)),
+        (PixelArtAlgorithm::ScaleNx, 4) => Ok(scale2x(&scale2x(img))),
+        (PixelArtAlgorithm::ScaleNx, _) => {
+            Err(AppError::invalid("factor", "Scale2x/Scale3x only supports 2x, 3x and 4x"))
+        }
+        (PixelArtAlgorithm::Xbr, 2) => Ok(xbr2x(img)),
+        (PixelArtAlgorithm::Xbr, 4) => Ok(xbr2x(&xbr2x(img))),
+        (PixelArtAlgorithm::Xbr, _) => {
+            Err(AppError::invalid("factor", "xBR only supports 2x and 4x"))
+        }
+    }
+}
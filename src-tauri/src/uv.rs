@@ -0,0 +1,37 @@
+// Binary sidecar with each frame's normalized UVs, little-endian:
+//   magic "TPUV", u32 version (1), u32 frame count, then per frame in name
+//   order: u16 name length, UTF-8 name, f32 u0, v0, u1, v1
+const MAGIC: &[u8; 4] = b"TPUV";
+const VERSION: u32 = 1;
+
+// (u0, v0) is the frame's top-left and (u1, v1) its bottom-right corner, as
+// fractions of the atlas size
+pub fn normalized(x: u32, y: u32, w: u32, h: u32, atlas: (u32, u32)) -> [f32; 4] {
+    let (aw, ah) = (atlas.0.max(1) as f32, atlas.1.max(1) as f32);
+    [
+        x as f32 / aw,
+        y as f32 / ah,
+        (x + w) as f32 / aw,
+        (y + h) as f32 / ah,
+    ]
+}
+
+pub fn encode_sidecar(frames: &[(&str, [f32; 4])]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.extend_from_slice(&(frames.len() as u32).to_le_bytes());
+    for &(name, uv) in frames {
+        // Names longer than u16 are cut at a character boundary
+        let mut end = name.len().min(u16::MAX as usize);
+        while !name.is_char_boundary(end) {
+            end -= 1;
+        }
+        out.extend_from_slice(&(end as u16).to_le_bytes());
+        out.extend_from_slice(&name.as_bytes()[..end]);
+        for v in uv {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+    out
+}
@@ -0,0 +1,177 @@
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+use crate::atlas_packer::{load_sprite_dir, pack_atlas, AtlasOptions, AtlasOutput, DirImport};
+use crate::budget::{self, PageStats};
+use crate::error::AppError;
+use crate::incremental;
+use crate::sidecars::{precompressed_json, sidecars};
+
+#[derive(Deserialize)]
+pub struct WatchConfig {
+    // Source folders whose images make up the atlas
+    pub folders: Vec<String>,
+    pub padding: u32,
+    #[serde(default)]
+    pub options: AtlasOptions,
+    // Sub-folder handling and frame naming
+    #[serde(default)]
+    pub import: DirImport,
+    // Write atlas.<ext> + atlas.json here after every re-pack
+    #[serde(default)]
+    pub output_dir: Option<String>,
+    // Quiet period after the last change before re-packing
+    #[serde(default = "default_debounce_ms")]
+    pub debounce_ms: u64,
+}
+
+fn default_debounce_ms() -> u64 {
+    300
+}
+
+#[derive(Serialize, Clone)]
+struct WatchRepacked {
+    sprite_count: usize,
+    atlas: AtlasOutput,
+    written: Vec<String>,
+}
+
+// Managed state: the running watcher, if any. Dropping the watcher closes
+// the event channel, which ends the re-pack thread.
+#[derive(Default)]
+pub struct WatchState {
+    watcher: Mutex<Option<RecommendedWatcher>>,
+}
+
+fn repack(config: &WatchConfig) -> Result<WatchRepacked, AppError> {
+    let mut sprites = Vec::new();
+    for folder in &config.folders {
+        sprites.extend(load_sprite_dir(Path::new(folder), &config.import)?);
+    }
+
+    let image_name = format!("atlas.{}", config.options.format.extension());
+    let mut output = pack_atlas(&sprites, config.padding, &config.options, &image_name)?;
+
+    // Sidecar bytes aren't part of the event payload
+    let mut extras =
+        precompressed_json(&image_name, &output.json, &config.options.precompress_json)?;
+    extras.extend(sidecars(
+        &image_name,
+        output.companions.clone(),
+        output.uv_bytes.take(),
+        output.binary_bytes.take(),
+        output.tsx.clone(),
+    ));
+
+    // An over-budget pack is reported as a watch error and nothing is written
+    if let Some(budget) = &config.options.budget {
+        let (width, height) = incremental::atlas_size(&output.json)?;
+        let page = PageStats {
+            image: image_name.clone(),
+            width,
+            height,
+            image_bytes: output.image_bytes.len(),
+            total_bytes: output.image_bytes.len()
+                + output.json.len()
+                + extras.iter().map(|(_, data)| data.len()).sum::<usize>(),
+        };
+        budget::check(budget, &[page]).into_result()?;
+    }
+
+    let mut written = Vec::new();
+    if let Some(dir) = &config.output_dir {
+        let dir = PathBuf::from(dir);
+        std::fs::create_dir_all(&dir)?;
+        let files = [
+            (image_name.as_str(), output.image_bytes.as_slice()),
+            ("atlas.json", output.json.as_bytes()),
+        ]
+        .into_iter()
+        .chain(
+            extras
+                .iter()
+                .map(|(name, data)| (name.as_str(), data.as_slice())),
+        );
+        for (name, data) in files {
+            let path = dir.join(name);
+            std::fs::write(&path, data)?;
+            written.push(path.to_string_lossy().into_owned());
+        }
+    }
+
+    Ok(WatchRepacked {
+        sprite_count: sprites.len(),
+        atlas: output,
+        written,
+    })
+}
+
+fn emit_repack(app: &AppHandle, config: &WatchConfig) {
+    let _ = match repack(config) {
+        Ok(result) => app.emit("watch-repacked", result),
+        Err(e) => app.emit("watch-error", e),
+    };
+}
+
+// Replaces any running watcher. Packs once immediately, then again after
+// each burst of file changes in the watched folders. Packing runs on `pool`.
+pub fn start(
+    app: AppHandle,
+    state: &WatchState,
+    pool: Arc<rayon::ThreadPool>,
+    config: WatchConfig,
+) -> Result<(), AppError> {
+    let (tx, rx) = mpsc::channel();
+    let output_dir = config.output_dir.as_ref().map(PathBuf::from);
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else { return };
+        // Our own writes to the output folder must not trigger another pack
+        let own_output = output_dir
+            .as_ref()
+            .is_some_and(|dir| event.paths.iter().all(|p| p.starts_with(dir)));
+        if !event.kind.is_access() && !own_output {
+            let _ = tx.send(());
+        }
+    })
+    .map_err(AppError::io)?;
+
+    let mode = if config.import.recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    for folder in &config.folders {
+        watcher
+            .watch(Path::new(folder), mode)
+            .map_err(|e| AppError::io(format!("{}: {}", folder, e)))?;
+    }
+    *state.watcher.lock().unwrap() = Some(watcher);
+
+    std::thread::spawn(move || {
+        let debounce = Duration::from_millis(config.debounce_ms);
+        pool.install(|| emit_repack(&app, &config));
+
+        while rx.recv().is_ok() {
+            // Editors often write a file several times; wait until it settles
+            loop {
+                match rx.recv_timeout(debounce) {
+                    Ok(()) => continue,
+                    Err(mpsc::RecvTimeoutError::Timeout) => break,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                }
+            }
+            pool.install(|| emit_repack(&app, &config));
+        }
+    });
+
+    Ok(())
+}
+
+pub fn stop(state: &WatchState) {
+    state.watcher.lock().unwrap().take();
+}
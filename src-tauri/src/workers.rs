@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::error::AppError;
+use crate::session::write_json;
+
+// Upper bound on a configured pool, well past any desktop core count
+const MAX_THREADS: usize = 256;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WorkerSettings {
+    // Worker threads for packing and batch jobs; None uses one per core
+    pub threads: Option<usize>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkerStatus {
+    pub threads: Option<usize>,
+    // Threads the pool runs with now
+    pub active: usize,
+    pub available_cores: usize,
+}
+
+fn build_pool(threads: Option<usize>) -> Result<rayon::ThreadPool, AppError> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads.unwrap_or(0))
+        .thread_name(|i| format!("texturepack-worker-{}", i))
+        .build()
+        .map_err(|e| AppError::invalid("threads", e))
+}
+
+// Managed state: the thread pool that packing and batch commands run their
+// parallel work on, sized by workers.json in the app config directory so a
+// low-core machine can keep the app from taking every core
+pub struct WorkerPool {
+    path: PathBuf,
+    settings: Mutex<WorkerSettings>,
+    pool: Mutex<Arc<rayon::ThreadPool>>,
+}
+
+impl WorkerPool {
+    pub fn load(config_dir: PathBuf) -> Result<Self, AppError> {
+        let path = config_dir.join("workers.json");
+        let settings: WorkerSettings = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        // A bad saved value falls back to one thread per core
+        let pool = build_pool(settings.threads).or_else(|_| build_pool(None))?;
+
+        Ok(WorkerPool {
+            path,
+            settings: Mutex::new(settings),
+            pool: Mutex::new(Arc::new(pool)),
+        })
+    }
+
+    pub fn status(&self) -> WorkerStatus {
+        WorkerStatus {
+            threads: self.settings.lock().unwrap().threads,
+            active: self.pool.lock().unwrap().current_num_threads(),
+            available_cores: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+        }
+    }
+
+    // Resizes the pool and saves the setting. Jobs already running finish on
+    // the pool they started on.
+    pub fn set_threads(&self, threads: Option<usize>) -> Result<WorkerStatus, AppError> {
+        if let Some(threads) = threads.filter(|&t| t == 0 || t > MAX_THREADS) {
+            return Err(AppError::invalid(
+                "threads",
+                format!(
+                    "Thread count must be 1 to {} (got {})",
+                    MAX_THREADS, threads
+                ),
+            ));
+        }
+        let pool = build_pool(threads)?;
+        let settings = WorkerSettings { threads };
+        write_json(&self.path, &settings)?;
+        *self.settings.lock().unwrap() = settings;
+        *self.pool.lock().unwrap() = Arc::new(pool);
+        Ok(self.status())
+    }
+
+    // The current pool, for work that outlives a single command
+    pub fn pool(&self) -> Arc<rayon::ThreadPool> {
+        self.pool.lock().unwrap().clone()
+    }
+
+    // Runs `op` on the pool, so its rayon iterators use at most the
+    // configured number of threads
+    pub fn install<R: Send>(&self, op: impl FnOnce() -> R + Send) -> R {
+        self.pool().install(op)
+    }
+}
@@ -0,0 +1,131 @@
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use std::io::Write;
+
+use crate::error::AppError;
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATE: u16 = 8;
+// Version 2.0: deflate, folders
+const VERSION: u16 = 20;
+// General purpose flag bit 11: names are UTF-8
+const FLAG_UTF8: u16 = 1 << 11;
+// 1980-01-01 00:00, the earliest DOS date, so the same build gives the same
+// archive
+const DOS_TIME: u16 = 0;
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+struct Entry {
+    name: String,
+    method: u16,
+    crc: u32,
+    compressed_size: u32,
+    size: u32,
+    offset: u32,
+}
+
+// Minimal single-disk ZIP writer without ZIP64, enough for handing build
+// output over as one file
+#[derive(Default)]
+pub struct ZipWriter {
+    out: Vec<u8>,
+    entries: Vec<Entry>,
+}
+
+fn too_large() -> AppError {
+    AppError::encode("Archive exceeds the 4 GB ZIP limit")
+}
+
+fn to_u32(n: usize) -> Result<u32, AppError> {
+    u32::try_from(n).map_err(|_| too_large())
+}
+
+impl ZipWriter {
+    // Deflates `data`, or stores it when that doesn't help (PNG, WebP, ...)
+    pub fn add(&mut self, name: &str, data: &[u8]) -> Result<(), AppError> {
+        if self.entries.iter().any(|e| e.name == name) {
+            return Err(AppError::encode(format!(
+                "Duplicate archive entry {}",
+                name
+            )));
+        }
+        if self.entries.len() == u16::MAX as usize {
+            return Err(AppError::encode("Too many files for a ZIP archive"));
+        }
+
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data)?;
+        let deflated = encoder.finish()?;
+        let (method, body) = if deflated.len() < data.len() {
+            (METHOD_DEFLATE, deflated.as_slice())
+        } else {
+            (METHOD_STORED, data)
+        };
+
+        let entry = Entry {
+            name: name.to_string(),
+            method,
+            crc: crc32fast::hash(data),
+            compressed_size: to_u32(body.len())?,
+            size: to_u32(data.len())?,
+            offset: to_u32(self.out.len())?,
+        };
+        let out = &mut self.out;
+        out.extend_from_slice(&LOCAL_HEADER.to_le_bytes());
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&FLAG_UTF8.to_le_bytes());
+        out.extend_from_slice(&entry.method.to_le_bytes());
+        out.extend_from_slice(&DOS_TIME.to_le_bytes());
+        out.extend_from_slice(&DOS_DATE.to_le_bytes());
+        out.extend_from_slice(&entry.crc.to_le_bytes());
+        out.extend_from_slice(&entry.compressed_size.to_le_bytes());
+        out.extend_from_slice(&entry.size.to_le_bytes());
+        out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes());
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(body);
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    // Appends the central directory and returns the archive bytes
+    pub fn finish(mut self) -> Result<Vec<u8>, AppError> {
+        let directory_offset = to_u32(self.out.len())?;
+        let out = &mut self.out;
+        for entry in &self.entries {
+            out.extend_from_slice(&CENTRAL_HEADER.to_le_bytes());
+            // Made by (MS-DOS attributes) and needed to extract
+            out.extend_from_slice(&VERSION.to_le_bytes());
+            out.extend_from_slice(&VERSION.to_le_bytes());
+            out.extend_from_slice(&FLAG_UTF8.to_le_bytes());
+            out.extend_from_slice(&entry.method.to_le_bytes());
+            out.extend_from_slice(&DOS_TIME.to_le_bytes());
+            out.extend_from_slice(&DOS_DATE.to_le_bytes());
+            out.extend_from_slice(&entry.crc.to_le_bytes());
+            out.extend_from_slice(&entry.compressed_size.to_le_bytes());
+            out.extend_from_slice(&entry.size.to_le_bytes());
+            out.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+            // Extra field, comment, disk number, internal / external attributes
+            out.extend_from_slice(&[0; 12]);
+            out.extend_from_slice(&entry.offset.to_le_bytes());
+            out.extend_from_slice(entry.name.as_bytes());
+        }
+        let directory_size = to_u32(out.len())? - directory_offset;
+
+        let count = (self.entries.len() as u16).to_le_bytes();
+        out.extend_from_slice(&END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+        // This disk, disk with the directory
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&count);
+        out.extend_from_slice(&count);
+        out.extend_from_slice(&directory_size.to_le_bytes());
+        out.extend_from_slice(&directory_offset.to_le_bytes());
+        // Comment length
+        out.extend_from_slice(&[0; 2]);
+        Ok(self.out)
+    }
+}
@@ -0,0 +1,41 @@
+// Mirrors AppError in src-tauri/src/error.rs
+export type AppError =
+  | { kind: "io"; message: string }
+  | { kind: "decode"; message: string }
+  | { kind: "encode"; message: string }
+  | { kind: "packing-failed"; message: string; needed_size: number }
+  | { kind: "invalid-input"; field: string; message: string }
+  | { kind: "budget-exceeded"; message: string; violations: BudgetViolation[] };
+
+export type BudgetViolation = {
+  limit: string;
+  page: string | null;
+  allowed: number;
+  actual: number;
+};
+
+function isAppError(error: unknown): error is AppError {
+  return typeof error === "object" && error !== null && "kind" in error && "message" in error;
+}
+
+export function describeError(error: unknown): string {
+  if (!isAppError(error)) {
+    return String(error);
+  }
+  switch (error.kind) {
+    case "io":
+      return `读写文件失败，请检查路径和权限 (${error.message})`;
+    case "decode":
+      return `无法读取图片，文件可能已损坏或格式不受支持 (${error.message})`;
+    case "encode":
+      return `生成输出文件失败 (${error.message})`;
+    case "packing-failed":
+      return `精灵放不下，至少需要约 ${error.needed_size}x${error.needed_size} 的图集，请增大尺寸或减少图片`;
+    case "invalid-input":
+      return `参数 ${error.field} 无效: ${error.message}`;
+    case "budget-exceeded":
+      return `导出超出预算：${error.violations
+        .map((v) => `${v.page ? `${v.page} ` : ""}${v.limit} 为 ${v.actual}（上限 ${v.allowed}）`)
+        .join("；")}`;
+  }
+}
@@ -1,6 +1,7 @@
 import { useState, useEffect, useRef, useCallback } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { open } from "@tauri-apps/plugin-dialog";
+import { describeError } from "../../appError";
 import "./styles.css";
 
 interface SpriteFrame {
@@ -692,7 +693,7 @@ export function AtlasPacker({ importedFrames, importedFramesByAction, onClearImp
       }
     } catch (error) {
       console.error("生成 Atlas 失败:", error);
-      alert(`生成 Atlas 失败: ${error}`);
+      alert(`生成 Atlas 失败: ${describeError(error)}`);
     } finally {
       setIsGenerating(false);
     }